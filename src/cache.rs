@@ -0,0 +1,390 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::utils::unix_nanos_to_date;
+use mbinary::params::RetrieveParams;
+use prettytable::{format, Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/// How a historical retrieval uses the local cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Serve from the cache when possible, store what is fetched.
+    Use,
+    /// Always fetch and replace the cached copy.
+    Refresh,
+    /// Always fetch and leave the cache untouched.
+    Bypass,
+}
+
+impl CachePolicy {
+    pub fn new(no_cache: bool, refresh: bool) -> Result<Self> {
+        match (no_cache, refresh) {
+            (false, false) => Ok(CachePolicy::Use),
+            (false, true) => Ok(CachePolicy::Refresh),
+            (true, false) => Ok(CachePolicy::Bypass),
+            (true, true) => Err(error!(
+                CustomError,
+                "Use only one of --no-cache or --refresh."
+            )),
+        }
+    }
+
+    pub fn list() -> Vec<&'static str> {
+        vec!["use", "refresh", "bypass"]
+    }
+}
+
+impl TryFrom<&str> for CachePolicy {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "use" => Ok(CachePolicy::Use),
+            "refresh" => Ok(CachePolicy::Refresh),
+            "bypass" => Ok(CachePolicy::Bypass),
+            _ => Err(error!(CustomError, "Invalid cache policy : {}", value)),
+        }
+    }
+}
+
+/// Sidecar describing a cached file, stored next to it as <key>.json.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub key: String,
+    pub params: RetrieveParams,
+    pub size: u64,
+    pub created: i64,
+    pub last_used: i64,
+}
+
+/// Content addressed store of historical retrievals, keyed by the server and the normalized request.
+#[derive(Debug, Clone)]
+pub struct HistoricalCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Base url of the server the data is retrieved from.
+    url: String,
+}
+
+fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp_nanos() as i64
+}
+
+impl HistoricalCache {
+    pub fn new(dir: PathBuf, max_bytes: u64, url: &str) -> Self {
+        HistoricalCache {
+            dir,
+            max_bytes,
+            url: url.to_string(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn normalized(params: &RetrieveParams) -> String {
+        let mut symbols = params.symbols.clone();
+        symbols.sort();
+        symbols.dedup();
+
+        format!(
+            "symbols={};schema={};dataset={};stype={};start={};end={}",
+            symbols.join(","),
+            params.schema,
+            params.dataset,
+            params.stype,
+            params.start_ts,
+            params.end_ts
+        )
+    }
+
+    /// Same request, same key, regardless of symbol order or repeats.
+    pub fn request_key(params: &RetrieveParams) -> String {
+        hex::encode(Sha256::digest(Self::normalized(params).as_bytes()))
+    }
+
+    /// Key of the request on this cache's server, servers never share entries.
+    pub fn key(&self, params: &RetrieveParams) -> String {
+        let normalized = format!("url={};{}", self.url, Self::normalized(params));
+        hex::encode(Sha256::digest(normalized.as_bytes()))
+    }
+
+    /// Ranges reaching into the future may still grow, so only closed ranges are cached.
+    pub fn cacheable(params: &RetrieveParams) -> bool {
+        params.end_ts <= now()
+    }
+
+    fn data_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", key))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn write_entry(&self, entry: &CacheEntry) -> Result<()> {
+        fs::write(
+            self.entry_path(&entry.key),
+            serde_json::to_string_pretty(entry)?,
+        )?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        for path in [self.data_path(key), self.entry_path(key)] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the cached file for `params` to `output`, returns false on a miss.
+    pub fn fetch(&self, params: &RetrieveParams, output: &Path) -> Result<bool> {
+        let key = self.key(params);
+        let data = self.data_path(&key);
+        let entry = match fs::read_to_string(self.entry_path(&key)) {
+            Ok(json) => match serde_json::from_str::<CacheEntry>(&json) {
+                Ok(entry) => entry,
+                Err(_) => {
+                    self.remove(&key)?;
+                    return Ok(false);
+                }
+            },
+            Err(_) => return Ok(false),
+        };
+        if !data.exists() {
+            self.remove(&key)?;
+            return Ok(false);
+        }
+
+        fs::copy(&data, output)?;
+        self.write_entry(&CacheEntry {
+            last_used: now(),
+            ..entry
+        })?;
+        Ok(true)
+    }
+
+    /// Stores a copy of `file` for `params` and evicts least recently used entries
+    /// past the size limit. Files larger than the limit are not stored.
+    pub fn store(&self, params: &RetrieveParams, file: &Path) -> Result<Option<CacheEntry>> {
+        let size = fs::metadata(file)?.len();
+        if size > self.max_bytes {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let key = self.key(params);
+        fs::copy(file, self.data_path(&key))?;
+
+        let created = now();
+        let entry = CacheEntry {
+            key,
+            params: params.clone(),
+            size,
+            created,
+            last_used: created,
+        };
+        self.write_entry(&entry)?;
+        self.evict(self.max_bytes)?;
+
+        Ok(Some(entry))
+    }
+
+    /// Entries most recently used first. Unreadable sidecars are reported and skipped.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let entry = fs::read_to_string(&path)
+                .map_err(Error::from)
+                .and_then(|json| Ok(serde_json::from_str::<CacheEntry>(&json)?));
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!("Skipping cache entry {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            if self.data_path(&entry.key).exists() {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used));
+
+        Ok(entries)
+    }
+
+    /// Drops least recently used entries until the cache fits in `max_bytes`.
+    /// Returns the number of entries removed.
+    pub fn evict(&self, max_bytes: u64) -> Result<usize> {
+        let entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut removed = 0;
+
+        for entry in entries.iter().rev() {
+            if total <= max_bytes {
+                break;
+            }
+            self.remove(&entry.key)?;
+            total -= entry.size;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Removes every entry, returns the number of entries and bytes freed.
+    pub fn clear(&self) -> Result<(usize, u64)> {
+        let entries = self.entries()?;
+        let freed = entries.iter().map(|entry| entry.size).sum();
+        for entry in &entries {
+            self.remove(&entry.key)?;
+        }
+        Ok((entries.len(), freed))
+    }
+}
+
+pub fn cache_table(entries: &[CacheEntry]) -> Result<Table> {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.add_row(Row::new(
+        [
+            "Key",
+            "Symbols",
+            "Schema",
+            "Dataset",
+            "Stype",
+            "Start",
+            "End",
+            "Bytes",
+            "Last Used",
+        ]
+        .iter()
+        .map(|title| Cell::new(title).style_spec("bFg"))
+        .collect(),
+    ));
+
+    for entry in entries {
+        let params = &entry.params;
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.key[..12]).style_spec("Fw"),
+            Cell::new(&params.symbols.join(",")).style_spec("Fg"),
+            Cell::new(&params.schema.to_string()),
+            Cell::new(&params.dataset.to_string()),
+            Cell::new(&params.stype.to_string()),
+            Cell::new(&unix_nanos_to_date(params.start_ts)?),
+            Cell::new(&unix_nanos_to_date(params.end_ts)?),
+            Cell::new(&entry.size.to_string()),
+            Cell::new(&unix_nanos_to_date(entry.last_used)?),
+        ]));
+    }
+
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mbinary::enums::{Dataset, Schema, Stype};
+    use serial_test::serial;
+
+    fn params(symbols: &[&str], start_ts: i64) -> RetrieveParams {
+        RetrieveParams {
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            start_ts,
+            end_ts: start_ts + 1_000,
+            schema: Schema::Mbp1,
+            dataset: Dataset::Futures,
+            stype: Stype::Raw,
+        }
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let cache = HistoricalCache::new(PathBuf::from("cache"), 0, "http://127.0.0.1:8080");
+        let other = HistoricalCache::new(PathBuf::from("cache"), 0, "http://10.0.0.2:8080");
+        let key = cache.key(&params(&["HE.n.0", "ZC.n.0"], 0));
+
+        // Validate
+        assert_eq!(key, cache.key(&params(&["ZC.n.0", "HE.n.0", "ZC.n.0"], 0)));
+        assert_ne!(key, cache.key(&params(&["HE.n.0"], 0)));
+        assert_ne!(key, cache.key(&params(&["HE.n.0", "ZC.n.0"], 1)));
+        assert_ne!(key, other.key(&params(&["HE.n.0", "ZC.n.0"], 0)));
+        assert_eq!(key.len(), 64);
+        assert_eq!(
+            HistoricalCache::request_key(&params(&["HE.n.0", "ZC.n.0"], 0)),
+            HistoricalCache::request_key(&params(&["ZC.n.0", "HE.n.0"], 0))
+        );
+    }
+
+    #[test]
+    fn test_cache_policy() -> Result<()> {
+        assert_eq!(CachePolicy::new(false, false)?, CachePolicy::Use);
+        assert_eq!(CachePolicy::new(false, true)?, CachePolicy::Refresh);
+        assert_eq!(CachePolicy::new(true, false)?, CachePolicy::Bypass);
+        assert!(CachePolicy::new(true, true).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_cache_store_fetch_evict() -> Result<()> {
+        let dir = PathBuf::from("tests/data/test_cache");
+        let file = PathBuf::from("tests/data/test_cache_input.bin");
+        let output = PathBuf::from("tests/data/test_cache_output.bin");
+        fs::write(&file, vec![7u8; 100])?;
+        let cache = HistoricalCache::new(dir.clone(), 250, "http://127.0.0.1:8080");
+
+        // Test
+        let miss = cache.fetch(&params(&["HE.n.0"], 0), &output)?;
+        cache.store(&params(&["HE.n.0"], 0), &file)?;
+        cache.store(&params(&["HE.n.0"], 1), &file)?;
+        let hit = cache.fetch(&params(&["HE.n.0"], 0), &output)?;
+        cache.store(&params(&["HE.n.0"], 2), &file)?;
+
+        // Validate
+        assert!(!miss);
+        assert!(hit);
+        assert_eq!(fs::read(&output)?, vec![7u8; 100]);
+
+        // Least recently used entry was evicted
+        let entries = cache.entries()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].params.start_ts, 2);
+        assert_eq!(entries[1].params.start_ts, 0);
+
+        // Corrupt sidecars are skipped
+        fs::write(dir.join("corrupt.json"), "{")?;
+        assert_eq!(cache.entries()?.len(), 2);
+        fs::remove_file(dir.join("corrupt.json"))?;
+
+        // Larger than the limit is not stored
+        let small = HistoricalCache::new(dir.clone(), 50, "http://127.0.0.1:8080");
+        assert!(small.store(&params(&["ZC.n.0"], 0), &file)?.is_none());
+
+        assert_eq!(cache.clear()?, (2, 200));
+        assert!(cache.entries()?.is_empty());
+
+        // Cleanup
+        fs::remove_dir_all(&dir)?;
+        fs::remove_file(&file)?;
+        fs::remove_file(&output)?;
+
+        Ok(())
+    }
+}
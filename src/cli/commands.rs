@@ -1,4 +1,5 @@
 pub mod backtest;
+pub mod cache;
 pub mod historical;
 pub mod instrument;
 pub mod live;
@@ -9,6 +10,7 @@ pub mod vendors;
 use crate::commands::TaskManager;
 use crate::error::Result;
 use backtest::BacktestArgs;
+use cache::CacheArgs;
 use clap::{Parser, Subcommand};
 use historical::HistoricalArgs;
 use instrument::InstrumentArgs;
@@ -42,6 +44,8 @@ pub enum Commands {
     Midas(MidasArgs),
     /// Commands for Databento source
     Databento(DatabentoArgs),
+    /// Local cache of historical data.
+    Cache(CacheArgs),
 }
 
 // #[async_trait]
@@ -56,6 +60,7 @@ impl Commands {
             Commands::Dashboard => Ok(context.launch_dashboard()),
             Commands::Databento(args) => args.subcommand.process_command(context).await,
             Commands::Midas(args) => args.subcommand.process_command(context).await,
+            Commands::Cache(args) => args.subcommand.process_command(context).await,
         }
     }
 }
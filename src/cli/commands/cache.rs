@@ -0,0 +1,33 @@
+use crate::commands::TaskManager;
+use crate::error::Result;
+use clap::{Args, Subcommand};
+use std::fmt::Debug;
+
+#[derive(Debug, Args)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub subcommand: CacheCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommands {
+    /// List cached historical retrievals, most recently used first.
+    List,
+    /// Remove every cached historical retrieval.
+    Clear,
+}
+
+impl CacheCommands {
+    pub async fn process_command(&self, context: TaskManager) -> Result<()> {
+        match self {
+            CacheCommands::List => {
+                context.list_cache().await;
+            }
+            CacheCommands::Clear => {
+                context.clear_cache().await;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,9 +1,17 @@
+use crate::cache::CachePolicy;
 use crate::commands::TaskManager;
-use crate::error::Result;
+use crate::dates::DateParser;
+use crate::error;
+use crate::error::{Error, Result};
+use crate::retrieve::ChunkOptions;
+use crate::vendors::midas::export::{ExportFormat, ExportOptions};
+use crate::vendors::midas::plot::{ChartKind, PlotOptions};
+use crate::vendors::midas::slice::SliceFilter;
 use clap::Args;
 use mbinary::enums::{Dataset, Schema, Stype};
 use mbinary::params::RetrieveParams;
 use std::fmt::Debug;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Debug, Args)]
@@ -12,12 +20,12 @@ pub struct HistoricalArgs {
     #[arg(long, value_delimiter = ',')]
     pub symbols: Vec<String>,
 
-    /// Start date in YYYY-MM-DD HH:MM:SS format.
-    #[arg(long)]
+    /// Start ex. 2024-01-02, 2024-01-02 09:30:00, 2024-01-02T09:30:00-05:00, -5d
+    #[arg(long, allow_hyphen_values = true)]
     pub start: String,
 
-    /// End date in YYYY-MM-DD HH:MM:SS format.
-    #[arg(long)]
+    /// End ex. 2024-01-03, today
+    #[arg(long, allow_hyphen_values = true)]
     pub end: String,
 
     /// Schema ex. Mbp1, Ohlcv
@@ -32,9 +40,38 @@ pub struct HistoricalArgs {
     #[arg(long)]
     pub stype: String,
 
-    /// File path to save the downloaded binary data.
+    /// File path to save the downloaded data, - writes to stdout.
     #[arg(long)]
     pub file_path: String,
+
+    /// Output format ex. mbinary, csv, jsonl
+    #[arg(long, default_value = "mbinary")]
+    pub format: String,
+
+    /// Timezone of dates without an offset and of csv and jsonl timestamps ex. America/New_York,
+    /// defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+
+    /// Chart the retrieved prices ex. line, candles
+    #[arg(long)]
+    pub plot: Option<String>,
+
+    /// Always fetch from the server and leave the local cache untouched.
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Always fetch from the server and replace the cached copy.
+    #[arg(long)]
+    pub refresh: bool,
+
+    /// Length of each request the retrieval is split into ex. 6h, 1d
+    #[arg(long, default_value = "1d")]
+    pub chunk: String,
+
+    /// Chunk requests in flight at once.
+    #[arg(long, default_value_t = 4)]
+    pub concurrency: usize,
 }
 
 impl HistoricalArgs {
@@ -44,17 +81,49 @@ impl HistoricalArgs {
         let dataset = Dataset::from_str(&self.dataset)?;
         let stype = Stype::from_str(&self.stype)?;
 
-        // Attempt to create RetrieveParams and handle errors gracefully
-        let params = RetrieveParams::new(
-            self.symbols.clone(),
-            &self.start,
-            &self.end,
+        let dates = DateParser::new(self.tz.as_deref())?;
+        let params = RetrieveParams {
+            symbols: self.symbols.clone(),
+            start_ts: dates.parse(&self.start)?,
+            end_ts: dates.parse(&self.end)?,
             schema,
             dataset,
             stype,
-        )?;
+        };
+
+        let policy = CachePolicy::new(self.no_cache, self.refresh)?;
+        let options = ChunkOptions::new(&self.chunk, self.concurrency)?;
+        let export = ExportOptions::new(&self.format, self.tz.as_deref())?;
+
+        let plot = match &self.plot {
+            Some(_) if self.file_path == "-" || export.format != ExportFormat::Mbinary => {
+                return Err(error!(
+                    CustomError,
+                    "Plotting needs an mbinary file, not {} to {}.", self.format, self.file_path
+                ));
+            }
+            Some(kind) => Some(PlotOptions::new(
+                ChartKind::try_from(kind.as_str())?,
+                None,
+                15,
+            )?),
+            None => None,
+        };
 
-        context.get_historical(params, &self.file_path).await;
+        if context
+            .get_historical(params, &self.file_path, policy, options, export)
+            .await
+        {
+            if let Some(options) = plot {
+                context
+                    .plot_mbinary_file(
+                        PathBuf::from(&self.file_path),
+                        SliceFilter::default(),
+                        options,
+                    )
+                    .await;
+            }
+        }
 
         Ok(())
     }
@@ -1,13 +1,27 @@
 use crate::commands::TaskManager;
+use crate::dates::DateParser;
+use crate::error;
 use crate::error::{Error, Result};
-use crate::utils::date_to_unix_nanos;
+use crate::instruments::futures::{parse_months, parse_years, FuturesOptions};
+use crate::instruments::import::ImportOptions;
+use crate::instruments::options::{
+    definition_file, parse_moneyness, ChainFilter, ChainOptions, OptionKind,
+};
+use crate::instruments::plan::PlanOptions;
+use crate::instruments::search::{ticker_pattern, InstrumentFilter};
+use crate::table::TableOptions;
+use crate::utils::get_ticker_file;
+use crate::vendors::midas::coverage::CoverageOptions;
+use crate::vendors::midas::report::ReportFormat;
+use chrono::TimeZone;
 use clap::{Args, Subcommand};
 use dbn;
-use mbinary::enums::Dataset;
+use mbinary::enums::{Dataset, Schema};
 use mbinary::symbols::Instrument;
 use mbinary::vendors::{DatabentoData, VendorData, Vendors};
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 fn parse_vendor_data(s: &str) -> Result<HashMap<String, String>> {
@@ -70,6 +84,20 @@ pub enum InstrumentCommands {
     Get(GetArgs),
     Delete(DeleteArgs),
     Update(UpdateArgs),
+    /// Show what the server holds per instrument and the sessions missing from it
+    Coverage(CoverageArgs),
+    /// Create or update instruments from a .toml, .json or .csv file
+    Import(ImportArgs),
+    /// Write instruments to a .toml, .json or .csv file
+    Export(ExportArgs),
+    /// Show the creates, updates, deactivations and deletes that sync the server to a manifest
+    Plan(PlanArgs),
+    /// Sync the server to a manifest after confirmation
+    Apply(ApplyArgs),
+    /// Build a futures contract chain ex. --root HE --months GJKMNQVZ --years 2024-2026
+    GenerateFutures(GenerateFuturesArgs),
+    /// Build option instruments from a Databento OPRA definition file ex. --underlying SPY --date 2024-06-03
+    OptionChain(OptionChainArgs),
 }
 
 #[derive(Debug, Args)]
@@ -90,10 +118,10 @@ pub struct CreateArgs {
     #[arg(long, value_parser = parse_vendor_data)]
     pub vendor_data: HashMap<String, String>,
     /// first date available in database
-    #[arg(long)]
+    #[arg(long, allow_hyphen_values = true)]
     pub first_available: String,
     /// last date available in database
-    #[arg(long)]
+    #[arg(long, allow_hyphen_values = true)]
     pub expiration_date: String,
     /// Conntinuous status
     #[arg(long)]
@@ -101,6 +129,12 @@ pub struct CreateArgs {
     /// Active status
     #[arg(long)]
     pub active: bool,
+    /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+    /// Timezone of the expiration date when it has no offset.
+    #[arg(long, default_value = "America/New_York")]
+    pub expiry_tz: String,
 }
 
 #[derive(Debug, Args)]
@@ -111,6 +145,67 @@ pub struct GetArgs {
     /// Vendor Filter
     #[arg(long)]
     pub vendor: Option<String>,
+    /// Column to sort by ex. ticker, expiration_date
+    #[arg(long)]
+    pub sort: Option<String>,
+    /// Sort in descending order.
+    #[arg(long)]
+    pub desc: bool,
+    /// Columns to show ex. id,ticker,active, defaults to all.
+    #[arg(long)]
+    pub columns: Option<String>,
+    /// Print json instead of a table.
+    #[arg(long)]
+    pub json: bool,
+    /// Ticker glob ex. HE*, or a regex with --regex
+    #[arg(long)]
+    pub ticker: Option<String>,
+    /// Read --ticker as a regex.
+    #[arg(long)]
+    pub regex: bool,
+    /// Only active (true) or inactive (false) instruments.
+    #[arg(long)]
+    pub active: Option<bool>,
+    /// Only continuous (true) or dated (false) instruments.
+    #[arg(long)]
+    pub continuous: Option<bool>,
+    /// Expiring before a date ex. 2024-06-30, +30d
+    #[arg(long, allow_hyphen_values = true)]
+    pub expiring_before: Option<String>,
+    /// Expiring after a date ex. today
+    #[arg(long, allow_hyphen_values = true)]
+    pub expiring_after: Option<String>,
+    /// Only instruments whose data ends more than N days ago.
+    #[arg(long)]
+    pub stale: Option<u64>,
+    /// Timezone of dates without an offset ex. America/New_York, defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+}
+
+impl GetArgs {
+    pub fn filter(&self) -> Result<InstrumentFilter> {
+        let dates = DateParser::new(self.tz.as_deref())?;
+
+        Ok(InstrumentFilter {
+            ticker: self
+                .ticker
+                .as_deref()
+                .map(|ticker| ticker_pattern(ticker, self.regex))
+                .transpose()?,
+            active: self.active,
+            continuous: self.continuous,
+            expiring_before: dates.parse_optional(self.expiring_before.as_deref())?,
+            expiring_after: dates.parse_optional(self.expiring_after.as_deref())?,
+            stale_before: match self.stale {
+                Some(days) => Some(InstrumentFilter::stale_before(
+                    dates.parse_u64("now")?,
+                    days,
+                )),
+                None => None,
+            },
+        })
+    }
 }
 
 #[derive(Debug, Args)]
@@ -134,13 +229,13 @@ pub struct UpdateArgs {
     #[arg(long, value_parser = parse_vendor_data)]
     pub vendor_data: HashMap<String, String>,
     /// first date available in database
-    #[arg(long)]
+    #[arg(long, allow_hyphen_values = true)]
     pub first_available: String,
     /// last date available in database
-    #[arg(long)]
+    #[arg(long, allow_hyphen_values = true)]
     pub last_available: String,
     /// last date available in database
-    #[arg(long)]
+    #[arg(long, allow_hyphen_values = true)]
     pub expiration_date: String,
     /// Conntinuous status
     #[arg(long)]
@@ -148,6 +243,187 @@ pub struct UpdateArgs {
     /// Active status
     #[arg(long)]
     pub active: bool,
+    /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+    /// Timezone of the expiration date when it has no offset.
+    #[arg(long, default_value = "America/New_York")]
+    pub expiry_tz: String,
+}
+
+#[derive(Debug, Args)]
+pub struct CoverageArgs {
+    /// Dataset Filter
+    #[arg(long)]
+    pub dataset: String,
+    /// Vendor Filter
+    #[arg(long)]
+    pub vendor: Option<String>,
+    /// Tickers Filter ex. HE.n.0,ZC.n.0
+    #[arg(long, value_delimiter = ',')]
+    pub tickers: Vec<String>,
+    /// Schema sampled for record counts per day.
+    #[arg(long, default_value = "ohlcv-1h")]
+    pub schema: String,
+    /// Only check from this date ex. 2024-01-02, -30d
+    #[arg(long, allow_hyphen_values = true)]
+    pub start: Option<String>,
+    /// Only check up to this date ex. 2024-03-01, today
+    #[arg(long, allow_hyphen_values = true)]
+    pub end: Option<String>,
+    /// Timezone days are counted in and dates without an offset are read in, defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+    /// Output format ex. text (table), json
+    #[arg(long, default_value = "text")]
+    pub format: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// Instrument file, defaults to ~/.config/midas/tickers.json
+    pub file: Option<String>,
+    /// Update instruments that already exist instead of skipping them.
+    #[arg(long)]
+    pub upsert: bool,
+    /// Validate and show what would change without sending anything.
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+    /// Timezone of expiration dates without an offset.
+    #[arg(long, default_value = "America/New_York")]
+    pub expiry_tz: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Output file, the extension picks the format ex. universe.toml
+    pub file: String,
+    /// Dataset Filter, defaults to every dataset
+    #[arg(long)]
+    pub dataset: Option<String>,
+    /// Vendor Filter
+    #[arg(long)]
+    pub vendor: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct PlanArgs {
+    /// Manifest of the instrument universe, a .toml, .json or .csv instrument file
+    pub manifest: String,
+    /// Delete server instruments missing from the manifest instead of deactivating them.
+    #[arg(long)]
+    pub prune: bool,
+    /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+    /// Timezone of expiration dates without an offset.
+    #[arg(long, default_value = "America/New_York")]
+    pub expiry_tz: String,
+}
+
+impl PlanArgs {
+    fn options(&self) -> Result<PlanOptions> {
+        Ok(PlanOptions {
+            prune: self.prune,
+            dates: DateParser::new(self.tz.as_deref())?,
+            expiry_dates: DateParser::new(Some(&self.expiry_tz))?,
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ApplyArgs {
+    #[command(flatten)]
+    pub plan: PlanArgs,
+    /// Apply without asking for confirmation.
+    #[arg(long)]
+    pub approval: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateFuturesArgs {
+    /// Product root ex. HE, its expiration rule comes from [futures.<ROOT>] in config.toml or the built in ones
+    #[arg(long)]
+    pub root: String,
+    /// Contract month codes ex. GJKMNQVZ
+    #[arg(long)]
+    pub months: String,
+    /// Contract years ex. 2024 or 2024-2026
+    #[arg(long)]
+    pub years: String,
+    /// Databento schema stored in the vendor data.
+    #[arg(long, default_value = "mbp-1")]
+    pub schema: String,
+    /// first date available in database
+    #[arg(long, allow_hyphen_values = true)]
+    pub first_available: Option<String>,
+    /// Timezone of the first available date when it has no offset, defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+    /// Active status
+    #[arg(long)]
+    pub active: bool,
+    /// Update contracts that already exist instead of skipping them.
+    #[arg(long)]
+    pub upsert: bool,
+    /// Show what would change without sending anything.
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Write the contracts to a .toml, .json or .csv file instead of the server.
+    #[arg(long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct OptionChainArgs {
+    /// Underlying symbol ex. SPY
+    #[arg(long)]
+    pub underlying: String,
+    /// Date of the definitions, also the contracts' first available date ex. 2024-06-03
+    #[arg(long, allow_hyphen_values = true)]
+    pub date: String,
+    /// Definition file, defaults to the one `databento download` wrote for the underlying and date
+    #[arg(long)]
+    pub file: Option<String>,
+    /// Directory the definition file was downloaded to, defaults to RAW_DIR.
+    #[arg(long)]
+    pub dir_path: Option<String>,
+    /// Contract type ex. call, put, both
+    #[arg(long, default_value = "both")]
+    pub kind: String,
+    /// Earliest expiration ex. 2024-06-21, +1w
+    #[arg(long, allow_hyphen_values = true)]
+    pub expiry_start: Option<String>,
+    /// Latest expiration ex. 2024-09-20, +90d
+    #[arg(long, allow_hyphen_values = true)]
+    pub expiry_end: Option<String>,
+    /// Strike over underlying price band ex. 0.9-1.1, needs --spot
+    #[arg(long)]
+    pub moneyness: Option<String>,
+    /// Underlying price the moneyness band is taken from.
+    #[arg(long)]
+    pub spot: Option<f64>,
+    /// Databento schema stored in the vendor data.
+    #[arg(long, default_value = "mbp-1")]
+    pub schema: String,
+    /// Timezone of dates without an offset ex. America/New_York, defaults to UTC.
+    #[arg(long)]
+    pub tz: Option<String>,
+    /// Active status
+    #[arg(long)]
+    pub active: bool,
+    /// Update contracts that already exist instead of skipping them.
+    #[arg(long)]
+    pub upsert: bool,
+    /// Show what would change without sending anything.
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Write the contracts to a .toml, .json or .csv file instead of the server.
+    #[arg(long)]
+    pub output: Option<String>,
 }
 
 #[derive(Debug, Args)]
@@ -163,9 +439,10 @@ impl InstrumentCommands {
             InstrumentCommands::Create(args) => {
                 let vendor = Vendors::from_str(&args.vendor)?;
                 let dataset = Dataset::from_str(&args.dataset)?;
-                let first_available = date_to_unix_nanos(&args.first_available, None)?;
+                let dates = DateParser::new(args.tz.as_deref())?;
+                let first_available = dates.parse(&args.first_available)?;
                 let expiration_date =
-                    date_to_unix_nanos(&args.expiration_date, Some("America/New_York"))?;
+                    DateParser::new(Some(&args.expiry_tz))?.parse(&args.expiration_date)?;
 
                 let vendor_data_map = args.vendor_data.clone();
                 let vendor_data = construct_vendor_data(&vendor, vendor_data_map).map_err(|e| {
@@ -196,7 +473,19 @@ impl InstrumentCommands {
                 } else {
                     None
                 };
-                context.get_instruments(dataset, vendor).await;
+                let options = TableOptions::new(
+                    args.sort.as_deref(),
+                    args.desc,
+                    args.columns.as_deref(),
+                    args.json,
+                );
+
+                if !context
+                    .get_instruments(dataset, vendor, &args.filter()?, &options)
+                    .await
+                {
+                    return Err(error!(CustomError, "Get incomplete."));
+                }
             }
             InstrumentCommands::Delete(args) => {
                 context.delete_instrument(args.instrument_id).await;
@@ -204,10 +493,11 @@ impl InstrumentCommands {
             InstrumentCommands::Update(args) => {
                 let vendor = Vendors::from_str(&args.vendor)?;
                 let dataset = Dataset::from_str(&args.dataset)?;
-                let first_available = date_to_unix_nanos(&args.first_available, None)?;
-                let last_available = date_to_unix_nanos(&args.last_available, None)?;
+                let dates = DateParser::new(args.tz.as_deref())?;
+                let first_available = dates.parse(&args.first_available)?;
+                let last_available = dates.parse(&args.last_available)?;
                 let expiration_date =
-                    date_to_unix_nanos(&args.expiration_date, Some("America/New_York"))?;
+                    DateParser::new(Some(&args.expiry_tz))?.parse(&args.expiration_date)?;
                 let vendor_data_map = args.vendor_data.clone();
                 let vendor_data = construct_vendor_data(&vendor, vendor_data_map).map_err(|e| {
                     Error::CustomError(format!("Failed to parse vendor data '{}'", e))
@@ -229,6 +519,141 @@ impl InstrumentCommands {
 
                 context.update_instrument(instrument).await;
             }
+            InstrumentCommands::Coverage(args) => {
+                let dataset = Dataset::from_str(&args.dataset)?;
+                let vendor = match &args.vendor {
+                    Some(vendor) => Some(Vendors::from_str(vendor)?),
+                    None => None,
+                };
+                let dates = DateParser::new(args.tz.as_deref())?;
+                let options = CoverageOptions {
+                    schema: Schema::from_str(&args.schema)?,
+                    start: dates.parse_optional(args.start.as_deref())?,
+                    end: dates.parse_optional(args.end.as_deref())?,
+                    tz: dates.tz(),
+                };
+                let format = ReportFormat::try_from(args.format.as_str())?;
+
+                context
+                    .instrument_coverage(dataset, vendor, args.tickers.clone(), options, format)
+                    .await;
+            }
+            InstrumentCommands::Import(args) => {
+                let file = match &args.file {
+                    Some(file) => PathBuf::from(file),
+                    None => get_ticker_file()?,
+                };
+                let options = ImportOptions {
+                    upsert: args.upsert,
+                    dry_run: args.dry_run,
+                    dates: DateParser::new(args.tz.as_deref())?,
+                    expiry_dates: DateParser::new(Some(&args.expiry_tz))?,
+                };
+
+                if !context.import_instruments(file, options).await {
+                    return Err(error!(CustomError, "Import incomplete."));
+                }
+            }
+            InstrumentCommands::Export(args) => {
+                let datasets = match &args.dataset {
+                    Some(dataset) => vec![Dataset::from_str(dataset)?],
+                    None => vec![Dataset::Futures, Dataset::Equities, Dataset::Option],
+                };
+                let vendor = match &args.vendor {
+                    Some(vendor) => Some(Vendors::from_str(vendor)?),
+                    None => None,
+                };
+
+                context
+                    .export_instruments(PathBuf::from(&args.file), datasets, vendor)
+                    .await;
+            }
+            InstrumentCommands::Plan(args) => {
+                context
+                    .plan_instruments(PathBuf::from(&args.manifest), args.options()?)
+                    .await;
+            }
+            InstrumentCommands::Apply(args) => {
+                let file = PathBuf::from(&args.plan.manifest);
+
+                if !context
+                    .apply_instruments(file, args.plan.options()?, args.approval)
+                    .await
+                {
+                    return Err(error!(CustomError, "Apply incomplete."));
+                }
+            }
+            InstrumentCommands::GenerateFutures(args) => {
+                let options = FuturesOptions {
+                    months: parse_months(&args.months)?,
+                    years: parse_years(&args.years)?,
+                    schema: dbn::Schema::from_str(&args.schema)
+                        .map_err(|_| error!(CustomError, "Invalid 'schema': {}", args.schema))?,
+                    first_available: DateParser::new(args.tz.as_deref())?
+                        .parse_optional(args.first_available.as_deref())?
+                        .unwrap_or_default(),
+                    active: args.active,
+                };
+
+                if !context
+                    .generate_futures(
+                        args.root.to_uppercase(),
+                        options,
+                        args.upsert,
+                        args.dry_run,
+                        args.output.as_ref().map(PathBuf::from),
+                    )
+                    .await
+                {
+                    return Err(error!(CustomError, "Generate incomplete."));
+                }
+            }
+            InstrumentCommands::OptionChain(args) => {
+                let dates = DateParser::new(args.tz.as_deref())?;
+                let date = dates.parse_u64(&args.date)?;
+                let day = dates.tz().timestamp_nanos(date as i64).date_naive();
+                let moneyness = match &args.moneyness {
+                    Some(_) if args.spot.is_none() => {
+                        return Err(error!(CustomError, "--moneyness needs --spot"));
+                    }
+                    Some(band) => Some(parse_moneyness(band)?),
+                    None => None,
+                };
+                let filter = ChainFilter {
+                    kind: OptionKind::from_str(&args.kind)?,
+                    expiry_start: dates.parse_optional(args.expiry_start.as_deref())?,
+                    expiry_end: dates.parse_optional(args.expiry_end.as_deref())?,
+                    moneyness,
+                    spot: args.spot,
+                };
+                let options = ChainOptions {
+                    schema: dbn::Schema::from_str(&args.schema)
+                        .map_err(|_| error!(CustomError, "Invalid 'schema': {}", args.schema))?,
+                    first_available: date,
+                    active: args.active,
+                    upsert: args.upsert,
+                    dry_run: args.dry_run,
+                };
+                let file = definition_file(
+                    args.file.as_deref(),
+                    args.dir_path.as_deref(),
+                    &args.underlying.to_uppercase(),
+                    day,
+                )?;
+
+                if !context
+                    .option_chain(
+                        file,
+                        args.underlying.to_uppercase(),
+                        filter,
+                        options,
+                        args.output.as_ref().map(PathBuf::from),
+                    )
+                    .await
+                {
+                    return Err(error!(CustomError, "Option chain incomplete."));
+                }
+            }
         }
 
         Ok(())
@@ -1,7 +1,21 @@
 use crate::commands::TaskManager;
-use crate::error::Result;
+use crate::dates::DateParser;
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::checks::DUPLICATE_WINDOW;
+use crate::vendors::midas::generate::GenerateOptions;
+use crate::vendors::midas::plot::{ChartKind, PlotOptions};
+use crate::vendors::midas::remap::parse_id_map;
+use crate::vendors::midas::report::{CompareOptions, ReportFormat, DEFAULT_MAX_EXAMPLES};
+use crate::vendors::midas::resample::{EmptyIntervals, Interval, ResampleOptions, Session};
+use crate::vendors::midas::sample::SampleMode;
+use crate::vendors::midas::slice::SliceFilter;
+use crate::vendors::midas::split::SplitBy;
 use clap::{Args, Subcommand};
+use mbinary::enums::{Dataset, Schema};
+use mbinary::PRICE_SCALE;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Debug, Args)]
 pub struct MidasArgs {
@@ -17,11 +31,253 @@ pub enum MidasCommands {
         filepath1: String,
         #[arg(long)]
         filepath2: String,
+        /// File path to save the comparison report.
+        #[arg(long, default_value = "compare_results.txt")]
+        output: String,
+        /// Report format ex. text, json
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Absolute tolerance per field ex. ts_in_delta=100,price=0
+        #[arg(long)]
+        tolerances: Option<String>,
+        /// Near matches and unmatched records kept in the report, the rest are only counted.
+        #[arg(long, default_value_t = DEFAULT_MAX_EXAMPLES)]
+        max_examples: usize,
     },
     /// Compare databento and midas data
     Duplicates {
         #[arg(long)]
         filepath: String,
+        /// Nanoseconds behind the latest timestamp checked, widen for files not sorted by timestamp.
+        #[arg(long, default_value_t = DUPLICATE_WINDOW)]
+        window: u64,
+        /// File path to save a copy without duplicates.
+        #[arg(long)]
+        write_deduped: Option<String>,
+    },
+    /// Extract instruments and/or a time window from a file
+    Slice {
+        #[arg(long)]
+        filepath: String,
+        /// File path to save the sliced data.
+        #[arg(long)]
+        output: String,
+        /// Instrument ids ex. 20,21
+        #[arg(long, value_delimiter = ',')]
+        instrument_ids: Vec<u32>,
+        /// Tickers from the file symbol map ex. HE.n.0,ZC.n.0
+        #[arg(long, value_delimiter = ',')]
+        tickers: Vec<String>,
+        /// Start (inclusive) ex. 2024-01-02, 2024-01-02 09:30:00, -5d
+        #[arg(long, allow_hyphen_values = true)]
+        start: Option<String>,
+        /// End (exclusive) ex. 2024-01-03, today
+        #[arg(long, allow_hyphen_values = true)]
+        end: Option<String>,
+        /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+        #[arg(long)]
+        tz: Option<String>,
+    },
+    /// Merge files into one timestamp ordered file
+    Merge {
+        /// Files to merge ex. day1.bin,day2.bin
+        #[arg(long, value_delimiter = ',')]
+        filepaths: Vec<String>,
+        /// File path to save the merged data.
+        #[arg(long)]
+        output: String,
+    },
+    /// Split a file by instrument or by day
+    Split {
+        #[arg(long)]
+        filepath: String,
+        /// Directory to save the split files.
+        #[arg(long)]
+        output_dir: String,
+        /// Split key ex. instrument, day
+        #[arg(long, default_value = "instrument")]
+        by: String,
+    },
+    /// Build OHLCV bars from a mbp-1, tbbo or trades file
+    Resample {
+        #[arg(long)]
+        filepath: String,
+        /// Directory to save one OHLCV file per instrument.
+        #[arg(long)]
+        output_dir: String,
+        /// Bar interval ex. 1s, 1m, 1h, 1d
+        #[arg(long, default_value = "1m")]
+        interval: String,
+        /// Session open bars are aligned to in HH:MM format.
+        #[arg(long, default_value = "00:00")]
+        session_start: String,
+        /// Timezone of the session open ex. America/Chicago
+        #[arg(long, default_value = "UTC")]
+        tz: String,
+        /// Intervals without trades ex. skip, fill
+        #[arg(long, default_value = "skip")]
+        empty: String,
+    },
+    /// Convert a file to databento's dbn format
+    ToDbn {
+        #[arg(long)]
+        filepath: String,
+        /// File path to save the zstd compressed dbn file.
+        #[arg(long)]
+        output: String,
+    },
+    /// Check files for truncation, corrupt records and metadata mismatches
+    Verify {
+        /// File or directory of files to verify.
+        path: String,
+    },
+    /// Rewrite instrument ids in a file's records and symbol map
+    Remap {
+        #[arg(long)]
+        filepath: String,
+        /// Ids to replace ex. 20=45,21=46
+        #[arg(
+            long,
+            required_unless_present = "from_server",
+            conflicts_with = "from_server"
+        )]
+        map: Option<String>,
+        /// Use the ids the server has for the tickers in the file symbol map.
+        #[arg(long)]
+        from_server: bool,
+        /// File path to save the remapped data, the file is rewritten in place if not set.
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Per instrument record counts, time coverage, prices and volume
+    Stats {
+        #[arg(long)]
+        filepath: String,
+        /// Output format ex. text (table), json
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Timezone whose calendar days are counted as trading days ex. America/Chicago
+        #[arg(long, default_value = "UTC")]
+        tz: String,
+    },
+    /// Chart prices and volume per instrument in the terminal
+    Plot {
+        filepath: String,
+        /// Chart type ex. line, candles
+        #[arg(long, default_value = "line")]
+        kind: String,
+        /// Instrument ids ex. 20,21
+        #[arg(long, value_delimiter = ',')]
+        instrument_ids: Vec<u32>,
+        /// Tickers from the file symbol map ex. HE.n.0,ZC.n.0
+        #[arg(long, value_delimiter = ',')]
+        tickers: Vec<String>,
+        /// Start (inclusive) ex. 2024-01-02, 2024-01-02 09:30:00, -5d
+        #[arg(long, allow_hyphen_values = true)]
+        start: Option<String>,
+        /// End (exclusive) ex. 2024-01-03, today
+        #[arg(long, allow_hyphen_values = true)]
+        end: Option<String>,
+        /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+        #[arg(long)]
+        tz: Option<String>,
+        /// Chart columns, fits the terminal if not set.
+        #[arg(long)]
+        width: Option<usize>,
+        /// Chart rows.
+        #[arg(long, default_value_t = 15)]
+        height: usize,
+    },
+    /// Cut a small file from a large one, keeping its metadata
+    Sample {
+        #[arg(long)]
+        filepath: String,
+        /// File path to save the sample.
+        #[arg(long)]
+        output: String,
+        /// Keep the first N minutes of each instrument.
+        #[arg(long)]
+        minutes: Option<u64>,
+        /// Keep every k-th record of each instrument.
+        #[arg(long)]
+        every: Option<usize>,
+        /// Window start (inclusive) ex. 2024-01-02, 2024-01-02 09:30:00, -5d
+        #[arg(long, allow_hyphen_values = true)]
+        start: Option<String>,
+        /// Window end (exclusive) ex. 2024-01-03, today
+        #[arg(long, allow_hyphen_values = true)]
+        end: Option<String>,
+        /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+        #[arg(long)]
+        tz: Option<String>,
+    },
+    /// Write a synthetic file of random walk prices for testing
+    Generate {
+        /// File path to save the generated data.
+        #[arg(long)]
+        output: String,
+        /// Schema ex. mbp-1, tbbo, trades, ohlcv-1m, bbo-1s
+        #[arg(long, default_value = "mbp-1")]
+        schema: String,
+        /// Dataset ex. futures, equities, option
+        #[arg(long, default_value = "futures")]
+        dataset: String,
+        /// Tickers, assigned ids 1, 2, ... in order ex. HE.n.0,ZC.n.0
+        #[arg(long, value_delimiter = ',', default_value = "HE.n.0")]
+        tickers: Vec<String>,
+        /// First timestamp ex. 2024-01-02, 2024-01-02 09:30:00, -5d
+        #[arg(
+            long,
+            allow_hyphen_values = true,
+            default_value = "2024-01-02 00:00:00"
+        )]
+        start: String,
+        /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+        #[arg(long)]
+        tz: Option<String>,
+        /// Records to generate, not counting duplicates.
+        #[arg(long, default_value_t = 1000)]
+        records: usize,
+        /// Mean nanoseconds between mbp-1, tbbo and trades records.
+        #[arg(long, default_value_t = 1_000_000)]
+        interval: u64,
+        /// First price ex. 100.25
+        #[arg(long, default_value_t = 100.0)]
+        start_price: f64,
+        /// Price increment ex. 0.25
+        #[arg(long, default_value_t = 0.25)]
+        tick_size: f64,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Records to repeat right after themselves.
+        #[arg(long, default_value_t = 0)]
+        duplicates: usize,
+        /// Gaps to insert between records.
+        #[arg(long, default_value_t = 0)]
+        gaps: usize,
+        /// Length of each gap in nanoseconds.
+        #[arg(long, default_value_t = 3_600_000_000_000)]
+        gap: u64,
+    },
+    /// Write a zstd compressed copy of a file, defaults to the file path plus .zst
+    Compress {
+        #[arg(long)]
+        filepath: String,
+        #[arg(long)]
+        output: Option<String>,
+        /// Delete the source file once the copy is written.
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Write an uncompressed copy of a .zst file, defaults to the file path minus .zst
+    Decompress {
+        #[arg(long)]
+        filepath: String,
+        #[arg(long)]
+        output: Option<String>,
+        /// Delete the source file once the copy is written.
+        #[arg(long)]
+        remove: bool,
     },
 }
 
@@ -31,18 +287,287 @@ impl MidasCommands {
             MidasCommands::Compare {
                 filepath1,
                 filepath2,
+                output,
+                format,
+                tolerances,
+                max_examples,
+            } => {
+                let options =
+                    CompareOptions::new(output, format, tolerances.as_deref(), *max_examples)?;
+
+                if context
+                    .compare_mbinay_files(
+                        PathBuf::from(filepath1),
+                        PathBuf::from(filepath2),
+                        options,
+                    )
+                    .await
+                {
+                    Ok(())
+                } else {
+                    Err(error!(CustomError, "Files do not match."))
+                }
+            }
+            MidasCommands::Duplicates {
+                filepath,
+                window,
+                write_deduped,
+            } => {
+                context
+                    .check_duplicates(
+                        PathBuf::from(filepath),
+                        *window,
+                        write_deduped.as_ref().map(PathBuf::from),
+                    )
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Slice {
+                filepath,
+                output,
+                instrument_ids,
+                tickers,
+                start,
+                end,
+                tz,
+            } => {
+                let dates = DateParser::new(tz.as_deref())?;
+                let start = dates.parse_optional(start.as_deref())?;
+                let end = dates.parse_optional(end.as_deref())?;
+                let filter = SliceFilter {
+                    instrument_ids: instrument_ids.clone(),
+                    tickers: tickers.clone(),
+                    start,
+                    end,
+                };
+
+                context
+                    .slice_mbinary_file(PathBuf::from(filepath), PathBuf::from(output), filter)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Merge { filepaths, output } => {
+                let files = filepaths.iter().map(PathBuf::from).collect();
+
+                context
+                    .merge_mbinary_files(files, PathBuf::from(output))
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Split {
+                filepath,
+                output_dir,
+                by,
             } => {
+                let by = SplitBy::try_from(by.as_str())?;
+
                 context
-                    .compare_mbinay_files(PathBuf::from(filepath1), PathBuf::from(filepath2))
+                    .split_mbinary_file(PathBuf::from(filepath), PathBuf::from(output_dir), by)
                     .await;
 
                 Ok(())
             }
-            MidasCommands::Duplicates { filepath } => {
-                context.check_duplicates(PathBuf::from(filepath)).await;
+            MidasCommands::Resample {
+                filepath,
+                output_dir,
+                interval,
+                session_start,
+                tz,
+                empty,
+            } => {
+                let options = ResampleOptions {
+                    interval: Interval::try_from(interval.as_str())?,
+                    session: Session::new(session_start, tz)?,
+                    empty: EmptyIntervals::try_from(empty.as_str())?,
+                };
+
+                if context
+                    .resample_mbinary_file(
+                        PathBuf::from(filepath),
+                        PathBuf::from(output_dir),
+                        options,
+                    )
+                    .await
+                {
+                    Ok(())
+                } else {
+                    Err(error!(CustomError, "Resample failed."))
+                }
+            }
+            MidasCommands::ToDbn { filepath, output } => {
+                context
+                    .mbinary_to_dbn(PathBuf::from(filepath), PathBuf::from(output))
+                    .await;
 
                 Ok(())
             }
+            MidasCommands::Remap {
+                filepath,
+                map,
+                from_server,
+                output,
+            } => {
+                // None looks the ids up on the server
+                let map = match (map, from_server) {
+                    (Some(map), false) => Some(parse_id_map(map)?),
+                    (None, true) => None,
+                    _ => {
+                        return Err(error!(
+                            CustomError,
+                            "Remap takes either --map or --from-server."
+                        ))
+                    }
+                };
+
+                context
+                    .remap_mbinary_file(
+                        PathBuf::from(filepath),
+                        output.as_ref().map(PathBuf::from),
+                        map,
+                    )
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Stats {
+                filepath,
+                format,
+                tz,
+            } => {
+                let format = ReportFormat::try_from(format.as_str())?;
+
+                context
+                    .mbinary_file_stats(PathBuf::from(filepath), format, tz)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Plot {
+                filepath,
+                kind,
+                instrument_ids,
+                tickers,
+                start,
+                end,
+                tz,
+                width,
+                height,
+            } => {
+                let dates = DateParser::new(tz.as_deref())?;
+                let start = dates.parse_optional(start.as_deref())?;
+                let end = dates.parse_optional(end.as_deref())?;
+                let filter = SliceFilter {
+                    instrument_ids: instrument_ids.clone(),
+                    tickers: tickers.clone(),
+                    start,
+                    end,
+                };
+                let options =
+                    PlotOptions::new(ChartKind::try_from(kind.as_str())?, *width, *height)?;
+
+                context
+                    .plot_mbinary_file(PathBuf::from(filepath), filter, options)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Sample {
+                filepath,
+                output,
+                minutes,
+                every,
+                start,
+                end,
+                tz,
+            } => {
+                let dates = DateParser::new(tz.as_deref())?;
+                let start = dates.parse_optional(start.as_deref())?;
+                let end = dates.parse_optional(end.as_deref())?;
+                let mode = SampleMode::new(*minutes, *every, start, end)?;
+
+                context
+                    .sample_mbinary_file(PathBuf::from(filepath), PathBuf::from(output), mode)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Generate {
+                output,
+                schema,
+                dataset,
+                tickers,
+                start,
+                tz,
+                records,
+                interval,
+                start_price,
+                tick_size,
+                seed,
+                duplicates,
+                gaps,
+                gap,
+            } => {
+                let options = GenerateOptions {
+                    schema: Schema::from_str(schema)?,
+                    dataset: Dataset::from_str(dataset)?,
+                    tickers: tickers.clone(),
+                    start: DateParser::new(tz.as_deref())?.parse_u64(start)?,
+                    records: *records,
+                    interval: *interval,
+                    start_price: (start_price * PRICE_SCALE as f64).round() as i64,
+                    tick_size: (tick_size * PRICE_SCALE as f64).round() as i64,
+                    seed: *seed,
+                    duplicates: *duplicates,
+                    gaps: *gaps,
+                    gap: *gap,
+                };
+
+                context
+                    .generate_mbinary_file(PathBuf::from(output), options)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Compress {
+                filepath,
+                output,
+                remove,
+            } => {
+                context
+                    .compress_mbinary_file(
+                        PathBuf::from(filepath),
+                        output.as_ref().map(PathBuf::from),
+                        *remove,
+                    )
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Decompress {
+                filepath,
+                output,
+                remove,
+            } => {
+                context
+                    .decompress_mbinary_file(
+                        PathBuf::from(filepath),
+                        output.as_ref().map(PathBuf::from),
+                        *remove,
+                    )
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Verify { path } => {
+                if context.verify_mbinary_files(PathBuf::from(path)).await {
+                    Ok(())
+                } else {
+                    Err(error!(CustomError, "Verification failed."))
+                }
+            }
         }
     }
 }
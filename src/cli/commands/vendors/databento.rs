@@ -1,47 +1,15 @@
 use crate::commands::TaskManager;
+use crate::dates::DateParser;
 use crate::error;
 use crate::error::{Error, Result};
+use crate::vendors::midas::report::{CompareOptions, DEFAULT_MAX_EXAMPLES};
+use crate::vendors::midas::sample::SampleMode;
 use crate::vendors::DownloadType;
 use clap::{Args, Subcommand};
 use dbn;
 use mbinary::enums::Dataset;
 use std::path::PathBuf;
 use std::str::FromStr;
-use time::{format_description::well_known::Rfc3339, macros::time, OffsetDateTime};
-
-fn process_start_date(start: &String) -> Result<OffsetDateTime> {
-    // Append T00:00 to make it full day at 00:00 UTC
-    let start_datetime = format!("{}T00:00:00Z", start);
-
-    // Try to parse the datetime string as an OffsetDateTime
-    let start_date = OffsetDateTime::parse(&start_datetime, &Rfc3339).map_err(|_| {
-        Error::DateError(
-            "Error: Invalid start date format. Expected format: YYYY-MM-DD".to_string(),
-        )
-    })?;
-
-    Ok(start_date)
-}
-
-fn processs_end_date(end: Option<String>) -> Result<OffsetDateTime> {
-    let end_date = end
-        .as_ref()
-        .map(|s| s.clone()) // Clone the string if it exists
-        .unwrap_or_else(|| {
-            let now = OffsetDateTime::now_utc();
-            let end_of_today = now.replace_time(time!(00:00));
-            end_of_today.date().to_string() // Return the date part only
-        });
-
-    // Append T00:00 to make it full day at 00:00 UTC
-    let end_datetime = format!("{}T00:00:00Z", end_date);
-    let end_date = OffsetDateTime::parse(&end_datetime, &Rfc3339).map_err(|_| {
-        Error::DateError(
-            "Error: Invalid start date format. Expected format: YYYY-MM-DD".to_string(),
-        )
-    })?;
-    Ok(end_date)
-}
 
 #[derive(Debug, Args)]
 pub struct DatabentoArgs {
@@ -58,6 +26,9 @@ pub enum DatabentoCommands {
         dataset: String,
         #[arg(long)]
         approval: bool,
+        /// Stage files zstd compressed, they are decompressed again for the upload.
+        #[arg(long)]
+        compress: bool,
     },
     /// Download databento data to file
     Download {
@@ -77,14 +48,18 @@ pub enum DatabentoCommands {
         #[arg(long)]
         stype: String,
 
-        /// Start date in YYYY-MM-DD HH:MM:SS format.
-        #[arg(long)]
+        /// Start ex. 2024-01-02, 2024-01-02 09:30:00, -5d
+        #[arg(long, allow_hyphen_values = true)]
         start: String,
 
-        /// End date in YYYY-MM-DD HH:MM:SS format.
-        #[arg(long)]
+        /// End ex. 2024-01-03, today
+        #[arg(long, allow_hyphen_values = true)]
         end: String,
 
+        /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+        #[arg(long)]
+        tz: Option<String>,
+
         /// Optional path, if not provided will defualt to RAW_DIR variable.
         #[arg(long)]
         dir_path: Option<String>,
@@ -104,6 +79,10 @@ pub enum DatabentoCommands {
         /// File path to save the downloaded binary data.
         #[arg(long)]
         midas_filepath: String,
+
+        /// Stage the file zstd compressed, appends .zst to the file path.
+        #[arg(long)]
+        compress: bool,
     },
     /// Upload a databento file to database
     Upload {
@@ -121,6 +100,10 @@ pub enum DatabentoCommands {
         /// File path to save the downloaded binary data.
         #[arg(long)]
         midas_filepath: String,
+
+        /// Stage files zstd compressed, they are decompressed again for the upload.
+        #[arg(long)]
+        compress: bool,
     },
     /// Compare databento and midas data
     Compare {
@@ -128,15 +111,54 @@ pub enum DatabentoCommands {
         dbn_filepath: String,
         #[arg(long)]
         midas_filepath: String,
+        /// File path to save the comparison report.
+        #[arg(long, default_value = "compare_results.txt")]
+        output: String,
+        /// Report format ex. text, json
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Absolute tolerance per field ex. ts_in_delta=100,price=0
+        #[arg(long)]
+        tolerances: Option<String>,
+        /// Near matches and unmatched records kept in the report, the rest are only counted.
+        #[arg(long, default_value_t = DEFAULT_MAX_EXAMPLES)]
+        max_examples: usize,
+    },
+    /// Cut a small zstd dbn file from a large one, keeping its metadata and mappings
+    Sample {
+        #[arg(long)]
+        filepath: String,
+        /// File path to save the sample.
+        #[arg(long)]
+        output: String,
+        /// Keep the first N minutes of each instrument.
+        #[arg(long)]
+        minutes: Option<u64>,
+        /// Keep every k-th record of each instrument.
+        #[arg(long)]
+        every: Option<usize>,
+        /// Window start (inclusive) ex. 2024-01-02, 2024-01-02 09:30:00, -5d
+        #[arg(long, allow_hyphen_values = true)]
+        start: Option<String>,
+        /// Window end (exclusive) ex. 2024-01-03, today
+        #[arg(long, allow_hyphen_values = true)]
+        end: Option<String>,
+        /// Timezone of dates without an offset ex. America/Chicago, defaults to UTC.
+        #[arg(long)]
+        tz: Option<String>,
     },
 }
 
 impl DatabentoCommands {
     pub async fn process_command(&self, context: TaskManager) -> Result<()> {
         match self {
-            DatabentoCommands::Update { dataset, approval } => {
+            DatabentoCommands::Update {
+                dataset,
+                approval,
+                compress,
+            } => {
                 let dataset = Dataset::from_str(dataset)?;
-                context.update(dataset, *approval).await;
+                context.update(dataset, *approval, *compress).await;
 
                 Ok(())
             }
@@ -150,9 +172,11 @@ impl DatabentoCommands {
                 stype,
                 dir_path,
                 approval,
+                tz,
             } => {
-                let start_date = process_start_date(start)?;
-                let end_date = processs_end_date(Some(end.clone()))?;
+                let dates = DateParser::new(tz.as_deref())?;
+                let start_date = dates.parse_offset_datetime(start)?;
+                let end_date = dates.parse_offset_datetime(end)?;
                 let schema_enum = dbn::Schema::from_str(schema.as_str())
                     .map_err(|_| error!(CustomError, "Invalid schema : {}", schema.as_str()))?;
                 let dataset_enum = dbn::Dataset::from_str(dataset.as_str())
@@ -179,6 +203,7 @@ impl DatabentoCommands {
                 dataset,
                 dbn_filepath,
                 midas_filepath,
+                compress,
             } => {
                 let dbn_filepath = PathBuf::from(dbn_filepath);
                 let midas_filepath = PathBuf::from(midas_filepath);
@@ -186,7 +211,7 @@ impl DatabentoCommands {
                     .map_err(|_| error!(CustomError, "Invalid dataset : {}", dataset.as_str()))?;
 
                 context
-                    .transform(dataset, dbn_filepath, midas_filepath)
+                    .transform(dataset, dbn_filepath, midas_filepath, *compress)
                     .await;
 
                 Ok(())
@@ -196,6 +221,7 @@ impl DatabentoCommands {
                 dbn_filepath,
                 dbn_downloadtype,
                 midas_filepath,
+                compress,
             } => {
                 let dbn_filepath = PathBuf::from(dbn_filepath);
                 let midas_filepath = PathBuf::from(midas_filepath);
@@ -204,7 +230,13 @@ impl DatabentoCommands {
                     .map_err(|_| error!(CustomError, "Invalid dataset : {}", dataset.as_str()))?;
 
                 context
-                    .upload(dataset_enum, &download_type, &dbn_filepath, &midas_filepath)
+                    .upload(
+                        dataset_enum,
+                        &download_type,
+                        &dbn_filepath,
+                        &midas_filepath,
+                        *compress,
+                    )
                     .await;
 
                 Ok(())
@@ -212,9 +244,43 @@ impl DatabentoCommands {
             DatabentoCommands::Compare {
                 dbn_filepath,
                 midas_filepath,
+                output,
+                format,
+                tolerances,
+                max_examples,
+            } => {
+                let options =
+                    CompareOptions::new(output, format, tolerances.as_deref(), *max_examples)?;
+
+                if context
+                    .dbn_compare(
+                        PathBuf::from(dbn_filepath),
+                        PathBuf::from(midas_filepath),
+                        options,
+                    )
+                    .await
+                {
+                    Ok(())
+                } else {
+                    Err(error!(CustomError, "Files do not match."))
+                }
+            }
+            DatabentoCommands::Sample {
+                filepath,
+                output,
+                minutes,
+                every,
+                start,
+                end,
+                tz,
             } => {
+                let dates = DateParser::new(tz.as_deref())?;
+                let start = dates.parse_optional(start.as_deref())?;
+                let end = dates.parse_optional(end.as_deref())?;
+                let mode = SampleMode::new(*minutes, *every, start, end)?;
+
                 context
-                    .dbn_compare(PathBuf::from(dbn_filepath), PathBuf::from(midas_filepath))
+                    .sample_dbn_file(PathBuf::from(filepath), PathBuf::from(output), mode)
                     .await;
 
                 Ok(())
@@ -222,51 +288,3 @@ impl DatabentoCommands {
         }
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use time::macros::datetime;
-
-    #[test]
-    fn test_process_start_date() -> Result<()> {
-        let start_str = "2024-01-01".to_string();
-
-        // Test
-        let start = process_start_date(&start_str)?;
-
-        // Validate
-        assert_eq!(start, datetime!(2024-01-01 0:00:00.0 +00:00:00));
-
-        Ok(())
-    }
-
-    #[test]
-    fn test_process_end_date() -> Result<()> {
-        let end_str = Some("2024-01-01".to_string());
-
-        // Test
-        let end = processs_end_date(end_str)?;
-
-        // Validate
-        assert_eq!(end, datetime!(2024-01-01 0:00:00.0 +00:00:00));
-
-        Ok(())
-    }
-
-    #[test]
-    fn test_process_end_date_auto() -> Result<()> {
-        let end_str = None;
-
-        // Test
-        let end = processs_end_date(end_str)?;
-
-        // Validate
-        let now = OffsetDateTime::now_utc();
-        let expt_end = now.replace_time(time::macros::time!(00:00));
-
-        assert_eq!(end, expt_end);
-
-        Ok(())
-    }
-}
@@ -1,7 +1,37 @@
+use crate::cache::{cache_table, CachePolicy, HistoricalCache};
 use crate::context::Context;
-use crate::utils::{get_dashboard_path, run_python_engine};
-use crate::vendors::midas::checks::find_duplicates;
+use crate::error::{Error, Result};
+use crate::instruments::file::{read_instrument_file, write_instrument_file, InstrumentRecord};
+use crate::instruments::futures::{chain_table, futures_chain, futures_rule, FuturesOptions};
+use crate::instruments::import::{plan_import, validate_records, ImportAction, ImportOptions};
+use crate::instruments::options::{
+    chain_table as option_chain_table, filter_chain, option_instruments, read_option_chain,
+    ChainFilter, ChainOptions,
+};
+use crate::instruments::plan::{plan_manifest, InstrumentPlan, PlanAction, PlanOptions};
+use crate::instruments::search::InstrumentFilter;
+use crate::retrieve::{retrieve_chunked, ChunkOptions, OutputLock};
+use crate::table::{render_table, TableOptions};
+use crate::utils::{get_dashboard_path, run_python_engine, user_input};
+use crate::vendors::databento::sample::sample_dbn;
+use crate::vendors::databento::transform::to_dbn;
+use crate::vendors::midas::checks::report_duplicates;
 use crate::vendors::midas::compare::compare_mbinary;
+use crate::vendors::midas::compress::{compress_mbinary, compressed_path, decompress_mbinary};
+use crate::vendors::midas::coverage::{instrument_coverage, render_coverage, CoverageOptions};
+use crate::vendors::midas::export::{export_mbinary, ExportFormat, ExportOptions};
+use crate::vendors::midas::generate::{generate_mbinary, GenerateOptions};
+use crate::vendors::midas::load::{is_zstd, read_mbinary_file};
+use crate::vendors::midas::merge::merge_mbinary;
+use crate::vendors::midas::plot::{plot_series, render_chart, PlotOptions};
+use crate::vendors::midas::remap::{remap_mbinary, remap_mbinary_in_place, server_id_map};
+use crate::vendors::midas::report::{CompareOptions, CompareReport, ReportFormat};
+use crate::vendors::midas::resample::{resample_mbinary, ResampleOptions};
+use crate::vendors::midas::sample::{sample_mbinary, SampleMode};
+use crate::vendors::midas::slice::{slice_mbinary, SliceFilter};
+use crate::vendors::midas::split::{split_mbinary, SplitBy};
+use crate::vendors::midas::stats::{mbinary_stats, render_stats};
+use crate::vendors::midas::verify::verify_path;
 use crate::vendors::{databento::compare::compare_dbn, DownloadType, Vendor};
 use mbinary::enums::Dataset;
 use mbinary::params::RetrieveParams;
@@ -166,17 +196,161 @@ impl TaskManager {
         }
     }
 
-    pub async fn get_historical(&self, params: RetrieveParams, file_path: &str) {
+    /// Fills `path` with the requested mbinary data from the cache or the server,
+    /// returns a status line describing where the data came from.
+    async fn retrieve_historical(
+        &self,
+        params: &RetrieveParams,
+        path: &Path,
+        policy: CachePolicy,
+        options: ChunkOptions,
+    ) -> Result<String> {
+        let cache = self.context.get_historical_cache();
+        let cacheable = HistoricalCache::cacheable(params);
+
+        if policy == CachePolicy::Use && cacheable {
+            match cache.fetch(params, path) {
+                Ok(true) => return Ok("Data loaded from cache".to_string()),
+                Ok(false) => (),
+                Err(e) => eprintln!("Error reading cache: {}", e),
+            }
+        }
+
         let client = self.context.get_historical_client();
+        let fetch = move |chunk: RetrieveParams| {
+            let client = client.clone();
+            async move {
+                let response = client.get_records(&chunk).await?;
+                if response.code != 200 {
+                    return Err(Error::ApiError(response.message));
+                }
+                Ok(response.data)
+            }
+        };
 
-        // Attempt to get records and save them to the file
-        match client.get_records_to_file(&params, file_path).await {
-            Ok(_) => {
-                println!("Data successfully saved to {}", file_path);
+        let summary = retrieve_chunked(params, path, &options, fetch).await?;
+        if policy != CachePolicy::Bypass && cacheable {
+            if let Err(e) = cache.store(params, path) {
+                eprintln!("Error caching data: {}", e);
+            }
+        }
+
+        Ok(format!(
+            "Data successfully retrieved ({} chunks, {} fetched, {} bytes)",
+            summary.chunks, summary.fetched, summary.bytes
+        ))
+    }
+
+    /// Writes the requested data to `file_path` in `export.format`, a path of `-` writes to
+    /// stdout and moves status messages to stderr.
+    pub async fn get_historical(
+        &self,
+        params: RetrieveParams,
+        file_path: &str,
+        policy: CachePolicy,
+        options: ChunkOptions,
+        export: ExportOptions,
+    ) -> bool {
+        let to_stdout = file_path == "-";
+        let report = |message: String| {
+            if to_stdout {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
+        };
+
+        // Decoded or piped output is staged as mbinary first, named by the request so a failed
+        // run resumes from its fetched chunks, and locked so concurrent runs don't share it
+        let staged = to_stdout || export.format != ExportFormat::Mbinary;
+        let mbinary_path = if staged {
+            std::env::temp_dir().join(format!(
+                "midas_historical_{}.bin",
+                HistoricalCache::request_key(&params)
+            ))
+        } else {
+            PathBuf::from(file_path)
+        };
+        let _lock = match staged
+            .then(|| OutputLock::acquire(&mbinary_path))
+            .transpose()
+        {
+            Ok(lock) => lock,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return false;
+            }
+        };
+
+        let status = match self
+            .retrieve_historical(&params, &mbinary_path, policy, options)
+            .await
+        {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return false;
+            }
+        };
+
+        if !staged {
+            report(format!("{}, saved to {}", status, file_path));
+            return true;
+        }
+
+        let result = if to_stdout {
+            let stdout = std::io::stdout();
+            export_mbinary(&mbinary_path, &mut stdout.lock(), &export).await
+        } else {
+            match fs::File::create(file_path) {
+                Ok(file) => {
+                    export_mbinary(&mbinary_path, &mut std::io::BufWriter::new(file), &export).await
+                }
+                Err(e) => Err(e.into()),
+            }
+        };
+        let _ = fs::remove_file(&mbinary_path);
+
+        match result {
+            Ok(count) => {
+                let target = if to_stdout { "stdout" } else { file_path };
+                report(format!("{}, wrote {} records to {}", status, count, target));
+                true
             }
+            // Readers like head close the pipe early
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => true,
             Err(e) => {
                 eprintln!("Error: {}", e);
+                false
+            }
+        }
+    }
+
+    pub async fn list_cache(&self) {
+        let cache = self.context.get_historical_cache();
+
+        match cache.entries() {
+            Ok(entries) if entries.is_empty() => {
+                println!("Cache is empty ({}).", cache.dir().display())
             }
+            Ok(entries) => match cache_table(&entries) {
+                Ok(table) => {
+                    table.printstd();
+                    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+                    println!("{} entries, {} bytes", entries.len(), total);
+                }
+                Err(e) => println!("Error {}", e),
+            },
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn clear_cache(&self) {
+        let cache = self.context.get_historical_cache();
+
+        match cache.clear() {
+            Ok((count, bytes)) => println!("Removed {} entries, {} bytes", count, bytes),
+            Err(e) => println!("Error {}", e),
         }
     }
 
@@ -193,23 +367,476 @@ impl TaskManager {
         }
     }
 
-    pub async fn get_instruments(&self, dataset: Dataset, vendor: Option<Vendors>) {
+    /// Server instruments of a dataset, only those of `vendor` when given.
+    pub async fn list_instruments(
+        &self,
+        dataset: Dataset,
+        vendor: Option<Vendors>,
+    ) -> Result<Vec<Instrument>> {
         let client = self.context.get_instrument_client();
 
-        let response = if vendor.is_some() {
-            client.list_vendor_symbols(&vendor.unwrap(), &dataset).await
-        } else {
-            client.list_dataset_symbols(&dataset).await
+        let response = match vendor {
+            Some(vendor) => client.list_vendor_symbols(&vendor, &dataset).await?,
+            None => client.list_dataset_symbols(&dataset).await?,
         };
+        if response.code != 200 {
+            return Err(Error::ApiError(response.message));
+        }
+        Ok(response.data)
+    }
 
-        match response {
-            Ok(symbols) => {
-                println!("{:?}", symbols.data);
+    /// Prints the instruments of a dataset that pass the filter as a table, or as json with
+    /// `options.json`.
+    pub async fn get_instruments(
+        &self,
+        dataset: Dataset,
+        vendor: Option<Vendors>,
+        filter: &InstrumentFilter,
+        options: &TableOptions,
+    ) -> bool {
+        let instruments = self.list_instruments(dataset, vendor).await;
+        match instruments.and_then(|instruments| render_table(&filter.apply(instruments), options))
+        {
+            Ok(output) => {
+                println!("{}", output);
+                true
             }
             Err(e) => {
                 eprintln!("{}", e);
+                false
+            }
+        }
+    }
+
+    /// Prints what the server holds for each matching instrument and its missing sessions.
+    pub async fn instrument_coverage(
+        &self,
+        dataset: Dataset,
+        vendor: Option<Vendors>,
+        tickers: Vec<String>,
+        options: CoverageOptions,
+        format: ReportFormat,
+    ) {
+        let instruments = match self.list_instruments(dataset, vendor).await {
+            Ok(instruments) => instruments,
+            Err(e) => {
+                println!("Error {}", e);
+                return;
+            }
+        };
+
+        let client = self.context.get_historical_client();
+        let now = OffsetDateTime::now_utc().unix_timestamp_nanos() as u64;
+        let mut coverage = Vec::new();
+
+        for instrument in instruments
+            .iter()
+            .filter(|instrument| tickers.is_empty() || tickers.contains(&instrument.ticker))
+        {
+            match instrument_coverage(instrument, &client, &options, now).await {
+                Ok(report) => coverage.push(report),
+                Err(e) => eprintln!("Error {} : {}", instrument.ticker, e),
+            }
+        }
+
+        if coverage.is_empty() {
+            println!("No instruments matched.");
+            return;
+        }
+
+        match render_coverage(&coverage, format) {
+            Ok(output) => println!("{}", output),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    /// Server instruments of every dataset the given instruments belong to.
+    async fn instruments_in_datasets(&self, instruments: &[Instrument]) -> Result<Vec<Instrument>> {
+        let mut datasets: Vec<Dataset> = Vec::new();
+        for instrument in instruments {
+            if !datasets.contains(&instrument.dataset) {
+                datasets.push(instrument.dataset);
+            }
+        }
+
+        let mut existing = Vec::new();
+        for dataset in datasets {
+            existing.extend(self.list_instruments(dataset, None).await?);
+        }
+        Ok(existing)
+    }
+
+    /// Validates the whole file first, then sends it with `send_instruments`.
+    pub async fn import_instruments(&self, file: PathBuf, options: ImportOptions) -> bool {
+        let records = match read_instrument_file(&file) {
+            Ok(records) => records,
+            Err(e) => {
+                println!("Error {}", e);
+                return false;
+            }
+        };
+
+        let instruments = match validate_records(&records, &options.dates, &options.expiry_dates) {
+            Ok(instruments) => instruments,
+            Err(errors) => {
+                for error in &errors {
+                    println!("{}", error);
+                }
+                println!(
+                    "{} of {} rows invalid, nothing was imported.",
+                    errors.len(),
+                    records.len()
+                );
+                return false;
+            }
+        };
+
+        self.send_instruments(instruments, options.upsert, options.dry_run)
+            .await
+    }
+
+    /// Creates the instruments the server lacks and, with `upsert`, updates the ones that
+    /// differ, reporting the outcome per row. Returns true when every row went through.
+    async fn send_instruments(
+        &self,
+        instruments: Vec<Instrument>,
+        upsert: bool,
+        dry_run: bool,
+    ) -> bool {
+        let existing = match self.instruments_in_datasets(&instruments).await {
+            Ok(existing) => existing,
+            Err(e) => {
+                println!("Error {}", e);
+                return false;
+            }
+        };
+
+        let plan = plan_import(instruments, &existing, upsert);
+        let client = self.context.get_instrument_client();
+        let mut counts: HashMap<ImportAction, usize> = HashMap::new();
+        let mut failed = 0;
+
+        for row in &plan {
+            let label = format!(
+                "Row {} {} ({})",
+                row.row,
+                row.instrument.ticker,
+                row.instrument.dataset.as_str()
+            );
+
+            if dry_run {
+                println!("{} : {}", label, row.action);
+                *counts.entry(row.action).or_default() += 1;
+                continue;
+            }
+
+            let result = match row.action {
+                ImportAction::Create => client
+                    .create_symbol(&row.instrument)
+                    .await
+                    .map(|response| (response.code, response.message)),
+                ImportAction::Update => client
+                    .update_symbol(&row.instrument)
+                    .await
+                    .map(|response| (response.code, response.message)),
+                ImportAction::Unchanged | ImportAction::Skip => Ok((200, String::new())),
+            };
+
+            match result {
+                Ok((200, _)) => {
+                    println!("{} : {}", label, row.action.done());
+                    *counts.entry(row.action).or_default() += 1;
+                }
+                Ok((_, message)) => {
+                    println!("{} : failed, {}", label, message);
+                    failed += 1;
+                }
+                Err(e) => {
+                    println!("{} : failed, {}", label, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        let count = |action| counts.get(&action).copied().unwrap_or_default();
+        if dry_run {
+            println!(
+                "Dry run, nothing was sent. {} to create, {} to update, {} unchanged, {} skipped.",
+                count(ImportAction::Create),
+                count(ImportAction::Update),
+                count(ImportAction::Unchanged),
+                count(ImportAction::Skip)
+            );
+        } else {
+            println!(
+                "Created {}, updated {}, unchanged {}, skipped {}, failed {}.",
+                count(ImportAction::Create),
+                count(ImportAction::Update),
+                count(ImportAction::Unchanged),
+                count(ImportAction::Skip),
+                failed
+            );
+        }
+
+        failed == 0
+    }
+
+    /// Reads and validates the manifest and diffs it against the server, printing any errors.
+    async fn manifest_plan(&self, file: &Path, options: &PlanOptions) -> Option<InstrumentPlan> {
+        let records = match read_instrument_file(file) {
+            Ok(records) => records,
+            Err(e) => {
+                println!("Error {}", e);
+                return None;
+            }
+        };
+
+        let instruments = match validate_records(&records, &options.dates, &options.expiry_dates) {
+            Ok(instruments) => instruments,
+            Err(errors) => {
+                for error in &errors {
+                    println!("{}", error);
+                }
+                println!("{} of {} rows invalid.", errors.len(), records.len());
+                return None;
+            }
+        };
+
+        match self.instruments_in_datasets(&instruments).await {
+            Ok(existing) => Some(plan_manifest(instruments, &existing, options.prune)),
+            Err(e) => {
+                println!("Error {}", e);
+                None
+            }
+        }
+    }
+
+    /// Prints the changes `apply_instruments` would make for the manifest.
+    pub async fn plan_instruments(&self, file: PathBuf, options: PlanOptions) {
+        if let Some(plan) = self.manifest_plan(&file, &options).await {
+            println!("{}", plan);
+        }
+    }
+
+    /// Shows the manifest's plan, asks for confirmation unless approved, then sends each
+    /// change and reports the outcome. Returns true when every change went through.
+    pub async fn apply_instruments(
+        &self,
+        file: PathBuf,
+        options: PlanOptions,
+        approval: bool,
+    ) -> bool {
+        let plan = match self.manifest_plan(&file, &options).await {
+            Some(plan) => plan,
+            None => return false,
+        };
+        println!("{}", plan);
+
+        if plan.is_empty() {
+            println!("Nothing to apply.");
+            return true;
+        }
+
+        if !approval {
+            match user_input() {
+                Ok(true) => {}
+                Ok(false) => return true,
+                Err(e) => {
+                    println!("Error {}", e);
+                    return false;
+                }
             }
         }
+
+        let client = self.context.get_instrument_client();
+        let mut counts: HashMap<PlanAction, usize> = HashMap::new();
+        let mut failed = 0;
+
+        for item in &plan.items {
+            let label = format!(
+                "{} ({})",
+                item.instrument.ticker,
+                item.instrument.dataset.as_str()
+            );
+
+            let result = match item.action {
+                PlanAction::Create => client
+                    .create_symbol(&item.instrument)
+                    .await
+                    .map(|response| (response.code, response.message)),
+                PlanAction::Update | PlanAction::Deactivate => client
+                    .update_symbol(&item.instrument)
+                    .await
+                    .map(|response| (response.code, response.message)),
+                // Without a usable id the delete is counted as failed, never sent with a default
+                PlanAction::Delete => match item.instrument.instrument_id.map(i32::try_from) {
+                    Some(Ok(id)) => client
+                        .delete_symbol(&id)
+                        .await
+                        .map(|response| (response.code, response.message)),
+                    Some(Err(_)) => Ok((400, "instrument id out of range".to_string())),
+                    None => Ok((400, "instrument id missing".to_string())),
+                },
+            };
+
+            match result {
+                Ok((200, _)) => {
+                    println!("{} : {}", label, item.action.done());
+                    *counts.entry(item.action).or_default() += 1;
+                }
+                Ok((_, message)) => {
+                    println!("{} : {} failed, {}", label, item.action, message);
+                    failed += 1;
+                }
+                Err(e) => {
+                    println!("{} : {} failed, {}", label, item.action, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        let count = |action| counts.get(&action).copied().unwrap_or_default();
+        println!(
+            "Created {}, updated {}, deactivated {}, deleted {}, failed {}.",
+            count(PlanAction::Create),
+            count(PlanAction::Update),
+            count(PlanAction::Deactivate),
+            count(PlanAction::Delete),
+            failed
+        );
+
+        failed == 0
+    }
+
+    /// Builds a root's contracts from its expiration rule and previews them. They are then
+    /// written to `output` when given, or sent like an import. Returns true when all went through.
+    pub async fn generate_futures(
+        &self,
+        root: String,
+        options: FuturesOptions,
+        upsert: bool,
+        dry_run: bool,
+        output: Option<PathBuf>,
+    ) -> bool {
+        let chain = futures_rule(&root, &self.context.get_config().futures).and_then(|rule| {
+            let instruments = futures_chain(&root, &rule, &options)?;
+            let table = chain_table(&instruments, &rule)?;
+            Ok((instruments, table))
+        });
+        let (instruments, table) = match chain {
+            Ok(chain) => chain,
+            Err(e) => {
+                println!("Error {}", e);
+                return false;
+            }
+        };
+        println!("{}", table);
+
+        self.write_or_send_instruments(instruments, upsert, dry_run, output)
+            .await
+    }
+
+    /// Writes generated instruments to `output` when given, otherwise sends them like an import.
+    async fn write_or_send_instruments(
+        &self,
+        instruments: Vec<Instrument>,
+        upsert: bool,
+        dry_run: bool,
+        output: Option<PathBuf>,
+    ) -> bool {
+        match output {
+            Some(file) => {
+                let records: Vec<InstrumentRecord> = instruments
+                    .iter()
+                    .map(InstrumentRecord::from_instrument)
+                    .collect();
+                match write_instrument_file(&file, &records) {
+                    Ok(()) => {
+                        println!("Wrote {} contracts to {}", records.len(), file.display());
+                        true
+                    }
+                    Err(e) => {
+                        println!("Error {}", e);
+                        false
+                    }
+                }
+            }
+            None => self.send_instruments(instruments, upsert, dry_run).await,
+        }
+    }
+
+    /// Reads the underlying's option chain from a Databento definition file, previews the
+    /// contracts the filter keeps and writes or sends them like `generate_futures`.
+    pub async fn option_chain(
+        &self,
+        file: PathBuf,
+        underlying: String,
+        filter: ChainFilter,
+        options: ChainOptions,
+        output: Option<PathBuf>,
+    ) -> bool {
+        let contracts = match read_option_chain(&file, &underlying).await {
+            Ok(contracts) => contracts,
+            Err(e) => {
+                println!("Error {}", e);
+                return false;
+            }
+        };
+        let total = contracts.len();
+        let contracts = filter_chain(contracts, &filter);
+
+        println!("{}", option_chain_table(&contracts));
+        println!(
+            "{} of {} {} contracts selected from {}",
+            contracts.len(),
+            total,
+            underlying,
+            file.display()
+        );
+        if contracts.is_empty() {
+            return true;
+        }
+
+        let instruments = option_instruments(
+            &contracts,
+            options.schema,
+            options.first_available,
+            options.active,
+        );
+        self.write_or_send_instruments(instruments, options.upsert, options.dry_run, output)
+            .await
+    }
+
+    /// Writes the instruments of each dataset to `file`, the format follows its extension.
+    pub async fn export_instruments(
+        &self,
+        file: PathBuf,
+        datasets: Vec<Dataset>,
+        vendor: Option<Vendors>,
+    ) {
+        let mut records = Vec::new();
+
+        for dataset in datasets {
+            match self.list_instruments(dataset, vendor).await {
+                Ok(instruments) => {
+                    records.extend(instruments.iter().map(InstrumentRecord::from_instrument))
+                }
+                Err(e) => {
+                    println!("Error {}", e);
+                    return;
+                }
+            }
+        }
+        records.sort_by(|a, b| (&a.dataset, &a.ticker).cmp(&(&b.dataset, &b.ticker)));
+
+        match write_instrument_file(&file, &records) {
+            Ok(()) => println!(
+                "Exported {} instruments to {}",
+                records.len(),
+                file.display()
+            ),
+            Err(e) => println!("Error {}", e),
+        }
     }
 
     pub async fn delete_instrument(&self, instrument_id: i32) {
@@ -238,16 +865,220 @@ impl TaskManager {
         }
     }
 
-    pub async fn compare_mbinay_files(&self, file1: PathBuf, file2: PathBuf) {
-        match compare_mbinary(&file1, &file2).await {
+    /// Returns true if all records matched.
+    pub async fn compare_mbinay_files(
+        &self,
+        file1: PathBuf,
+        file2: PathBuf,
+        options: CompareOptions,
+    ) -> bool {
+        let report = compare_mbinary(&file1, &file2, &options).await;
+        write_compare_report(report, &options)
+    }
+
+    pub async fn check_duplicates(&self, file: PathBuf, window: u64, deduped: Option<PathBuf>) {
+        match report_duplicates(&file, window, deduped.as_deref()).await {
             Ok(_) => (),
             Err(e) => println!("Error {}", e),
         }
     }
 
-    pub async fn check_duplicates(&self, file: PathBuf) {
-        match find_duplicates(&file).await {
-            Ok(_) => (),
+    pub async fn slice_mbinary_file(&self, file: PathBuf, output: PathBuf, filter: SliceFilter) {
+        match slice_mbinary(&file, &output, &filter).await {
+            Ok(count) => println!("Wrote {} records to {}", count, output.display()),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn merge_mbinary_files(&self, files: Vec<PathBuf>, output: PathBuf) {
+        match merge_mbinary(&files, &output).await {
+            Ok(count) => println!("Wrote {} records to {}", count, output.display()),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn split_mbinary_file(&self, file: PathBuf, output_dir: PathBuf, by: SplitBy) {
+        match split_mbinary(&file, &output_dir, by).await {
+            Ok(files) => {
+                for file in files {
+                    println!("Wrote {}", file.display());
+                }
+            }
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    /// Returns true when every file was written.
+    pub async fn resample_mbinary_file(
+        &self,
+        file: PathBuf,
+        output_dir: PathBuf,
+        options: ResampleOptions,
+    ) -> bool {
+        match resample_mbinary(&file, &output_dir, &options).await {
+            Ok(files) => {
+                for file in files {
+                    println!("Wrote {}", file.display());
+                }
+                true
+            }
+            Err(e) => {
+                println!("Error {}", e);
+                false
+            }
+        }
+    }
+
+    pub async fn mbinary_to_dbn(&self, file: PathBuf, output: PathBuf) {
+        match to_dbn(&file, &output).await {
+            Ok(count) => println!("Wrote {} records to {}", count, output.display()),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    /// Returns true if every file passed.
+    pub async fn verify_mbinary_files(&self, path: PathBuf) -> bool {
+        match verify_path(&path).await {
+            Ok(results) => {
+                for result in &results {
+                    println!("{}", result);
+                }
+                let failed = results.iter().filter(|r| !r.is_ok()).count();
+                println!(
+                    "{} of {} files passed.",
+                    results.len() - failed,
+                    results.len()
+                );
+                failed == 0
+            }
+            Err(e) => {
+                println!("Error {}", e);
+                false
+            }
+        }
+    }
+
+    /// Remaps with `map`, or with the ids the server has for the file tickers when None.
+    pub async fn remap_mbinary_file(
+        &self,
+        file: PathBuf,
+        output: Option<PathBuf>,
+        map: Option<HashMap<u32, u32>>,
+    ) {
+        let map = match map {
+            Some(map) => map,
+            None => {
+                let client = self.context.get_instrument_client();
+                let metadata = match read_mbinary_file(&file).await {
+                    Ok(mut decoder) => decoder.metadata(),
+                    Err(e) => {
+                        println!("Error {}", e);
+                        return;
+                    }
+                };
+                let Some(metadata) = metadata else {
+                    println!("Error File {} has no metadata.", file.display());
+                    return;
+                };
+                match server_id_map(&metadata, &client).await {
+                    Ok(map) => map,
+                    Err(e) => {
+                        println!("Error {}", e);
+                        return;
+                    }
+                }
+            }
+        };
+
+        if map.is_empty() {
+            println!("Ids already match the server, nothing to remap.");
+            return;
+        }
+
+        let result = match &output {
+            Some(output) => remap_mbinary(&file, output, &map).await,
+            None => remap_mbinary_in_place(&file, &map).await,
+        };
+        match result {
+            Ok(count) => println!(
+                "Remapped {} records in {}",
+                count,
+                output.unwrap_or(file).display()
+            ),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn mbinary_file_stats(&self, file: PathBuf, format: ReportFormat, tz: &str) {
+        let output = match mbinary_stats(&file, tz).await {
+            Ok(stats) => render_stats(&stats, format),
+            Err(e) => Err(e),
+        };
+        match output {
+            Ok(output) => println!("{}", output),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn generate_mbinary_file(&self, output: PathBuf, options: GenerateOptions) {
+        match generate_mbinary(&options, &output).await {
+            Ok(count) => println!("Generated {} records to {}", count, output.display()),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn sample_mbinary_file(&self, file: PathBuf, output: PathBuf, mode: SampleMode) {
+        match sample_mbinary(&file, &output, mode).await {
+            Ok(count) => println!("Sampled {} records to {}", count, output.display()),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn sample_dbn_file(&self, file: PathBuf, output: PathBuf, mode: SampleMode) {
+        match sample_dbn(&file, &output, mode).await {
+            Ok(count) => println!("Sampled {} records to {}", count, output.display()),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn plot_mbinary_file(
+        &self,
+        file: PathBuf,
+        filter: SliceFilter,
+        options: PlotOptions,
+    ) {
+        match plot_series(&file, &filter, options.width).await {
+            Ok(series) => {
+                for series in &series {
+                    println!("{}\n", render_chart(series, &options));
+                }
+            }
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn compress_mbinary_file(
+        &self,
+        file: PathBuf,
+        output: Option<PathBuf>,
+        remove: bool,
+    ) {
+        match compress_mbinary(&file, output, remove).await {
+            Ok((output, count)) => println!("Compressed {} records to {}", count, output.display()),
+            Err(e) => println!("Error {}", e),
+        }
+    }
+
+    pub async fn decompress_mbinary_file(
+        &self,
+        file: PathBuf,
+        output: Option<PathBuf>,
+        remove: bool,
+    ) {
+        match decompress_mbinary(&file, output, remove).await {
+            Ok((output, count)) => {
+                println!("Decompressed {} records to {}", count, output.display())
+            }
             Err(e) => println!("Error {}", e),
         }
     }
@@ -287,7 +1118,7 @@ impl TaskManager {
     }
 
     // Vendors
-    pub async fn update(&self, dataset: Dataset, approval: bool) {
+    pub async fn update(&self, dataset: Dataset, approval: bool, compress: bool) {
         let db_client = self.context.get_databento_client();
         let hist_client = self.context.get_historical_client();
         let inst_client = self.context.get_instrument_client();
@@ -295,7 +1126,7 @@ impl TaskManager {
         let mut db_client = db_client.lock().await;
 
         match db_client
-            .update(dataset, &hist_client, &inst_client, approval)
+            .update(dataset, &hist_client, &inst_client, approval, compress)
             .await
         {
             Ok(_) => (),
@@ -340,11 +1171,18 @@ impl TaskManager {
         dataset: Dataset,
         dbn_filepath: PathBuf,
         midas_filepath: PathBuf,
+        compress: bool,
     ) {
         let db_client = self.context.get_databento_client();
         let inst_client = self.context.get_instrument_client();
         let db_client = db_client.lock().await;
 
+        let midas_filepath = if compress && !is_zstd(&midas_filepath) {
+            compressed_path(&midas_filepath)
+        } else {
+            midas_filepath
+        };
+
         match db_client
             .transform(dataset, &dbn_filepath, &midas_filepath, &inst_client, false)
             .await
@@ -360,6 +1198,7 @@ impl TaskManager {
         download_type: &DownloadType,
         download_path: &PathBuf,
         mbinary_filename: &PathBuf,
+        compress: bool,
     ) {
         let db_client = self.context.get_databento_client();
         let db_client = db_client.lock().await;
@@ -374,6 +1213,7 @@ impl TaskManager {
                 &download_path,
                 &mbinary_filename,
                 &inst_client,
+                compress,
             )
             .await
         {
@@ -390,10 +1230,41 @@ impl TaskManager {
         };
     }
 
-    pub async fn dbn_compare(&self, dbn_filepath: PathBuf, midas_filepath: PathBuf) {
-        match compare_dbn(dbn_filepath, &midas_filepath).await {
-            Ok(_) => (),
-            Err(e) => println!("Error {}", e),
-        };
+    /// Returns true if all records matched.
+    pub async fn dbn_compare(
+        &self,
+        dbn_filepath: PathBuf,
+        midas_filepath: PathBuf,
+        options: CompareOptions,
+    ) -> bool {
+        let report = compare_dbn(dbn_filepath, &midas_filepath, &options).await;
+        write_compare_report(report, &options)
+    }
+}
+
+/// Writes the report and prints the outcome, returns true if all records matched.
+fn write_compare_report(report: Result<CompareReport>, options: &CompareOptions) -> bool {
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            println!("Error {}", e);
+            return false;
+        }
+    };
+
+    if let Err(e) = report.write(&options.output, options.format) {
+        println!("Error {}", e);
+        return false;
+    }
+
+    if report.is_match() {
+        println!("All {} records match successfully.", report.matched());
+        true
+    } else {
+        eprintln!(
+            "Unmatched records detected. Check the output file: {}",
+            options.output.display()
+        );
+        false
     }
 }
@@ -1,7 +1,10 @@
+use crate::cache::HistoricalCache;
 use crate::error::Result;
+use crate::instruments::futures::FuturesRule;
 use crate::vendors::databento::DatabentoVendor;
 use midas_client::{historical::Historical, instrument::Instruments, trading::Trading};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -10,6 +13,11 @@ use tokio::sync::Mutex;
 pub struct Config {
     pub common: CommonConfig,
     pub vendors: VendorsConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Futures expiration rules by root, on top of the built in ones.
+    #[serde(default)]
+    pub futures: HashMap<String, FuturesRule>,
 }
 
 impl Config {
@@ -55,6 +63,18 @@ impl Default for CommonConfig {
     }
 }
 
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Size limit of the historical cache, least recently used files are evicted past it.
+    pub max_size_mb: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { max_size_mb: 2048 }
+    }
+}
+
 #[allow(dead_code)]
 pub struct Context {
     config_dir: PathBuf,
@@ -95,6 +115,14 @@ impl Context {
         self.config.clone()
     }
 
+    pub fn get_historical_cache(&self) -> HistoricalCache {
+        HistoricalCache::new(
+            self.config_dir.join("cache").join("historical"),
+            self.config.cache.max_size_mb * 1024 * 1024,
+            &self.config.common.midas_url,
+        )
+    }
+
     pub fn get_historical_client(&self) -> Historical {
         self.historical_client.clone()
     }
@@ -0,0 +1,367 @@
+use crate::error;
+use crate::error::{Error, Result};
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use time::OffsetDateTime;
+
+/// Formats accepted by every command that takes a date, shown in help and prompts.
+pub const DATE_HELP: &str =
+    "YYYY-MM-DD, YYYY-MM-DD HH:MM:SS, ISO 8601 with offset, now, today, yesterday, -5d, -2h or last-session";
+
+/// Nanoseconds in a 24 hour day.
+pub const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+
+/// Naive formats, read as wall time in the parser's timezone.
+const NAIVE_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+pub fn parse_tz(tz: &str) -> Result<Tz> {
+    tz.parse()
+        .map_err(|_| error!(CustomError, "Invalid timezone: {}", tz))
+}
+
+/// Unix nanoseconds of `datetime`, an error when it falls outside what an i64 holds (1677 to 2262).
+fn nanos<T: TimeZone>(input: &str, datetime: DateTime<T>) -> Result<i64> {
+    datetime
+        .timestamp_nanos_opt()
+        .ok_or_else(|| out_of_range(input))
+}
+
+fn out_of_range(input: &str) -> Error {
+    error!(
+        InvalidDateFormat,
+        "'{}' is out of range, dates must fall between 1677 and 2262.", input
+    )
+}
+
+pub fn to_offset_datetime(unix_nanos: i64) -> Result<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp_nanos(unix_nanos as i128)
+        .map_err(|e| error!(DateError, "{}", e))
+}
+
+/// Turns user supplied dates into unix nanoseconds.
+///
+/// Inputs with an offset (2024-01-02T09:30:00-05:00, ...Z) are absolute. Dates and
+/// datetimes without one are wall time in `tz`. Relative inputs are resolved against the
+/// current time: whole day offsets (-5d, +1w) and today, yesterday, tomorrow and
+/// last-session land on midnight in `tz`, smaller offsets (-2h, -30m, -10s) on the clock.
+#[derive(Debug, Clone, Copy)]
+pub struct DateParser {
+    tz: Tz,
+    now: DateTime<Utc>,
+}
+
+impl DateParser {
+    /// Parser in `tz`, UTC when none is given.
+    pub fn new(tz: Option<&str>) -> Result<Self> {
+        let tz = match tz {
+            Some(tz) => parse_tz(tz)?,
+            None => chrono_tz::UTC,
+        };
+        Ok(Self::at(tz, Utc::now()))
+    }
+
+    /// Parser with a fixed current time, relative inputs resolve against `now`.
+    pub fn at(tz: Tz, now: DateTime<Utc>) -> Self {
+        DateParser { tz, now }
+    }
+
+    pub fn tz(&self) -> Tz {
+        self.tz
+    }
+
+    fn today(&self) -> NaiveDate {
+        self.now.with_timezone(&self.tz).date_naive()
+    }
+
+    fn localize(&self, input: &str, naive: NaiveDateTime) -> Result<i64> {
+        // Ambiguous wall times during a DST change take the earlier instant
+        let datetime = self
+            .tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .ok_or_else(|| {
+                error!(
+                    InvalidDateFormat,
+                    "'{}' does not exist in {}.", input, self.tz
+                )
+            })?;
+
+        nanos(input, datetime)
+    }
+
+    fn midnight(&self, input: &str, date: NaiveDate) -> Result<i64> {
+        self.localize(input, date.and_time(NaiveTime::MIN))
+    }
+
+    /// Midnight `days` away from today.
+    fn days_from_today(&self, input: &str, days: Duration) -> Result<i64> {
+        let date = self
+            .today()
+            .checked_add_signed(days)
+            .ok_or_else(|| out_of_range(input))?;
+        self.midnight(input, date)
+    }
+
+    fn relative(&self, input: &str) -> Option<Result<i64>> {
+        let (sign, rest) = match input.chars().next()? {
+            '-' => (-1, &input[1..]),
+            '+' => (1, &input[1..]),
+            _ => return None,
+        };
+        let split = rest.find(|c: char| !c.is_ascii_digit())?;
+        let (count, unit) = rest.split_at(split);
+        let Ok(count) = count.parse::<i64>() else {
+            return Some(Err(out_of_range(input)));
+        };
+
+        Some(self.shift(input, sign * count, unit))
+    }
+
+    fn shift(&self, input: &str, count: i64, unit: &str) -> Result<i64> {
+        let offset = match unit {
+            "s" => Duration::try_seconds(count),
+            "m" => Duration::try_minutes(count),
+            "h" => Duration::try_hours(count),
+            "d" => Duration::try_days(count),
+            "w" => Duration::try_weeks(count),
+            _ => {
+                return Err(error!(
+                    InvalidDateFormat,
+                    "Invalid unit in '{}', expected s, m, h, d or w.", input
+                ))
+            }
+        }
+        .ok_or_else(|| out_of_range(input))?;
+
+        if matches!(unit, "d" | "w") {
+            return self.days_from_today(input, offset);
+        }
+        let datetime = self
+            .now
+            .checked_add_signed(offset)
+            .ok_or_else(|| out_of_range(input))?;
+        nanos(input, datetime)
+    }
+
+    /// Start of the most recent weekday before today.
+    fn last_session(&self) -> Option<NaiveDate> {
+        let mut date = self.today().pred_opt()?;
+        while matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            date = date.pred_opt()?;
+        }
+        Some(date)
+    }
+
+    pub fn parse(&self, input: &str) -> Result<i64> {
+        let input = input.trim();
+
+        match input.to_lowercase().as_str() {
+            "now" => return nanos(input, self.now),
+            "today" => return self.midnight(input, self.today()),
+            "yesterday" => return self.days_from_today(input, Duration::days(-1)),
+            "tomorrow" => return self.days_from_today(input, Duration::days(1)),
+            "last-session" => {
+                let date = self.last_session().ok_or_else(|| out_of_range(input))?;
+                return self.midnight(input, date);
+            }
+            _ => (),
+        }
+
+        if let Some(result) = self.relative(input) {
+            return result;
+        }
+
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+            return nanos(input, datetime);
+        }
+
+        if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+            return self.midnight(input, date);
+        }
+
+        for format in NAIVE_FORMATS {
+            if let Ok(naive) = NaiveDateTime::parse_from_str(input, format) {
+                return self.localize(input, naive);
+            }
+        }
+
+        Err(error!(
+            InvalidDateFormat,
+            "Invalid date '{}'. Expected one of: {}", input, DATE_HELP
+        ))
+    }
+
+    /// Like `parse`, dates before 1970 are rejected as unsigned timestamps can't hold them.
+    pub fn parse_u64(&self, input: &str) -> Result<u64> {
+        u64::try_from(self.parse(input)?).map_err(|_| {
+            error!(
+                InvalidDateFormat,
+                "Invalid date '{}'. Dates before 1970-01-01 are not supported.", input
+            )
+        })
+    }
+
+    /// Parses an optional bound, None stays None.
+    pub fn parse_optional(&self, input: Option<&str>) -> Result<Option<u64>> {
+        input.map(|input| self.parse_u64(input)).transpose()
+    }
+
+    pub fn parse_offset_datetime(&self, input: &str) -> Result<OffsetDateTime> {
+        to_offset_datetime(self.parse(input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wednesday 2024-01-10 15:30:00 UTC
+    fn parser(tz: Tz) -> DateParser {
+        DateParser::at(tz, Utc.with_ymd_and_hms(2024, 1, 10, 15, 30, 0).unwrap())
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, m, d, h, min, s)
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_absolute() -> Result<()> {
+        let utc_parser = parser(chrono_tz::UTC);
+        let ny_parser = parser(chrono_tz::America::New_York);
+
+        // Validate
+        assert_eq!(utc_parser.parse("2024-01-01")?, utc(2024, 1, 1, 0, 0, 0));
+        assert_eq!(
+            utc_parser.parse("2021-11-01 01:01:01")?,
+            1635728461000000000
+        );
+        assert_eq!(
+            utc_parser.parse("2024-01-02T09:30")?,
+            utc(2024, 1, 2, 9, 30, 0)
+        );
+        assert_eq!(
+            utc_parser.parse("2024-01-02 09:30:00.5")?,
+            utc(2024, 1, 2, 9, 30, 0) + 500_000_000
+        );
+        assert_eq!(ny_parser.parse("2021-12-01 13:00:00")?, 1638381600000000000);
+        assert_eq!(ny_parser.parse("2021-07-01 13:00:00")?, 1625158800000000000);
+
+        // Offsets win over the parser timezone
+        assert_eq!(
+            ny_parser.parse("2024-01-02T09:30:00-06:00")?,
+            utc(2024, 1, 2, 15, 30, 0)
+        );
+        assert_eq!(
+            ny_parser.parse("2024-01-02T09:30:00Z")?,
+            utc(2024, 1, 2, 9, 30, 0)
+        );
+
+        assert!(utc_parser.parse("01/02/2024").is_err());
+        assert!(utc_parser.parse("2024-13-01").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_relative() -> Result<()> {
+        let utc_parser = parser(chrono_tz::UTC);
+        let ny_parser = parser(chrono_tz::America::New_York);
+
+        // Validate
+        assert_eq!(utc_parser.parse("now")?, utc(2024, 1, 10, 15, 30, 0));
+        assert_eq!(utc_parser.parse("today")?, utc(2024, 1, 10, 0, 0, 0));
+        assert_eq!(utc_parser.parse("Yesterday")?, utc(2024, 1, 9, 0, 0, 0));
+        assert_eq!(utc_parser.parse("-5d")?, utc(2024, 1, 5, 0, 0, 0));
+        assert_eq!(utc_parser.parse("+1w")?, utc(2024, 1, 17, 0, 0, 0));
+        assert_eq!(utc_parser.parse("-2h")?, utc(2024, 1, 10, 13, 30, 0));
+        assert_eq!(utc_parser.parse("-30m")?, utc(2024, 1, 10, 15, 0, 0));
+        assert_eq!(ny_parser.parse("today")?, utc(2024, 1, 10, 5, 0, 0));
+        assert!(utc_parser.parse("-5y").is_err());
+
+        // Monday looks back to Friday
+        let monday = DateParser::at(
+            chrono_tz::UTC,
+            Utc.with_ymd_and_hms(2024, 1, 8, 12, 0, 0).unwrap(),
+        );
+        assert_eq!(monday.parse("last-session")?, utc(2024, 1, 5, 0, 0, 0));
+        assert_eq!(utc_parser.parse("last-session")?, utc(2024, 1, 9, 0, 0, 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_out_of_range() -> Result<()> {
+        let utc_parser = parser(chrono_tz::UTC);
+
+        // Validate
+        for input in [
+            "2300-01-01",
+            "1600-01-01 09:30",
+            "2300-01-01T00:00:00Z",
+            "-999999d",
+            "+9999999999w",
+            "-99999999999999h",
+            "-99999999999999999999s",
+        ] {
+            let err = utc_parser.parse(input).unwrap_err();
+            assert!(
+                err.to_string().contains("out of range"),
+                "{}: {}",
+                input,
+                err
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_u64() -> Result<()> {
+        let utc_parser = parser(chrono_tz::UTC);
+
+        // Validate
+        assert_eq!(
+            utc_parser.parse_u64("2024-01-01")?,
+            utc(2024, 1, 1, 0, 0, 0) as u64
+        );
+        assert_eq!(utc_parser.parse_u64("1970-01-01")?, 0);
+        for input in ["1969-12-31 23:59:59", "1900-01-01", "-3000w"] {
+            let err = utc_parser.parse_u64(input).unwrap_err();
+            assert!(
+                err.to_string().contains("before 1970"),
+                "{}: {}",
+                input,
+                err
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_offset_datetime() -> Result<()> {
+        let utc_parser = parser(chrono_tz::UTC);
+
+        // Test
+        let date = utc_parser.parse_offset_datetime("2024-01-01")?;
+
+        // Validate
+        assert_eq!(
+            date,
+            time::macros::datetime!(2024-01-01 0:00:00.0 +00:00:00)
+        );
+        assert!(DateParser::new(Some("Mars/Olympus")).is_err());
+
+        Ok(())
+    }
+}
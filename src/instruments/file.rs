@@ -0,0 +1,329 @@
+use crate::dates::DateParser;
+use crate::error;
+use crate::error::{Error, Result};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use mbinary::enums::Dataset;
+use mbinary::symbols::Instrument;
+use mbinary::vendors::{DatabentoData, VendorData, Vendors};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// One instrument as written in an import or export file, the same flat fields in every format.
+///
+/// Dates take any format the date parser accepts, exports write them as UTC ISO 8601.
+/// Missing dates are stored as 0. The vendor_* fields hold the vendor data, Databento
+/// instruments need all three ex. GLBX.MDP3, mbp-1, raw_symbol.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstrumentRecord {
+    pub ticker: String,
+    pub name: String,
+    pub dataset: String,
+    pub vendor: String,
+    #[serde(default)]
+    pub vendor_dataset: Option<String>,
+    #[serde(default)]
+    pub vendor_schema: Option<String>,
+    #[serde(default)]
+    pub vendor_stype: Option<String>,
+    #[serde(default)]
+    pub first_available: Option<String>,
+    /// Left out on import to keep what the server has.
+    #[serde(default)]
+    pub last_available: Option<String>,
+    #[serde(default)]
+    pub expiration_date: Option<String>,
+    #[serde(default)]
+    pub is_continuous: bool,
+    #[serde(default)]
+    pub active: bool,
+}
+
+/// TOML and JSON files hold the records under an `instruments` key.
+#[derive(Debug, Serialize, Deserialize)]
+struct InstrumentFile {
+    instruments: Vec<InstrumentRecord>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstrumentFileFormat {
+    Toml,
+    Json,
+    Csv,
+}
+
+impl InstrumentFileFormat {
+    /// Format from the file extension ex. universe.toml
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(InstrumentFileFormat::Toml),
+            Some("json") => Ok(InstrumentFileFormat::Json),
+            Some("csv") => Ok(InstrumentFileFormat::Csv),
+            _ => Err(error!(
+                CustomError,
+                "Unknown instrument file type : {}, expected .toml, .json or .csv",
+                path.display()
+            )),
+        }
+    }
+}
+
+pub fn read_instrument_file(path: &Path) -> Result<Vec<InstrumentRecord>> {
+    let format = InstrumentFileFormat::from_path(path)?;
+    let text = fs::read_to_string(path)?;
+
+    match format {
+        InstrumentFileFormat::Toml => Ok(toml::from_str::<InstrumentFile>(&text)
+            .map_err(|e| error!(CustomError, "{}", e))?
+            .instruments),
+        InstrumentFileFormat::Json => {
+            Ok(serde_json::from_str::<InstrumentFile>(&text)?.instruments)
+        }
+        InstrumentFileFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(text.as_bytes());
+            reader
+                .deserialize()
+                .enumerate()
+                .map(|(i, row)| row.map_err(|e| error!(CustomError, "Row {} : {}", i + 1, e)))
+                .collect()
+        }
+    }
+}
+
+pub fn write_instrument_file(path: &Path, records: &[InstrumentRecord]) -> Result<()> {
+    let text = match InstrumentFileFormat::from_path(path)? {
+        InstrumentFileFormat::Toml => toml::to_string(&InstrumentFile {
+            instruments: records.to_vec(),
+        })
+        .map_err(|e| error!(CustomError, "{}", e))?,
+        InstrumentFileFormat::Json => serde_json::to_string_pretty(&InstrumentFile {
+            instruments: records.to_vec(),
+        })?,
+        InstrumentFileFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for record in records {
+                writer
+                    .serialize(record)
+                    .map_err(|e| error!(CustomError, "{}", e))?;
+            }
+            let bytes = writer
+                .into_inner()
+                .map_err(|e| error!(CustomError, "{}", e))?;
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+    };
+
+    fs::write(path, text)?;
+    Ok(())
+}
+
+fn parse_date(dates: &DateParser, date: Option<&str>) -> Result<u64> {
+    match date.map(str::trim) {
+        Some(date) if !date.is_empty() => dates.parse_u64(date),
+        _ => Ok(0),
+    }
+}
+
+/// RFC 3339 in UTC, None for 0.
+pub(crate) fn format_date(unix_nanos: u64) -> Option<String> {
+    (unix_nanos != 0).then(|| {
+        Utc.timestamp_nanos(unix_nanos as i64)
+            .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+    })
+}
+
+fn required<'a>(value: &'a Option<String>, field: &str) -> Result<&'a str> {
+    match value.as_deref().map(str::trim) {
+        Some(value) if !value.is_empty() => Ok(value),
+        _ => Err(error!(CustomError, "Missing required '{}'", field)),
+    }
+}
+
+impl InstrumentRecord {
+    fn vendor_data(&self, vendor: &Vendors) -> Result<VendorData> {
+        match vendor {
+            Vendors::Databento => {
+                let dataset = required(&self.vendor_dataset, "vendor_dataset")?;
+                let schema = required(&self.vendor_schema, "vendor_schema")?;
+                let stype = required(&self.vendor_stype, "vendor_stype")?;
+
+                Ok(VendorData::Databento(DatabentoData {
+                    dataset: dbn::Dataset::from_str(dataset).map_err(|_| {
+                        error!(CustomError, "Invalid 'vendor_dataset': {}", dataset)
+                    })?,
+                    schema: dbn::Schema::from_str(schema)
+                        .map_err(|_| error!(CustomError, "Invalid 'vendor_schema': {}", schema))?,
+                    stype: dbn::SType::from_str(stype)
+                        .map_err(|_| error!(CustomError, "Invalid 'vendor_stype': {}", stype))?,
+                }))
+            }
+            Vendors::Internal => Ok(VendorData::Internal),
+            _ => Err(error!(CustomError, "Vendor not implemeted : {}", vendor)),
+        }
+    }
+
+    /// Checks every field and builds the instrument, dates without an offset are read with
+    /// `dates` and the expiration date with `expiry_dates`.
+    pub fn to_instrument(
+        &self,
+        dates: &DateParser,
+        expiry_dates: &DateParser,
+    ) -> Result<Instrument> {
+        if self.ticker.trim().is_empty() {
+            return Err(error!(CustomError, "Missing required 'ticker'"));
+        }
+        let dataset = Dataset::from_str(&self.dataset.to_lowercase())
+            .map_err(|_| error!(CustomError, "Invalid 'dataset': {}", self.dataset))?;
+        let vendor = Vendors::from_str(&self.vendor.to_lowercase())
+            .map_err(|_| error!(CustomError, "Invalid 'vendor': {}", self.vendor))?;
+        let vendor_data = self.vendor_data(&vendor)?;
+
+        Ok(Instrument {
+            instrument_id: None,
+            ticker: self.ticker.trim().to_string(),
+            name: self.name.clone(),
+            dataset,
+            vendor,
+            vendor_data: vendor_data.encode(),
+            first_available: parse_date(dates, self.first_available.as_deref())?,
+            last_available: parse_date(dates, self.last_available.as_deref())?,
+            expiration_date: parse_date(expiry_dates, self.expiration_date.as_deref())?,
+            is_continuous: self.is_continuous,
+            active: self.active,
+        })
+    }
+
+    pub fn from_instrument(instrument: &Instrument) -> Self {
+        let (vendor_dataset, vendor_schema, vendor_stype) =
+            match VendorData::decode(instrument.vendor_data, &instrument.vendor) {
+                VendorData::Databento(data) => (
+                    Some(data.dataset.as_str().to_string()),
+                    Some(data.schema.as_str().to_string()),
+                    Some(data.stype.as_str().to_string()),
+                ),
+                _ => (None, None, None),
+            };
+
+        InstrumentRecord {
+            ticker: instrument.ticker.clone(),
+            name: instrument.name.clone(),
+            dataset: instrument.dataset.as_str().to_string(),
+            vendor: instrument.vendor.as_str().to_string(),
+            vendor_dataset,
+            vendor_schema,
+            vendor_stype,
+            first_available: format_date(instrument.first_available),
+            last_available: format_date(instrument.last_available),
+            expiration_date: format_date(instrument.expiration_date),
+            is_continuous: instrument.is_continuous,
+            active: instrument.active,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::path::PathBuf;
+
+    fn record() -> InstrumentRecord {
+        InstrumentRecord {
+            ticker: "HEG4".to_string(),
+            name: "Lean Hogs, Feb 2024".to_string(),
+            dataset: "futures".to_string(),
+            vendor: "databento".to_string(),
+            vendor_dataset: Some("GLBX.MDP3".to_string()),
+            vendor_schema: Some("mbp-1".to_string()),
+            vendor_stype: Some("raw_symbol".to_string()),
+            first_available: Some("2024-01-02T00:00:00Z".to_string()),
+            last_available: None,
+            expiration_date: Some("2024-02-14T19:00:00Z".to_string()),
+            is_continuous: false,
+            active: true,
+        }
+    }
+
+    #[test]
+    fn test_to_instrument() -> Result<()> {
+        let utc = DateParser::new(None)?;
+        let new_york = DateParser::new(Some("America/New_York"))?;
+
+        // Test
+        let instrument = InstrumentRecord {
+            expiration_date: Some("2024-02-14 14:00".to_string()),
+            ..record()
+        }
+        .to_instrument(&utc, &new_york)?;
+
+        // Validate
+        assert_eq!(instrument.dataset, Dataset::Futures);
+        assert_eq!(instrument.vendor, Vendors::Databento);
+        assert_eq!(instrument.first_available, 1704153600000000000);
+        assert_eq!(instrument.last_available, 0);
+        assert_eq!(instrument.expiration_date, 1707937200000000000);
+        assert_eq!(InstrumentRecord::from_instrument(&instrument), record());
+
+        let missing_stype = InstrumentRecord {
+            vendor_stype: None,
+            ..record()
+        };
+        assert!(missing_stype.to_instrument(&utc, &new_york).is_err());
+        let bad_dataset = InstrumentRecord {
+            dataset: "crypto".to_string(),
+            ..record()
+        };
+        assert!(bad_dataset.to_instrument(&utc, &new_york).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_date_round_trip() -> Result<()> {
+        let new_york = DateParser::new(Some("America/New_York"))?;
+
+        // Validate
+        for unix_nanos in [
+            1707937200000000000,
+            1707937200123456789,
+            1720000000500000000,
+        ] {
+            let text = format_date(unix_nanos).unwrap();
+            assert_eq!(new_york.parse_u64(&text)?, unix_nanos, "{}", text);
+        }
+        assert_eq!(format_date(0), None);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_instrument_file_round_trip() -> Result<()> {
+        let internal = InstrumentRecord {
+            ticker: "AAPL".to_string(),
+            name: "Apple Inc.".to_string(),
+            dataset: "equities".to_string(),
+            vendor: "internal".to_string(),
+            ..Default::default()
+        };
+        let records = vec![record(), internal];
+
+        for name in ["toml", "json", "csv"] {
+            let path = PathBuf::from(format!("tests/data/test_instruments.{}", name));
+
+            // Test
+            write_instrument_file(&path, &records)?;
+            let read = read_instrument_file(&path)?;
+
+            // Validate
+            assert_eq!(read, records, "{}", name);
+
+            // Cleanup
+            fs::remove_file(&path)?;
+        }
+        assert!(InstrumentFileFormat::from_path(Path::new("universe.yaml")).is_err());
+
+        Ok(())
+    }
+}
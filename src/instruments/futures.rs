@@ -0,0 +1,410 @@
+use crate::dates::DateParser;
+use crate::error;
+use crate::error::{Error, Result};
+use chrono::{Datelike, NaiveDate, TimeZone, Weekday};
+use mbinary::enums::Dataset;
+use mbinary::symbols::Instrument;
+use mbinary::vendors::{DatabentoData, VendorData, Vendors};
+use prettytable::{format, Cell, Row, Table};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// CME month codes, January to December.
+pub const MONTH_CODES: [char; 12] = ['F', 'G', 'H', 'J', 'K', 'M', 'N', 'Q', 'U', 'V', 'X', 'Z'];
+
+/// Day of the month a contract stops trading. Business days are weekdays, exchange
+/// holidays are not accounted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ExpiryDay {
+    /// Nth business day of the month ex. business-day:10
+    BusinessDay(u32),
+    /// N business days before the month's last one ex. last-business-day, last-business-day:2
+    LastBusinessDay(u32),
+    /// Nth weekday of the month ex. weekday:fri:3
+    Weekday(Weekday, u32),
+    /// N business days before a calendar day ex. before-day:15, before-day:25:3
+    BeforeDay(u32, u32),
+}
+
+impl ExpiryDay {
+    pub fn date(&self, year: i32, month: u32) -> Result<NaiveDate> {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)
+            .ok_or_else(|| error!(CustomError, "Invalid month : {}-{}", year, month))?;
+        let business_days: Vec<NaiveDate> = first
+            .iter_days()
+            .take_while(|day| day.month() == month)
+            .filter(|day| !matches!(day.weekday(), Weekday::Sat | Weekday::Sun))
+            .collect();
+
+        let date = match *self {
+            ExpiryDay::BusinessDay(n) => business_days.get((n as usize).wrapping_sub(1)).copied(),
+            ExpiryDay::LastBusinessDay(n) => business_days
+                .len()
+                .checked_sub(n as usize + 1)
+                .map(|i| business_days[i]),
+            ExpiryDay::Weekday(weekday, n) => u8::try_from(n)
+                .ok()
+                .and_then(|n| NaiveDate::from_weekday_of_month_opt(year, month, weekday, n)),
+            ExpiryDay::BeforeDay(day, n) => {
+                let before: Vec<&NaiveDate> = business_days
+                    .iter()
+                    .filter(|date| date.day() < day)
+                    .collect();
+                before.len().checked_sub(n as usize).map(|i| *before[i])
+            }
+        };
+
+        date.ok_or_else(|| error!(CustomError, "No {} in {}-{:02}", self, year, month))
+    }
+}
+
+impl fmt::Display for ExpiryDay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpiryDay::BusinessDay(n) => write!(f, "business-day:{}", n),
+            ExpiryDay::LastBusinessDay(0) => write!(f, "last-business-day"),
+            ExpiryDay::LastBusinessDay(n) => write!(f, "last-business-day:{}", n),
+            ExpiryDay::Weekday(weekday, n) => {
+                write!(f, "weekday:{}:{}", weekday.to_string().to_lowercase(), n)
+            }
+            ExpiryDay::BeforeDay(day, 1) => write!(f, "before-day:{}", day),
+            ExpiryDay::BeforeDay(day, n) => write!(f, "before-day:{}:{}", day, n),
+        }
+    }
+}
+
+impl FromStr for ExpiryDay {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            error!(
+                CustomError,
+                "Invalid expiry rule : {}, expected business-day:N, last-business-day[:N], weekday:DAY:N or before-day:D[:N]",
+                s
+            )
+        };
+        let number = |value: &str| value.parse::<u32>().map_err(|_| invalid());
+        let parts: Vec<&str> = s.trim().split(':').collect();
+
+        match parts.as_slice() {
+            ["business-day", n] => Ok(ExpiryDay::BusinessDay(number(n)?)),
+            ["last-business-day"] => Ok(ExpiryDay::LastBusinessDay(0)),
+            ["last-business-day", n] => Ok(ExpiryDay::LastBusinessDay(number(n)?)),
+            ["weekday", day, n] => Ok(ExpiryDay::Weekday(
+                Weekday::from_str(day).map_err(|_| invalid())?,
+                number(n)?,
+            )),
+            ["before-day", day] => Ok(ExpiryDay::BeforeDay(number(day)?, 1)),
+            ["before-day", day, n] => Ok(ExpiryDay::BeforeDay(number(day)?, number(n)?)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for ExpiryDay {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        ExpiryDay::from_str(&value)
+    }
+}
+
+/// How a product's contracts are named and when they expire, set per root under
+/// `[futures.<ROOT>]` in config.toml.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FuturesRule {
+    /// Name prefix, contracts are named `<name>-MMYY` ex. LeanHogs-0224
+    pub name: String,
+    pub expiry: ExpiryDay,
+    /// Months from the contract month to the expiry month, -1 when it expires the month before.
+    #[serde(default)]
+    pub month_offset: i32,
+    /// Local time trading stops ex. 12:00
+    pub time: String,
+    pub tz: String,
+}
+
+impl FuturesRule {
+    fn new(name: &str, expiry: ExpiryDay, time: &str) -> Self {
+        FuturesRule {
+            name: name.to_string(),
+            expiry,
+            month_offset: 0,
+            time: time.to_string(),
+            tz: "America/Chicago".to_string(),
+        }
+    }
+
+    /// Expiration of the contract for `month` of `year` in unix nanos.
+    pub fn expiration(&self, year: i32, month: u32) -> Result<u64> {
+        let months = year * 12 + month as i32 - 1 + self.month_offset;
+        let date = self
+            .expiry
+            .date(months.div_euclid(12), months.rem_euclid(12) as u32 + 1)?;
+
+        DateParser::new(Some(&self.tz))?.parse_u64(&format!("{} {}", date, self.time))
+    }
+}
+
+/// Rules for the common CME products, config.toml entries take precedence.
+pub fn default_rules() -> HashMap<String, FuturesRule> {
+    let grains = ExpiryDay::BeforeDay(15, 1);
+    let index = ExpiryDay::Weekday(Weekday::Fri, 3);
+
+    [
+        ("HE", "LeanHogs", ExpiryDay::BusinessDay(10), "12:00"),
+        ("LE", "LiveCattle", ExpiryDay::LastBusinessDay(0), "12:00"),
+        ("ZC", "Corn", grains, "12:00"),
+        ("ZS", "Soybeans", grains, "12:00"),
+        ("ZW", "Wheat", grains, "12:00"),
+        ("ZM", "SoybeanMeal", grains, "12:00"),
+        ("ZL", "SoybeanOil", grains, "12:00"),
+        ("ES", "EMiniSP500", index, "08:30"),
+        ("NQ", "EMiniNasdaq100", index, "08:30"),
+        ("YM", "EMiniDow", index, "08:30"),
+        ("RTY", "EMiniRussell2000", index, "08:30"),
+    ]
+    .into_iter()
+    .map(|(root, name, expiry, time)| (root.to_string(), FuturesRule::new(name, expiry, time)))
+    .collect()
+}
+
+/// Rule for `root`, from `configured` first then the defaults.
+pub fn futures_rule(root: &str, configured: &HashMap<String, FuturesRule>) -> Result<FuturesRule> {
+    configured
+        .get(root)
+        .cloned()
+        .or_else(|| default_rules().remove(root))
+        .ok_or_else(|| {
+            error!(
+                CustomError,
+                "No expiration rule for {}, add one under [futures.{}] in config.toml", root, root
+            )
+        })
+}
+
+/// Month codes ex. GJKMNQVZ, returned as month numbers in calendar order.
+pub fn parse_months(codes: &str) -> Result<Vec<u32>> {
+    let mut months = Vec::new();
+
+    for code in codes.trim().to_uppercase().chars() {
+        let month = MONTH_CODES
+            .iter()
+            .position(|c| *c == code)
+            .ok_or_else(|| error!(CustomError, "Invalid month code : {}", code))?
+            as u32
+            + 1;
+        if !months.contains(&month) {
+            months.push(month);
+        }
+    }
+    months.sort();
+
+    if months.is_empty() {
+        return Err(error!(CustomError, "No month codes given"));
+    }
+    Ok(months)
+}
+
+/// Single year or an inclusive range ex. 2024, 2024-2026, spanning at most 10 years as
+/// contract symbols only carry the year's last digit.
+pub fn parse_years(years: &str) -> Result<RangeInclusive<i32>> {
+    let year = |value: &str| {
+        value
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| error!(CustomError, "Invalid years : {}", years))
+    };
+
+    let (start, end) = match years.split_once('-') {
+        Some((start, end)) => (year(start)?, year(end)?),
+        None => (year(years)?, year(years)?),
+    };
+    if start > end {
+        return Err(error!(CustomError, "Invalid years : {}", years));
+    }
+    if end - start >= 10 {
+        return Err(error!(
+            CustomError,
+            "Invalid years : {}, contract symbols repeat after 10 years", years
+        ));
+    }
+    Ok(start..=end)
+}
+
+/// CME raw symbol ex. HEQ4, the year is its last digit.
+pub fn contract_symbol(root: &str, year: i32, month: u32) -> String {
+    format!(
+        "{}{}{}",
+        root,
+        MONTH_CODES[month as usize - 1],
+        year.rem_euclid(10)
+    )
+}
+
+/// Contracts to generate for a root.
+#[derive(Debug, Clone)]
+pub struct FuturesOptions {
+    pub months: Vec<u32>,
+    pub years: RangeInclusive<i32>,
+    /// Databento schema stored in the vendor data ex. mbp-1
+    pub schema: dbn::Schema,
+    pub first_available: u64,
+    pub active: bool,
+}
+
+/// One Databento instrument per contract of the chain, ordered by expiration.
+pub fn futures_chain(
+    root: &str,
+    rule: &FuturesRule,
+    options: &FuturesOptions,
+) -> Result<Vec<Instrument>> {
+    let vendor_data = VendorData::Databento(DatabentoData {
+        dataset: dbn::Dataset::GlbxMdp3,
+        schema: options.schema,
+        stype: dbn::SType::RawSymbol,
+    })
+    .encode();
+    let mut instruments = Vec::new();
+
+    for year in options.years.clone() {
+        for month in &options.months {
+            instruments.push(Instrument {
+                instrument_id: None,
+                ticker: contract_symbol(root, year, *month),
+                name: format!("{}-{:02}{:02}", rule.name, month, year.rem_euclid(100)),
+                dataset: Dataset::Futures,
+                vendor: Vendors::Databento,
+                vendor_data,
+                // Databento updates start from last_available, as with `instrument create`.
+                last_available: options.first_available,
+                first_available: options.first_available,
+                expiration_date: rule.expiration(year, *month)?,
+                is_continuous: false,
+                active: options.active,
+            });
+        }
+    }
+
+    Ok(instruments)
+}
+
+/// Contracts with their expirations in the rule's timezone.
+pub fn chain_table(instruments: &[Instrument], rule: &FuturesRule) -> Result<Table> {
+    let tz = DateParser::new(Some(&rule.tz))?.tz();
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.add_row(Row::new(
+        ["Ticker", "Name", "Expiration"]
+            .iter()
+            .map(|title| Cell::new(title).style_spec("bFg"))
+            .collect(),
+    ));
+
+    for instrument in instruments {
+        let expiration = tz
+            .timestamp_nanos(instrument.expiration_date as i64)
+            .format("%Y-%m-%d %H:%M %Z");
+        table.add_row(Row::new(vec![
+            Cell::new(&instrument.ticker).style_spec("Fg"),
+            Cell::new(&instrument.name),
+            Cell::new(&expiration.to_string()),
+        ]));
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(year: i32, month: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, d).unwrap()
+    }
+
+    #[test]
+    fn test_expiry_day() -> Result<()> {
+        // Validate
+        assert_eq!(ExpiryDay::BusinessDay(10).date(2024, 2)?, day(2024, 2, 14));
+        assert_eq!(
+            ExpiryDay::LastBusinessDay(0).date(2024, 3)?,
+            day(2024, 3, 29)
+        );
+        assert_eq!(
+            ExpiryDay::LastBusinessDay(2).date(2024, 3)?,
+            day(2024, 3, 27)
+        );
+        assert_eq!(
+            ExpiryDay::Weekday(Weekday::Fri, 3).date(2024, 6)?,
+            day(2024, 6, 21)
+        );
+        // 2024-09-15 is a Sunday
+        assert_eq!(ExpiryDay::BeforeDay(15, 1).date(2024, 9)?, day(2024, 9, 13));
+        assert_eq!(ExpiryDay::BeforeDay(25, 3).date(2024, 9)?, day(2024, 9, 20));
+        assert!(ExpiryDay::BusinessDay(30).date(2024, 2).is_err());
+        assert!(ExpiryDay::Weekday(Weekday::Fri, 5).date(2024, 6).is_err());
+        assert!(ExpiryDay::Weekday(Weekday::Fri, 259).date(2024, 6).is_err());
+
+        for rule in [
+            "business-day:10",
+            "last-business-day",
+            "last-business-day:2",
+            "weekday:fri:3",
+            "before-day:15",
+            "before-day:25:3",
+        ] {
+            assert_eq!(ExpiryDay::from_str(rule)?.to_string(), rule);
+        }
+        assert!(ExpiryDay::from_str("weekday:fri").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_futures_chain() -> Result<()> {
+        let options = FuturesOptions {
+            months: parse_months("JG")?,
+            years: parse_years("2024")?,
+            schema: dbn::Schema::Mbp1,
+            first_available: 1704153600000000000,
+            active: true,
+        };
+        let rule = futures_rule("HE", &HashMap::new())?;
+
+        // Test
+        let instruments = futures_chain("HE", &rule, &options)?;
+
+        // Validate
+        assert_eq!(instruments.len(), 2);
+        assert_eq!(instruments[0].ticker, "HEG4");
+        assert_eq!(instruments[0].name, "LeanHogs-0224");
+        // 2024-02-14 12:00 America/Chicago
+        assert_eq!(instruments[0].expiration_date, 1707933600000000000);
+        assert_eq!(instruments[1].ticker, "HEJ4");
+        assert!(instruments
+            .iter()
+            .all(|instrument| instrument.last_available == 1704153600000000000));
+
+        let rule = FuturesRule {
+            month_offset: -1,
+            ..futures_rule("LE", &HashMap::new())?
+        };
+        // LEG4 expires on 2024-02-29, a month earlier it is 2024-01-31
+        assert_eq!(rule.expiration(2024, 2)?, 1706724000000000000);
+        assert_eq!(contract_symbol("ZC", 2030, 12), "ZCZ0");
+
+        assert!(parse_months("GA").is_err());
+        assert_eq!(parse_years("2024-2026")?, 2024..=2026);
+        assert!(parse_years("2026-2024").is_err());
+        assert_eq!(parse_years("2024-2033")?, 2024..=2033);
+        assert!(parse_years("2024-2034").is_err());
+        assert!(futures_rule("XX", &HashMap::new()).is_err());
+
+        Ok(())
+    }
+}
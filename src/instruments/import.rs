@@ -0,0 +1,295 @@
+use crate::dates::DateParser;
+use crate::instruments::file::InstrumentRecord;
+use mbinary::symbols::Instrument;
+use std::collections::HashMap;
+use std::fmt;
+
+/// What importing one row does to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImportAction {
+    Create,
+    /// Exists with different fields and --upsert was given.
+    Update,
+    Unchanged,
+    /// Exists with different fields, left alone without --upsert.
+    Skip,
+}
+
+impl fmt::Display for ImportAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportAction::Create => write!(f, "create"),
+            ImportAction::Update => write!(f, "update"),
+            ImportAction::Unchanged => write!(f, "unchanged"),
+            ImportAction::Skip => write!(f, "exists, skip"),
+        }
+    }
+}
+
+impl ImportAction {
+    /// Outcome once the row was sent.
+    pub fn done(&self) -> &'static str {
+        match self {
+            ImportAction::Create => "created",
+            ImportAction::Update => "updated",
+            ImportAction::Unchanged => "unchanged",
+            ImportAction::Skip => "exists, skipped (use --upsert to update)",
+        }
+    }
+}
+
+/// How an instrument file is imported.
+#[derive(Debug, Clone, Copy)]
+pub struct ImportOptions {
+    /// Update instruments that already exist instead of skipping them.
+    pub upsert: bool,
+    /// Validate and report what would change without sending anything.
+    pub dry_run: bool,
+    pub dates: DateParser,
+    pub expiry_dates: DateParser,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportRow {
+    /// 1 based position in the file.
+    pub row: usize,
+    pub instrument: Instrument,
+    pub action: ImportAction,
+}
+
+/// Converts every record before anything is sent, so a bad row fails the whole import.
+/// Errors name the row and ticker, tickers repeated within a dataset are errors too.
+pub fn validate_records(
+    records: &[InstrumentRecord],
+    dates: &DateParser,
+    expiry_dates: &DateParser,
+) -> std::result::Result<Vec<Instrument>, Vec<String>> {
+    let mut instruments = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen: HashMap<(String, String), usize> = HashMap::new();
+
+    for (i, record) in records.iter().enumerate() {
+        let row = i + 1;
+        match record.to_instrument(dates, expiry_dates) {
+            Ok(instrument) => {
+                let key = (
+                    instrument.ticker.clone(),
+                    instrument.dataset.as_str().to_string(),
+                );
+                if let Some(first) = seen.insert(key, row) {
+                    errors.push(format!(
+                        "Row {} ({}) : duplicate of row {}",
+                        row, instrument.ticker, first
+                    ));
+                }
+                instruments.push(instrument);
+            }
+            Err(e) => errors.push(format!("Row {} ({}) : {}", row, record.ticker, e)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(instruments)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Instrument has no PartialEq, compares every field.
+pub fn same_instrument(a: &Instrument, b: &Instrument) -> bool {
+    a.instrument_id == b.instrument_id
+        && a.ticker == b.ticker
+        && a.name == b.name
+        && a.dataset == b.dataset
+        && a.vendor == b.vendor
+        && a.vendor_data == b.vendor_data
+        && a.first_available == b.first_available
+        && a.last_available == b.last_available
+        && a.expiration_date == b.expiration_date
+        && a.is_continuous == b.is_continuous
+        && a.active == b.active
+}
+
+/// Server instruments by ticker and dataset, the key files and manifests are matched on.
+pub fn by_ticker(existing: &[Instrument]) -> HashMap<(String, &'static str), &Instrument> {
+    existing
+        .iter()
+        .map(|instrument| (ticker_key(instrument), instrument))
+        .collect()
+}
+
+/// Key of `instrument` in `by_ticker`.
+pub fn ticker_key(instrument: &Instrument) -> (String, &'static str) {
+    (instrument.ticker.clone(), instrument.dataset.as_str())
+}
+
+/// Gives a matched instrument the server's id, and the server's dates where it leaves them out.
+pub fn merge_server(instrument: &mut Instrument, server: &Instrument) {
+    instrument.instrument_id = server.instrument_id;
+    if instrument.first_available == 0 {
+        instrument.first_available = server.first_available;
+    }
+    if instrument.last_available == 0 {
+        instrument.last_available = server.last_available;
+    }
+    if instrument.expiration_date == 0 {
+        instrument.expiration_date = server.expiration_date;
+    }
+}
+
+/// Matches the instruments to the server's and decides what to do with each, see `merge_server`
+/// for what updates keep.
+pub fn plan_import(
+    instruments: Vec<Instrument>,
+    existing: &[Instrument],
+    upsert: bool,
+) -> Vec<ImportRow> {
+    let existing = by_ticker(existing);
+
+    instruments
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut instrument)| {
+            let action = match existing.get(&ticker_key(&instrument)) {
+                None => ImportAction::Create,
+                Some(current) => {
+                    merge_server(&mut instrument, current);
+
+                    if same_instrument(&instrument, current) {
+                        ImportAction::Unchanged
+                    } else if upsert {
+                        ImportAction::Update
+                    } else {
+                        ImportAction::Skip
+                    }
+                }
+            };
+
+            ImportRow {
+                row: i + 1,
+                instrument,
+                action,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use mbinary::enums::Dataset;
+    use mbinary::vendors::Vendors;
+
+    fn record(ticker: &str) -> InstrumentRecord {
+        InstrumentRecord {
+            ticker: ticker.to_string(),
+            name: ticker.to_string(),
+            dataset: "futures".to_string(),
+            vendor: "internal".to_string(),
+            first_available: Some("2024-01-02".to_string()),
+            active: true,
+            ..Default::default()
+        }
+    }
+
+    fn server(ticker: &str, id: u32, active: bool) -> Instrument {
+        Instrument {
+            instrument_id: Some(id),
+            ticker: ticker.to_string(),
+            name: ticker.to_string(),
+            dataset: Dataset::Futures,
+            vendor: Vendors::Internal,
+            vendor_data: 0,
+            last_available: 1704240000000000000,
+            first_available: 1704153600000000000,
+            expiration_date: 0,
+            is_continuous: false,
+            active,
+        }
+    }
+
+    #[test]
+    fn test_validate_records() -> Result<()> {
+        let dates = DateParser::new(None)?;
+        let bad_vendor = InstrumentRecord {
+            vendor: "bloomberg".to_string(),
+            ..record("ZC.n.0")
+        };
+        let records = vec![record("HE.n.0"), bad_vendor, record("HE.n.0")];
+
+        // Test
+        let errors = validate_records(&records, &dates, &dates).unwrap_err();
+
+        // Validate
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("Row 2 (ZC.n.0) : "));
+        assert!(errors[0].contains("Invalid 'vendor': bloomberg"));
+        assert_eq!(errors[1], "Row 3 (HE.n.0) : duplicate of row 1");
+        assert_eq!(
+            validate_records(&records[..1], &dates, &dates)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_import() -> Result<()> {
+        let dates = DateParser::new(None)?;
+        let records = vec![record("HE.n.0"), record("ZC.n.0"), record("LE.n.0")];
+        let instruments = validate_records(&records, &dates, &dates).unwrap();
+        let existing = vec![server("HE.n.0", 1, true), server("ZC.n.0", 2, false)];
+
+        // Test
+        let plan = plan_import(instruments.clone(), &existing, false);
+        let upsert = plan_import(instruments, &existing, true);
+
+        // Validate
+        let actions: Vec<ImportAction> = plan.iter().map(|row| row.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ImportAction::Unchanged,
+                ImportAction::Skip,
+                ImportAction::Create
+            ]
+        );
+        assert_eq!(upsert[1].action, ImportAction::Update);
+        assert_eq!(upsert[1].instrument.instrument_id, Some(2));
+        assert_eq!(upsert[1].instrument.last_available, 1704240000000000000);
+        assert_eq!(upsert[2].row, 3);
+        assert_eq!(upsert[2].instrument.instrument_id, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_import_keeps_server_dates() -> Result<()> {
+        let dates = DateParser::new(None)?;
+        let records = vec![InstrumentRecord {
+            name: "Lean Hogs".to_string(),
+            first_available: None,
+            ..record("HE.n.0")
+        }];
+        let instruments = validate_records(&records, &dates, &dates).unwrap();
+        let existing = vec![Instrument {
+            expiration_date: 1707937200000000000,
+            ..server("HE.n.0", 1, true)
+        }];
+
+        // Test
+        let plan = plan_import(instruments, &existing, true);
+
+        // Validate
+        assert_eq!(plan[0].action, ImportAction::Update);
+        assert_eq!(plan[0].instrument.name, "Lean Hogs");
+        assert_eq!(plan[0].instrument.first_available, 1704153600000000000);
+        assert_eq!(plan[0].instrument.last_available, 1704240000000000000);
+        assert_eq!(plan[0].instrument.expiration_date, 1707937200000000000);
+
+        Ok(())
+    }
+}
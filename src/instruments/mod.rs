@@ -0,0 +1,7 @@
+pub mod file;
+pub mod futures;
+pub mod import;
+pub mod options;
+pub mod plan;
+pub mod search;
+pub mod table;
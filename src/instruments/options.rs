@@ -0,0 +1,493 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::databento::extract::read_dbn_file;
+use chrono::{NaiveDate, TimeZone, Utc};
+use dbn::{InstrumentClass, InstrumentDefMsg, UNDEF_PRICE};
+use mbinary::enums::Dataset;
+use mbinary::symbols::Instrument;
+use mbinary::vendors::{DatabentoData, VendorData, Vendors};
+use prettytable::{format, Cell, Row, Table};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+    Call,
+    Put,
+    Both,
+}
+
+impl FromStr for OptionKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "call" | "c" => Ok(OptionKind::Call),
+            "put" | "p" => Ok(OptionKind::Put),
+            "both" => Ok(OptionKind::Both),
+            _ => Err(error!(
+                CustomError,
+                "Invalid option kind : {}, expected call, put or both", s
+            )),
+        }
+    }
+}
+
+impl OptionKind {
+    pub fn list() -> Vec<&'static str> {
+        vec!["both", "call", "put"]
+    }
+
+    fn matches(&self, class: InstrumentClass) -> bool {
+        match self {
+            OptionKind::Call => class == InstrumentClass::Call,
+            OptionKind::Put => class == InstrumentClass::Put,
+            OptionKind::Both => true,
+        }
+    }
+}
+
+/// One option contract from a Databento definition record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionContract {
+    /// OSI symbol as OPRA lists it ex. `SPY   240621C00530000`
+    pub raw_symbol: String,
+    pub underlying: String,
+    pub class: InstrumentClass,
+    pub strike: f64,
+    pub expiration: u64,
+}
+
+impl OptionContract {
+    fn from_definition(definition: &InstrumentDefMsg) -> Result<Option<Self>> {
+        let class = match definition.instrument_class() {
+            Ok(class @ (InstrumentClass::Call | InstrumentClass::Put)) => class,
+            _ => return Ok(None),
+        };
+        if definition.strike_price == UNDEF_PRICE {
+            return Ok(None);
+        }
+
+        Ok(Some(OptionContract {
+            raw_symbol: definition.raw_symbol()?.to_string(),
+            underlying: definition.underlying()?.to_string(),
+            class,
+            strike: definition.strike_price as f64 / 1e9,
+            expiration: definition.expiration,
+        }))
+    }
+
+    pub fn expiration_date(&self) -> NaiveDate {
+        Utc.timestamp_nanos(self.expiration as i64).date_naive()
+    }
+
+    /// ex. SPY 2024-06-21 C 530
+    pub fn name(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.underlying,
+            self.expiration_date(),
+            char::from(self.class),
+            self.strike
+        )
+    }
+}
+
+impl fmt::Display for OptionContract {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Which contracts of a chain become instruments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainFilter {
+    pub kind: OptionKind,
+    /// Inclusive expiration range in unix nanos.
+    pub expiry_start: Option<u64>,
+    pub expiry_end: Option<u64>,
+    /// Strike over underlying price band ex. (0.9, 1.1), needs `spot`.
+    pub moneyness: Option<(f64, f64)>,
+    pub spot: Option<f64>,
+}
+
+impl Default for ChainFilter {
+    fn default() -> Self {
+        ChainFilter {
+            kind: OptionKind::Both,
+            expiry_start: None,
+            expiry_end: None,
+            moneyness: None,
+            spot: None,
+        }
+    }
+}
+
+impl ChainFilter {
+    fn keep(&self, contract: &OptionContract) -> bool {
+        let moneyness = match (self.moneyness, self.spot) {
+            (Some((low, high)), Some(spot)) => {
+                let ratio = contract.strike / spot;
+                low <= ratio && ratio <= high
+            }
+            _ => true,
+        };
+
+        self.kind.matches(contract.class)
+            && contract.expiration >= self.expiry_start.unwrap_or_default()
+            && contract.expiration <= self.expiry_end.unwrap_or(u64::MAX)
+            && moneyness
+    }
+}
+
+/// How the selected contracts become instruments and are sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainOptions {
+    /// Databento schema stored in the vendor data ex. mbp-1
+    pub schema: dbn::Schema,
+    pub first_available: u64,
+    pub active: bool,
+    /// Update contracts that already exist instead of skipping them.
+    pub upsert: bool,
+    /// Show what would change without sending anything.
+    pub dry_run: bool,
+}
+
+/// Moneyness band ex. 0.9-1.1
+pub fn parse_moneyness(band: &str) -> Result<(f64, f64)> {
+    let invalid = || {
+        error!(
+            CustomError,
+            "Invalid moneyness band : {}, ex. 0.9-1.1", band
+        )
+    };
+    let (low, high) = band.split_once('-').ok_or_else(invalid)?;
+    let low: f64 = low.trim().parse().map_err(|_| invalid())?;
+    let high: f64 = high.trim().parse().map_err(|_| invalid())?;
+
+    if low <= 0.0 || low > high {
+        return Err(invalid());
+    }
+    Ok((low, high))
+}
+
+/// Definition file of a prior `databento download` for the underlying's parent symbol
+/// ex. `OPRA.PILLAR_definition_SPY.OPT_2024-06-03T00:00:00Z_...dbn` in `dir`.
+pub fn find_definition_file(dir: &Path, underlying: &str, date: NaiveDate) -> Result<PathBuf> {
+    let prefix = format!(
+        "{}_{}_",
+        dbn::Dataset::OpraPillar.as_str(),
+        dbn::Schema::Definition.as_str()
+    );
+    let symbol = format!("{}.OPT", underlying);
+    let start = format!("_{}T", date);
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| error!(CustomError, "Unable to read {} : {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(&prefix)
+                        && name.split('_').any(|part| part == symbol)
+                        && name.contains(&start)
+                })
+        })
+        .collect();
+    files.sort();
+
+    files.pop().ok_or_else(|| {
+        error!(
+            CustomError,
+            "No definition file for {} on {} in {}, download one with : databento download --dataset OPRA.PILLAR --schema definition --stype parent --tickers {} --start {} --end {}",
+            underlying,
+            date,
+            dir.display(),
+            symbol,
+            date,
+            date.succ_opt().unwrap_or(date)
+        )
+    })
+}
+
+/// `file` when given, otherwise the definition file downloaded to `dir`, or to RAW_DIR like
+/// `databento download`.
+pub fn definition_file(
+    file: Option<&str>,
+    dir: Option<&str>,
+    underlying: &str,
+    date: NaiveDate,
+) -> Result<PathBuf> {
+    if let Some(file) = file {
+        return Ok(PathBuf::from(file));
+    }
+
+    let dir = match dir {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(
+            std::env::var("RAW_DIR")
+                .map_err(|_| error!(CustomError, "Environment variable RAW_DIR is not set."))?,
+        ),
+    };
+    find_definition_file(&dir.join("databento"), underlying, date)
+}
+
+/// Option contracts of `underlying` in a definition file, the latest definition of each symbol.
+pub async fn read_option_chain(file: &Path, underlying: &str) -> Result<Vec<OptionContract>> {
+    let (mut decoder, _) = read_dbn_file(file.to_path_buf()).await?;
+    let mut contracts: BTreeMap<String, OptionContract> = BTreeMap::new();
+
+    while let Some(definition) = decoder.decode_record::<InstrumentDefMsg>().await? {
+        if let Some(contract) = OptionContract::from_definition(definition)? {
+            if contract.underlying.eq_ignore_ascii_case(underlying) {
+                contracts.insert(contract.raw_symbol.clone(), contract);
+            }
+        }
+    }
+
+    Ok(contracts.into_values().collect())
+}
+
+/// Contracts the filter keeps, by expiration, strike then calls before puts.
+pub fn filter_chain(contracts: Vec<OptionContract>, filter: &ChainFilter) -> Vec<OptionContract> {
+    let mut kept: Vec<OptionContract> = contracts
+        .into_iter()
+        .filter(|contract| filter.keep(contract))
+        .collect();
+
+    kept.sort_by(|a, b| {
+        a.expiration
+            .cmp(&b.expiration)
+            .then(a.strike.total_cmp(&b.strike))
+            .then((a.class as u8).cmp(&(b.class as u8)))
+    });
+    kept
+}
+
+/// Databento OPRA instruments for the contracts, updates start from `first_available`.
+pub fn option_instruments(
+    contracts: &[OptionContract],
+    schema: dbn::Schema,
+    first_available: u64,
+    active: bool,
+) -> Vec<Instrument> {
+    let vendor_data = VendorData::Databento(DatabentoData {
+        dataset: dbn::Dataset::OpraPillar,
+        schema,
+        stype: dbn::SType::RawSymbol,
+    })
+    .encode();
+
+    contracts
+        .iter()
+        .map(|contract| Instrument {
+            instrument_id: None,
+            ticker: contract.raw_symbol.clone(),
+            name: contract.name(),
+            dataset: Dataset::Option,
+            vendor: Vendors::Databento,
+            vendor_data,
+            last_available: first_available,
+            first_available,
+            expiration_date: contract.expiration,
+            is_continuous: false,
+            active,
+        })
+        .collect()
+}
+
+pub fn chain_table(contracts: &[OptionContract]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.add_row(Row::new(
+        ["Ticker", "Expiration", "Type", "Strike"]
+            .iter()
+            .map(|title| Cell::new(title).style_spec("bFg"))
+            .collect(),
+    ));
+
+    for contract in contracts {
+        table.add_row(Row::new(vec![
+            Cell::new(&contract.raw_symbol).style_spec("Fg"),
+            Cell::new(&contract.expiration_date().to_string()),
+            Cell::new(&char::from(contract.class).to_string()),
+            Cell::new(&contract.strike.to_string()).style_spec("r"),
+        ]));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbn::encode::{DbnEncoder, EncodeRecord};
+    use dbn::record::str_to_c_chars;
+    use dbn::{MetadataBuilder, RecordHeader, SType};
+    use serial_test::serial;
+
+    // 2024-06-21 and 2024-07-19 00:00:00 UTC
+    const JUNE: u64 = 1718928000000000000;
+    const JULY: u64 = 1721347200000000000;
+
+    fn definition(
+        id: u32,
+        symbol: &str,
+        underlying: &str,
+        class: InstrumentClass,
+        strike: f64,
+        expiration: u64,
+    ) -> Result<InstrumentDefMsg> {
+        Ok(InstrumentDefMsg {
+            hd: RecordHeader::new::<InstrumentDefMsg>(
+                dbn::rtype::INSTRUMENT_DEF,
+                dbn::Publisher::OpraPillarOpra as u16,
+                id,
+                1717372800000000000,
+            ),
+            raw_symbol: str_to_c_chars(symbol)?,
+            underlying: str_to_c_chars(underlying)?,
+            instrument_class: u8::from(class) as std::ffi::c_char,
+            strike_price: (strike * 1e9) as i64,
+            expiration,
+            ..Default::default()
+        })
+    }
+
+    fn write_definitions(path: &Path) -> Result<()> {
+        let metadata = MetadataBuilder::new()
+            .dataset(dbn::Dataset::OpraPillar.as_str().to_string())
+            .schema(Some(dbn::Schema::Definition))
+            .start(1717372800000000000)
+            .stype_in(Some(SType::Parent))
+            .stype_out(SType::InstrumentId)
+            .build();
+        let records = vec![
+            definition(
+                1,
+                "SPY   240621C00500000",
+                "SPY",
+                InstrumentClass::Call,
+                500.0,
+                JUNE,
+            )?,
+            definition(
+                2,
+                "SPY   240621P00530000",
+                "SPY",
+                InstrumentClass::Put,
+                530.0,
+                JUNE,
+            )?,
+            definition(
+                3,
+                "SPY   240621C00600000",
+                "SPY",
+                InstrumentClass::Call,
+                600.0,
+                JUNE,
+            )?,
+            definition(
+                4,
+                "SPY   240719C00530000",
+                "SPY",
+                InstrumentClass::Call,
+                530.0,
+                JULY,
+            )?,
+            // Repeated definition, the later one is kept
+            definition(
+                2,
+                "SPY   240621P00530000",
+                "SPY",
+                InstrumentClass::Put,
+                530.0,
+                JUNE,
+            )?,
+            definition(
+                5,
+                "SPXW  240621C05300000",
+                "SPXW",
+                InstrumentClass::Call,
+                5300.0,
+                JUNE,
+            )?,
+        ];
+
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = DbnEncoder::with_zstd(file, &metadata)?;
+        for record in &records {
+            encoder.encode_record(record)?;
+        }
+        encoder.flush()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_read_option_chain() -> Result<()> {
+        let dir = PathBuf::from("tests/data/databento");
+        let path = dir
+            .join("OPRA.PILLAR_definition_SPY.OPT_2024-06-03T00:00:00Z_2024-06-04T00:00:00Z.dbn");
+        write_definitions(&path)?;
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+
+        // Test
+        let file = find_definition_file(&dir, "SPY", date)?;
+        let contracts = read_option_chain(&file, "SPY").await?;
+        let june = filter_chain(
+            contracts.clone(),
+            &ChainFilter {
+                expiry_end: Some(JUNE),
+                moneyness: Some((0.95, 1.05)),
+                spot: Some(530.0),
+                ..Default::default()
+            },
+        );
+        let calls = filter_chain(
+            contracts.clone(),
+            &ChainFilter {
+                kind: OptionKind::Call,
+                ..Default::default()
+            },
+        );
+
+        // Validate
+        assert_eq!(file, path);
+        assert_eq!(contracts.len(), 4);
+        assert_eq!(june.len(), 1);
+        assert_eq!(june[0].raw_symbol, "SPY   240621P00530000");
+        assert_eq!(june[0].name(), "SPY 2024-06-21 P 530");
+        assert_eq!(
+            calls
+                .iter()
+                .map(|contract| contract.strike)
+                .collect::<Vec<f64>>(),
+            vec![500.0, 600.0, 530.0]
+        );
+
+        let instruments = option_instruments(&june, dbn::Schema::Mbp1, JUNE, true);
+        assert_eq!(instruments[0].dataset, Dataset::Option);
+        assert_eq!(instruments[0].expiration_date, JUNE);
+        assert_eq!(instruments[0].last_available, JUNE);
+        assert!(find_definition_file(&dir, "QQQ", date).is_err());
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_moneyness() -> Result<()> {
+        // Validate
+        assert_eq!(parse_moneyness("0.9-1.1")?, (0.9, 1.1));
+        assert!(parse_moneyness("1.1-0.9").is_err());
+        assert!(parse_moneyness("0.9").is_err());
+        assert_eq!(OptionKind::from_str("PUT")?, OptionKind::Put);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,371 @@
+use crate::dates::DateParser;
+use crate::instruments::file::InstrumentRecord;
+use crate::instruments::import::{by_ticker, merge_server, same_instrument, ticker_key};
+use mbinary::symbols::Instrument;
+use std::fmt;
+
+/// Change `apply` makes to one instrument, in the order they are carried out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PlanAction {
+    Create,
+    Update,
+    Deactivate,
+    Delete,
+}
+
+impl PlanAction {
+    fn symbol(&self) -> char {
+        match self {
+            PlanAction::Create => '+',
+            PlanAction::Update => '~',
+            PlanAction::Deactivate => '-',
+            PlanAction::Delete => 'x',
+        }
+    }
+
+    /// Outcome once the change was sent.
+    pub fn done(&self) -> &'static str {
+        match self {
+            PlanAction::Create => "created",
+            PlanAction::Update => "updated",
+            PlanAction::Deactivate => "deactivated",
+            PlanAction::Delete => "deleted",
+        }
+    }
+}
+
+impl fmt::Display for PlanAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanAction::Create => write!(f, "create"),
+            PlanAction::Update => write!(f, "update"),
+            PlanAction::Deactivate => write!(f, "deactivate"),
+            PlanAction::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/// How a manifest is read and reconciled with the server.
+#[derive(Debug, Clone, Copy)]
+pub struct PlanOptions {
+    /// Delete server instruments missing from the manifest instead of deactivating them.
+    pub prune: bool,
+    pub dates: DateParser,
+    pub expiry_dates: DateParser,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanItem {
+    pub action: PlanAction,
+    /// State after the change, the server's current state for deletes.
+    pub instrument: Instrument,
+    /// Changed fields as `field: old -> new`.
+    pub changes: Vec<String>,
+}
+
+/// Differences between a manifest and the server, limited to the datasets in the manifest.
+#[derive(Debug, Clone, Default)]
+pub struct InstrumentPlan {
+    pub items: Vec<PlanItem>,
+    pub unchanged: usize,
+}
+
+impl InstrumentPlan {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn count(&self, action: PlanAction) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.action == action)
+            .count()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Plan: {} to create, {} to update, {} to deactivate, {} to delete, {} unchanged.",
+            self.count(PlanAction::Create),
+            self.count(PlanAction::Update),
+            self.count(PlanAction::Deactivate),
+            self.count(PlanAction::Delete),
+            self.unchanged
+        )
+    }
+}
+
+impl fmt::Display for InstrumentPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.items {
+            writeln!(
+                f,
+                "{} {:<11}{} ({})",
+                item.action.symbol(),
+                item.action.to_string(),
+                item.instrument.ticker,
+                item.instrument.dataset.as_str()
+            )?;
+            for change in &item.changes {
+                writeln!(f, "      {}", change)?;
+            }
+        }
+        write!(f, "{}", self.summary())
+    }
+}
+
+/// Fields compared in a plan, as they read in a manifest.
+fn record_fields(record: &InstrumentRecord) -> Vec<(&'static str, String)> {
+    let text = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+
+    vec![
+        ("ticker", record.ticker.clone()),
+        ("dataset", record.dataset.clone()),
+        ("name", record.name.clone()),
+        ("vendor", record.vendor.clone()),
+        ("vendor_dataset", text(&record.vendor_dataset)),
+        ("vendor_schema", text(&record.vendor_schema)),
+        ("vendor_stype", text(&record.vendor_stype)),
+        ("first_available", text(&record.first_available)),
+        ("last_available", text(&record.last_available)),
+        ("expiration_date", text(&record.expiration_date)),
+        ("is_continuous", record.is_continuous.to_string()),
+        ("active", record.active.to_string()),
+    ]
+}
+
+/// Changed fields between the server's instrument and the desired one.
+pub fn instrument_changes(current: &Instrument, desired: &Instrument) -> Vec<String> {
+    let current = record_fields(&InstrumentRecord::from_instrument(current));
+    let desired = record_fields(&InstrumentRecord::from_instrument(desired));
+
+    current
+        .into_iter()
+        .zip(desired)
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| format!("{}: {} -> {}", field, old, new))
+        .collect()
+}
+
+/// True when `desired` only turns off an active instrument.
+fn is_deactivation(current: &Instrument, desired: &Instrument) -> bool {
+    current.active
+        && !desired.active
+        && same_instrument(
+            &Instrument {
+                active: true,
+                ..desired.clone()
+            },
+            current,
+        )
+}
+
+/// Diffs the manifest against the server's instruments of the same datasets.
+///
+/// Instruments are matched like an import, by ticker and dataset with dates left out of the
+/// manifest keeping the server's values. Server instruments missing from the manifest are deactivated, or deleted
+/// with `prune`. An update that only sets active to false counts as a deactivation.
+pub fn plan_manifest(
+    desired: Vec<Instrument>,
+    existing: &[Instrument],
+    prune: bool,
+) -> InstrumentPlan {
+    let mut plan = InstrumentPlan::default();
+    let mut current = by_ticker(existing);
+    current.retain(|_, instrument| desired.iter().any(|d| d.dataset == instrument.dataset));
+
+    for mut instrument in desired {
+        let server = match current.remove(&ticker_key(&instrument)) {
+            Some(server) => server,
+            None => {
+                plan.items.push(PlanItem {
+                    action: PlanAction::Create,
+                    instrument,
+                    changes: Vec::new(),
+                });
+                continue;
+            }
+        };
+
+        merge_server(&mut instrument, server);
+        if same_instrument(&instrument, server) {
+            plan.unchanged += 1;
+            continue;
+        }
+
+        let action = if is_deactivation(server, &instrument) {
+            PlanAction::Deactivate
+        } else {
+            PlanAction::Update
+        };
+        plan.items.push(PlanItem {
+            action,
+            changes: instrument_changes(server, &instrument),
+            instrument,
+        });
+    }
+
+    for server in current.into_values() {
+        if prune {
+            plan.items.push(PlanItem {
+                action: PlanAction::Delete,
+                instrument: server.clone(),
+                changes: Vec::new(),
+            });
+        } else if server.active {
+            let instrument = Instrument {
+                active: false,
+                ..server.clone()
+            };
+            plan.items.push(PlanItem {
+                action: PlanAction::Deactivate,
+                changes: instrument_changes(server, &instrument),
+                instrument,
+            });
+        } else {
+            plan.unchanged += 1;
+        }
+    }
+
+    plan.items.sort_by(|a, b| {
+        (
+            a.action,
+            a.instrument.dataset.as_str(),
+            &a.instrument.ticker,
+        )
+            .cmp(&(
+                b.action,
+                b.instrument.dataset.as_str(),
+                &b.instrument.ticker,
+            ))
+    });
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mbinary::enums::Dataset;
+    use mbinary::vendors::Vendors;
+
+    // 2024-01-02 00:00:00 UTC
+    const START: u64 = 1704153600000000000;
+
+    fn instrument(ticker: &str, dataset: Dataset, id: Option<u32>, active: bool) -> Instrument {
+        Instrument {
+            instrument_id: id,
+            ticker: ticker.to_string(),
+            name: ticker.to_string(),
+            dataset,
+            vendor: Vendors::Internal,
+            vendor_data: 0,
+            last_available: 0,
+            first_available: START,
+            expiration_date: 0,
+            is_continuous: false,
+            active,
+        }
+    }
+
+    #[test]
+    fn test_plan_manifest() {
+        let desired = vec![
+            instrument("HE.n.0", Dataset::Futures, None, true),
+            Instrument {
+                name: "Corn".to_string(),
+                ..instrument("ZC.n.0", Dataset::Futures, None, true)
+            },
+            instrument("LE.n.0", Dataset::Futures, None, false),
+            instrument("NQ.n.0", Dataset::Futures, None, true),
+        ];
+        let existing = vec![
+            Instrument {
+                last_available: START + 1,
+                ..instrument("HE.n.0", Dataset::Futures, Some(1), true)
+            },
+            instrument("ZC.n.0", Dataset::Futures, Some(2), true),
+            instrument("LE.n.0", Dataset::Futures, Some(3), true),
+            instrument("GF.n.0", Dataset::Futures, Some(4), true),
+            instrument("KE.n.0", Dataset::Futures, Some(5), false),
+            // Not in a dataset of the manifest
+            instrument("AAPL", Dataset::Equities, Some(6), true),
+        ];
+
+        // Test
+        let plan = plan_manifest(desired.clone(), &existing, false);
+        let pruned = plan_manifest(desired, &existing, true);
+
+        // Validate
+        let actions: Vec<(PlanAction, &str)> = plan
+            .items
+            .iter()
+            .map(|item| (item.action, item.instrument.ticker.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (PlanAction::Create, "NQ.n.0"),
+                (PlanAction::Update, "ZC.n.0"),
+                (PlanAction::Deactivate, "GF.n.0"),
+                (PlanAction::Deactivate, "LE.n.0"),
+            ]
+        );
+        assert_eq!(plan.unchanged, 2);
+        assert_eq!(plan.items[1].changes, vec!["name: ZC.n.0 -> Corn"]);
+        assert_eq!(plan.items[1].instrument.instrument_id, Some(2));
+        assert!(!plan.items[2].instrument.active);
+
+        assert_eq!(pruned.count(PlanAction::Delete), 2);
+        assert_eq!(pruned.count(PlanAction::Deactivate), 1);
+        assert_eq!(
+            pruned.summary(),
+            "Plan: 1 to create, 1 to update, 1 to deactivate, 2 to delete, 1 unchanged."
+        );
+    }
+
+    #[test]
+    fn test_instrument_changes() {
+        let current = instrument("HE.n.0", Dataset::Futures, Some(1), true);
+        let desired = Instrument {
+            ticker: "HEG4".to_string(),
+            dataset: Dataset::Equities,
+            ..current.clone()
+        };
+
+        // Test
+        let changes = instrument_changes(&current, &desired);
+
+        // Validate
+        assert_eq!(
+            changes,
+            vec!["ticker: HE.n.0 -> HEG4", "dataset: futures -> equities"]
+        );
+        assert!(instrument_changes(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_plan_deactivate_with_changes() {
+        let desired = vec![
+            Instrument {
+                name: "Lean Hogs".to_string(),
+                ..instrument("HE.n.0", Dataset::Futures, None, false)
+            },
+            instrument("ZC.n.0", Dataset::Futures, None, false),
+        ];
+        let existing = vec![
+            instrument("HE.n.0", Dataset::Futures, Some(1), true),
+            instrument("ZC.n.0", Dataset::Futures, Some(2), true),
+        ];
+
+        // Test
+        let plan = plan_manifest(desired, &existing, false);
+
+        // Validate
+        assert_eq!(plan.items[0].action, PlanAction::Update);
+        assert_eq!(
+            plan.items[0].changes,
+            vec!["name: HE.n.0 -> Lean Hogs", "active: true -> false"]
+        );
+        assert_eq!(plan.items[1].action, PlanAction::Deactivate);
+        assert_eq!(plan.items[1].instrument.ticker, "ZC.n.0");
+    }
+}
@@ -0,0 +1,164 @@
+use crate::dates::NANOS_PER_DAY;
+use crate::error;
+use crate::error::{Error, Result};
+use mbinary::symbols::Instrument;
+use regex::Regex;
+
+/// Ticker pattern as an anchored regex. Globs take `*` and `?` and ignore case.
+pub fn ticker_pattern(pattern: &str, regex: bool) -> Result<Regex> {
+    let expression = if regex {
+        pattern.to_string()
+    } else {
+        let glob: String = pattern
+            .chars()
+            .map(|c| match c {
+                '*' => ".*".to_string(),
+                '?' => ".".to_string(),
+                c => regex::escape(&c.to_string()),
+            })
+            .collect();
+        format!("(?i)^{}$", glob)
+    };
+
+    Regex::new(&expression)
+        .map_err(|e| error!(CustomError, "Invalid ticker pattern '{}': {}", pattern, e))
+}
+
+/// Filters applied to the instruments listed by the server, None matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct InstrumentFilter {
+    pub ticker: Option<Regex>,
+    pub active: Option<bool>,
+    pub continuous: Option<bool>,
+    /// Expiration strictly before, instruments without one never match.
+    pub expiring_before: Option<u64>,
+    /// Expiration strictly after, instruments without one never match.
+    pub expiring_after: Option<u64>,
+    /// Data ending before this time is stale, instruments without data always are.
+    pub stale_before: Option<u64>,
+}
+
+impl InstrumentFilter {
+    /// Stale cutoff `days` before `now`, both in unix nanoseconds.
+    pub fn stale_before(now: u64, days: u64) -> u64 {
+        now.saturating_sub(days.saturating_mul(NANOS_PER_DAY))
+    }
+
+    pub fn matches(&self, instrument: &Instrument) -> bool {
+        let expiration = instrument.expiration_date;
+
+        self.ticker
+            .as_ref()
+            .is_none_or(|ticker| ticker.is_match(&instrument.ticker))
+            && self.active.is_none_or(|active| instrument.active == active)
+            && self
+                .continuous
+                .is_none_or(|continuous| instrument.is_continuous == continuous)
+            && self
+                .expiring_before
+                .is_none_or(|before| expiration != 0 && expiration < before)
+            && self
+                .expiring_after
+                .is_none_or(|after| expiration != 0 && expiration > after)
+            && self
+                .stale_before
+                .is_none_or(|cutoff| instrument.last_available < cutoff)
+    }
+
+    pub fn apply(&self, instruments: Vec<Instrument>) -> Vec<Instrument> {
+        instruments
+            .into_iter()
+            .filter(|instrument| self.matches(instrument))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mbinary::enums::Dataset;
+    use mbinary::vendors::Vendors;
+
+    // 2024-01-10 00:00:00 UTC
+    const NOW: u64 = 1704844800000000000;
+
+    fn instrument(ticker: &str, last_available: u64, expiration_date: u64) -> Instrument {
+        Instrument {
+            instrument_id: None,
+            ticker: ticker.to_string(),
+            name: ticker.to_string(),
+            dataset: Dataset::Futures,
+            vendor: Vendors::Databento,
+            vendor_data: 0,
+            last_available,
+            first_available: 0,
+            expiration_date,
+            is_continuous: ticker.contains(".n."),
+            active: true,
+        }
+    }
+
+    fn tickers(instruments: Vec<Instrument>) -> Vec<String> {
+        instruments.into_iter().map(|i| i.ticker).collect()
+    }
+
+    #[test]
+    fn test_ticker_pattern() -> Result<()> {
+        // Test
+        let glob = ticker_pattern("he*", false)?;
+        let single = ticker_pattern("HE?4", false)?;
+        let regex = ticker_pattern("^(HE|LE)[GJ]4$", true)?;
+
+        // Validate
+        assert!(glob.is_match("HEG4"));
+        assert!(glob.is_match("HE.n.0"));
+        assert!(!glob.is_match("LHE"));
+        assert!(single.is_match("HEG4"));
+        assert!(!single.is_match("HE.n.0"));
+        assert!(regex.is_match("LEJ4"));
+        assert!(!regex.is_match("LEM4"));
+        assert!(ticker_pattern("HE(", true).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_instrument_filter() -> Result<()> {
+        let day = NANOS_PER_DAY;
+        let instruments = vec![
+            instrument("HE.n.0", NOW - day, 0),
+            instrument("HEG4", NOW - 5 * day, NOW + 30 * day),
+            instrument("HEJ4", NOW - 5 * day, NOW + 90 * day),
+            instrument("LEG4", 0, NOW + 30 * day),
+            Instrument {
+                active: false,
+                ..instrument("ZCH4", NOW - 10 * day, NOW + 60 * day)
+            },
+        ];
+
+        // Test
+        let stale = InstrumentFilter {
+            active: Some(true),
+            stale_before: Some(InstrumentFilter::stale_before(NOW, 3)),
+            ..Default::default()
+        };
+        let expiring = InstrumentFilter {
+            ticker: Some(ticker_pattern("HE*", false)?),
+            continuous: Some(false),
+            expiring_after: Some(NOW),
+            expiring_before: Some(NOW + 60 * day),
+            ..Default::default()
+        };
+
+        // Validate
+        assert_eq!(
+            tickers(stale.apply(instruments.clone())),
+            vec!["HEG4", "HEJ4", "LEG4"]
+        );
+        assert_eq!(tickers(expiring.apply(instruments.clone())), vec!["HEG4"]);
+        assert_eq!(InstrumentFilter::default().apply(instruments).len(), 5);
+        assert_eq!(InstrumentFilter::stale_before(NOW, u64::MAX), 0);
+
+        Ok(())
+    }
+}
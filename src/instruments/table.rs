@@ -0,0 +1,103 @@
+use crate::instruments::file::format_date;
+use crate::table::Tabular;
+use mbinary::symbols::Instrument;
+use mbinary::vendors::VendorData;
+use serde_json::{json, Value};
+
+impl Tabular for Instrument {
+    fn columns() -> &'static [&'static str] {
+        &[
+            "id",
+            "ticker",
+            "name",
+            "dataset",
+            "vendor",
+            "vendor_data",
+            "first_available",
+            "last_available",
+            "expiration_date",
+            "continuous",
+            "active",
+        ]
+    }
+
+    fn cells(&self) -> Vec<Value> {
+        let vendor_data = match VendorData::decode(self.vendor_data, &self.vendor) {
+            VendorData::Databento(data) => json!(format!(
+                "{}/{}/{}",
+                data.dataset.as_str(),
+                data.schema.as_str(),
+                data.stype.as_str()
+            )),
+            _ => Value::Null,
+        };
+
+        vec![
+            json!(self.instrument_id),
+            json!(self.ticker),
+            json!(self.name),
+            json!(self.dataset.as_str()),
+            json!(self.vendor.as_str()),
+            vendor_data,
+            json!(format_date(self.first_available)),
+            json!(format_date(self.last_available)),
+            json!(format_date(self.expiration_date)),
+            json!(self.is_continuous),
+            json!(self.active),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use mbinary::enums::Dataset;
+    use mbinary::vendors::{DatabentoData, Vendors};
+
+    #[test]
+    fn test_instrument_cells() -> Result<()> {
+        let vendor_data = VendorData::Databento(DatabentoData {
+            schema: dbn::Schema::Mbp1,
+            dataset: dbn::Dataset::GlbxMdp3,
+            stype: dbn::SType::RawSymbol,
+        });
+        let instrument = Instrument {
+            instrument_id: Some(7),
+            ticker: "HEG4".to_string(),
+            name: "LeanHogs-0224".to_string(),
+            dataset: Dataset::Futures,
+            vendor: Vendors::Databento,
+            vendor_data: vendor_data.encode(),
+            last_available: 0,
+            first_available: 1704153600000000000,
+            expiration_date: 1707933600000000000,
+            is_continuous: false,
+            active: true,
+        };
+
+        // Test
+        let cells = instrument.cells();
+
+        // Validate
+        assert_eq!(cells.len(), Instrument::columns().len());
+        assert_eq!(
+            cells,
+            vec![
+                json!(7),
+                json!("HEG4"),
+                json!("LeanHogs-0224"),
+                json!("futures"),
+                json!("databento"),
+                json!("GLBX.MDP3/mbp-1/raw_symbol"),
+                json!("2024-01-02T00:00:00Z"),
+                Value::Null,
+                json!("2024-02-14T18:00:00Z"),
+                json!(false),
+                json!(true),
+            ]
+        );
+
+        Ok(())
+    }
+}
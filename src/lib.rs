@@ -1,9 +1,14 @@
+pub mod cache;
 pub mod cli;
 pub mod commands;
 pub mod context;
+pub mod dates;
 pub mod error;
+pub mod instruments;
 pub mod logger;
+pub mod retrieve;
 pub mod shell;
+pub mod table;
 pub mod utils;
 pub mod vendors;
 
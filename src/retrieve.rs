@@ -0,0 +1,455 @@
+use crate::cache::HistoricalCache;
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::is_zstd;
+use crate::vendors::midas::merge::merge_mbinary;
+use mbinary::params::RetrieveParams;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Parses a span like 30m, 6h or 1d into nanoseconds.
+pub fn parse_span(span: &str) -> Result<i64> {
+    let span = span.trim();
+    let split = span
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(span.len());
+    let (count, unit) = span.split_at(split);
+
+    let count: i64 = count
+        .parse()
+        .map_err(|_| error!(CustomError, "Invalid span : {}", span))?;
+    let unit_nanos = match unit {
+        "s" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        "d" => 86_400_000_000_000,
+        _ => return Err(error!(CustomError, "Invalid span unit : {}", span)),
+    };
+
+    if count == 0 {
+        return Err(error!(CustomError, "Span must be at least 1{}.", unit));
+    }
+    Ok(count * unit_nanos)
+}
+
+/// How a retrieval is split up and how many pieces are in flight at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkOptions {
+    /// Chunk length in nanoseconds, chunk bounds are multiples of it.
+    pub chunk: i64,
+    pub concurrency: usize,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        ChunkOptions {
+            chunk: 86_400_000_000_000,
+            concurrency: 4,
+        }
+    }
+}
+
+impl ChunkOptions {
+    pub fn new(chunk: &str, concurrency: usize) -> Result<Self> {
+        if concurrency == 0 {
+            return Err(error!(CustomError, "Concurrency must be at least 1."));
+        }
+        Ok(ChunkOptions {
+            chunk: parse_span(chunk)?,
+            concurrency,
+        })
+    }
+}
+
+/// Splits the request into [start, end) pieces on multiples of `chunk`, so reruns over an
+/// overlapping range line up with the pieces already fetched.
+pub fn chunk_params(params: &RetrieveParams, chunk: i64) -> Result<Vec<RetrieveParams>> {
+    let interval = params.schema_interval()?;
+    if chunk % interval != 0 {
+        return Err(error!(
+            CustomError,
+            "Chunk must be a multiple of the {} bar length.", params.schema
+        ));
+    }
+
+    let mut chunks = Vec::new();
+    let mut start = params.start_ts;
+
+    while start < params.end_ts {
+        let end = ((start.div_euclid(chunk) + 1) * chunk).min(params.end_ts);
+        chunks.push(RetrieveParams {
+            start_ts: start,
+            end_ts: end,
+            ..params.clone()
+        });
+        start = end;
+    }
+
+    Ok(chunks)
+}
+
+/// Directory holding the fetched chunks of `output` until they are stitched together.
+pub fn parts_dir(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".parts");
+    PathBuf::from(path)
+}
+
+/// Lock file held while a run writes `output`, removed again when dropped.
+pub struct OutputLock {
+    path: PathBuf,
+}
+
+impl OutputLock {
+    /// Fails when another run holds the lock on `output`.
+    pub fn acquire(output: &Path) -> Result<Self> {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                writeln!(file, "{}", std::process::id())?;
+                Ok(OutputLock { path })
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(error!(
+                CustomError,
+                "{} is in use by another run, remove {} if that run is gone.",
+                output.display(),
+                path.display()
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn chunk_path(parts: &Path, chunk: &RetrieveParams) -> PathBuf {
+    parts.join(format!("{}.bin", HistoricalCache::request_key(chunk)))
+}
+
+/// Single line progress bar on stderr.
+struct Progress {
+    total: usize,
+    done: usize,
+    bytes: u64,
+    started: Instant,
+}
+
+impl Progress {
+    fn new(total: usize, done: usize) -> Self {
+        let progress = Progress {
+            total,
+            done,
+            bytes: 0,
+            started: Instant::now(),
+        };
+        progress.draw();
+        progress
+    }
+
+    fn advance(&mut self, bytes: u64) {
+        self.done += 1;
+        self.bytes += bytes;
+        self.draw();
+    }
+
+    fn draw(&self) {
+        let width = 30;
+        let filled = (self.done * width).checked_div(self.total).unwrap_or(width);
+        let seconds = self.started.elapsed().as_secs_f64().max(1e-3);
+
+        eprint!(
+            "\r[{}{}] {}/{} chunks {:.1} MB {:.1} MB/s",
+            "#".repeat(filled),
+            "-".repeat(width - filled),
+            self.done,
+            self.total,
+            self.bytes as f64 / 1e6,
+            self.bytes as f64 / 1e6 / seconds
+        );
+        let _ = std::io::stderr().flush();
+    }
+
+    fn finish(&self) {
+        eprintln!();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkSummary {
+    pub chunks: usize,
+    /// Chunks fetched by this run, the rest were left over from an earlier one.
+    pub fetched: usize,
+    pub bytes: u64,
+}
+
+/// Fetches the request chunk by chunk with up to `concurrency` requests in flight, then
+/// stitches the chunks in timestamp order into `output`. Fetched chunks are kept next to the
+/// output until stitching succeeds, so a failed run can be repeated and only fetches the
+/// chunks that are missing.
+pub async fn retrieve_chunked<F, Fut>(
+    params: &RetrieveParams,
+    output: &Path,
+    options: &ChunkOptions,
+    fetch: F,
+) -> Result<ChunkSummary>
+where
+    F: Fn(RetrieveParams) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Vec<u8>>> + Send + 'static,
+{
+    let chunks = chunk_params(params, options.chunk)?;
+    let parts = parts_dir(output);
+    std::fs::create_dir_all(&parts)?;
+
+    let pending: Vec<RetrieveParams> = chunks
+        .iter()
+        .filter(|chunk| !chunk_path(&parts, chunk).exists())
+        .cloned()
+        .collect();
+    let fetched = pending.len();
+
+    let fetch = Arc::new(fetch);
+    let semaphore = Arc::new(Semaphore::new(options.concurrency));
+    let mut tasks = JoinSet::new();
+
+    for chunk in pending {
+        let fetch = Arc::clone(&fetch);
+        let semaphore = Arc::clone(&semaphore);
+        let path = chunk_path(&parts, &chunk);
+
+        tasks.spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .map_err(|e| error!(CustomError, "{}", e))?;
+            let data = fetch(chunk).await?;
+
+            // Written aside then renamed, so an interrupted write is never taken as done
+            let partial = path.with_extension("partial");
+            tokio::fs::write(&partial, &data).await?;
+            tokio::fs::rename(&partial, &path).await?;
+
+            Ok::<u64, Error>(data.len() as u64)
+        });
+    }
+
+    let mut progress = Progress::new(chunks.len(), chunks.len() - fetched);
+    let mut errors = Vec::new();
+
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok(Ok(bytes)) => progress.advance(bytes),
+            Ok(Err(e)) => errors.push(e.to_string()),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    progress.finish();
+
+    if let Some(first) = errors.first() {
+        return Err(error!(
+            CustomError,
+            "{} of {} chunks failed, rerun to fetch only the missing chunks. First error: {}",
+            errors.len(),
+            chunks.len(),
+            first
+        ));
+    }
+
+    // Chunks without data come back empty and are left out
+    let mut files = Vec::new();
+    for chunk in &chunks {
+        let path = chunk_path(&parts, chunk);
+        if std::fs::metadata(&path)?.len() > 0 {
+            files.push(path);
+        }
+    }
+
+    match files.as_slice() {
+        [] => {
+            std::fs::remove_dir_all(&parts)?;
+            return Err(Error::NoDataError);
+        }
+        [file] if !is_zstd(output) => {
+            std::fs::copy(file, output)?;
+        }
+        _ => {
+            merge_mbinary(&files, output).await?;
+        }
+    }
+    let bytes = std::fs::metadata(output)?.len();
+    std::fs::remove_dir_all(&parts)?;
+
+    Ok(ChunkSummary {
+        chunks: chunks.len(),
+        fetched,
+        bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::generate::{generate_mbinary, GenerateOptions};
+    use crate::vendors::midas::load::read_mbinary_file;
+    use mbinary::enums::{Dataset, Schema, Stype};
+    use mbinary::record_enum::RecordEnum;
+    use mbinary::records::Record;
+    use serial_test::serial;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const DAY: i64 = 86_400_000_000_000;
+    // 2024-01-02 00:00:00 UTC
+    const START: i64 = 1704153600000000000;
+
+    fn params(start_ts: i64, end_ts: i64, schema: Schema) -> RetrieveParams {
+        RetrieveParams {
+            symbols: vec!["HE.n.0".to_string()],
+            start_ts,
+            end_ts,
+            schema,
+            dataset: Dataset::Futures,
+            stype: Stype::Raw,
+        }
+    }
+
+    /// Stands in for the server, answers each chunk with an hour of minute bars.
+    async fn fake_fetch(chunk: RetrieveParams) -> Result<Vec<u8>> {
+        let path = PathBuf::from(format!("tests/data/test_retrieve_{}.bin", chunk.start_ts));
+        let options = GenerateOptions {
+            schema: Schema::Ohlcv1M,
+            start: chunk.start_ts as u64,
+            records: 60,
+            seed: chunk.start_ts as u64,
+            ..Default::default()
+        };
+        generate_mbinary(&options, &path).await?;
+        let data = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn test_parse_span() -> Result<()> {
+        assert_eq!(parse_span("1d")?, DAY);
+        assert_eq!(parse_span("6h")?, DAY / 4);
+        assert_eq!(parse_span("30m")?, 1_800_000_000_000);
+        assert!(parse_span("0d").is_err());
+        assert!(parse_span("1w").is_err());
+        assert!(parse_span("d").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_params() -> Result<()> {
+        let request = params(START + DAY / 2, START + 2 * DAY + 1_000, Schema::Mbp1);
+
+        // Test
+        let chunks = chunk_params(&request, DAY)?;
+
+        // Validate
+        let bounds: Vec<(i64, i64)> = chunks.iter().map(|c| (c.start_ts, c.end_ts)).collect();
+        assert_eq!(
+            bounds,
+            vec![
+                (START + DAY / 2, START + DAY),
+                (START + DAY, START + 2 * DAY),
+                (START + 2 * DAY, START + 2 * DAY + 1_000)
+            ]
+        );
+        assert!(chunk_params(&params(START, START + DAY, Schema::Ohlcv1H), DAY / 48).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_output_lock() -> Result<()> {
+        let output = PathBuf::from("tests/data/test_output_lock.bin");
+
+        // Test
+        let lock = OutputLock::acquire(&output)?;
+        let held = OutputLock::acquire(&output);
+        drop(lock);
+        let again = OutputLock::acquire(&output);
+
+        // Validate
+        assert!(held.is_err());
+        assert!(again.is_ok());
+        drop(again);
+        assert!(!PathBuf::from("tests/data/test_output_lock.bin.lock").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_retrieve_chunked_resume() -> Result<()> {
+        let output = PathBuf::from("tests/data/test_retrieve_output.bin");
+        let request = params(START, START + 3 * DAY, Schema::Ohlcv1M);
+        let options = ChunkOptions {
+            chunk: DAY,
+            concurrency: 2,
+        };
+
+        // Test
+        // First run loses the middle chunk
+        let failing = retrieve_chunked(&request, &output, &options, |chunk| async move {
+            if chunk.start_ts == START + DAY {
+                return Err(error!(CustomError, "connection reset"));
+            }
+            fake_fetch(chunk).await
+        })
+        .await;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let summary = retrieve_chunked(&request, &output, &options, move |chunk| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            fake_fetch(chunk)
+        })
+        .await?;
+
+        // Validate
+        assert!(failing.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            summary,
+            ChunkSummary {
+                chunks: 3,
+                fetched: 1,
+                bytes: std::fs::metadata(&output)?.len()
+            }
+        );
+        assert!(!parts_dir(&output).exists());
+
+        let mut decoder = read_mbinary_file(&output).await?;
+        let mut timestamps = Vec::new();
+        while let Some(record_ref) = decoder.decode_ref().await? {
+            timestamps.push(RecordEnum::from_ref(record_ref)?.timestamp());
+        }
+        assert_eq!(timestamps.len(), 180);
+        assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(timestamps[60], (START + DAY) as u64);
+
+        // Cleanup
+        std::fs::remove_file(&output)?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 pub mod backtest;
+pub mod cache;
 pub mod clear;
 pub mod exit;
 pub mod historical;
@@ -9,12 +10,17 @@ pub mod processes;
 pub mod strategies;
 pub mod vendors;
 
+use crate::dates::{DateParser, DATE_HELP};
+use crate::instruments::file::format_date;
 use crate::{Result, TaskManager};
 use backtest::BacktestArgs;
+use cache::CacheArgs;
 use clap::{Parser, Subcommand};
 use clear::Clear;
 use exit::Exit;
 use historical::HistoricalArgs;
+use inquire::validator::Validation;
+use inquire::Text;
 use instrument::InstrumentArgs;
 use live::LiveArgs;
 use midas::MidasArgs;
@@ -25,6 +31,75 @@ use strategies::StrategyArgs;
 use tokio::sync::Mutex;
 use vendors::databento::DatabentoArgs;
 
+/// Prompts for the timezone of dates entered without an offset.
+pub fn date_parser(message: &str, default: &str) -> Result<DateParser> {
+    let tz = Text::new(message)
+        .with_default(default)
+        .with_help_message("Timezone of dates without an offset ex. America/Chicago")
+        .prompt()?;
+    DateParser::new(Some(tz.trim()))
+}
+
+/// Prompts until the input parses as a date, returns it in unix nanoseconds.
+pub fn date_prompt(message: &str, dates: &DateParser) -> Result<i64> {
+    let validator_dates = *dates;
+    let input = Text::new(message)
+        .with_help_message(DATE_HELP)
+        .with_validator(move |input: &str| match validator_dates.parse(input) {
+            Ok(_) => Ok(Validation::Valid),
+            Err(e) => Ok(Validation::Invalid(e.to_string().into())),
+        })
+        .prompt()?;
+    dates.parse(&input)
+}
+
+/// Like `date_prompt`, blank input means no bound.
+pub fn optional_date_prompt(message: &str, dates: &DateParser) -> Result<Option<u64>> {
+    let validator_dates = *dates;
+    let input = Text::new(message)
+        .with_help_message("Leave blank for no bound")
+        .with_validator(move |input: &str| {
+            if input.trim().is_empty() {
+                return Ok(Validation::Valid);
+            }
+            match validator_dates.parse(input) {
+                Ok(_) => Ok(Validation::Valid),
+                Err(e) => Ok(Validation::Invalid(e.to_string().into())),
+            }
+        })
+        .prompt()?;
+
+    if input.trim().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(dates.parse_u64(&input)?))
+    }
+}
+
+/// Like `optional_date_prompt`, pre-filled with `current`. Blank input means 0.
+pub fn edit_date_prompt(message: &str, dates: &DateParser, current: u64) -> Result<u64> {
+    let current = format_date(current).unwrap_or_default();
+    let validator_dates = *dates;
+    let input = Text::new(message)
+        .with_initial_value(&current)
+        .with_help_message("Leave blank for none")
+        .with_validator(move |input: &str| {
+            if input.trim().is_empty() {
+                return Ok(Validation::Valid);
+            }
+            match validator_dates.parse(input) {
+                Ok(_) => Ok(Validation::Valid),
+                Err(e) => Ok(Validation::Invalid(e.to_string().into())),
+            }
+        })
+        .prompt()?;
+
+    match input.trim() {
+        "" => Ok(0),
+        input => dates.parse_u64(input),
+    }
+}
+
 // Data options
 pub struct Datasets;
 
@@ -86,6 +161,8 @@ pub enum Commands {
     Midas(MidasArgs),
     /// Commands for Databento source
     Databento(DatabentoArgs),
+    /// Local cache of historical data.
+    Cache(CacheArgs),
     /// Processes shell
     Processes(ProcessArgs),
     /// Clear shell
@@ -105,6 +182,7 @@ impl Commands {
             "Dashboard",
             "Midas",
             "Databento",
+            "Cache",
             "Clear",
             "Exit",
         ]
@@ -120,6 +198,7 @@ impl Commands {
             Commands::Instrument(args) => args.subcommand.process_command(context).await?,
             Commands::Midas(args) => args.subcommand.process_command(context).await?,
             Commands::Databento(args) => args.subcommand.process_command(context).await?,
+            Commands::Cache(args) => args.subcommand.process_command(context).await?,
             Commands::Processes(args) => args.subcommand.process_command(context).await?,
             Commands::Clear => Clear::process_command(&Clear).await?,
             Commands::Exit => Exit::process_command(&Exit).await?,
@@ -0,0 +1,39 @@
+use crate::{Result, TaskManager};
+use clap::{Args, Subcommand};
+use inquire::Confirm;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Args)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub subcommand: CacheCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommands {
+    /// List cached historical retrievals, most recently used first.
+    List,
+    /// Remove every cached historical retrieval.
+    Clear,
+}
+
+impl CacheCommands {
+    pub async fn process_command(&self, context: Arc<Mutex<TaskManager>>) -> Result<()> {
+        match self {
+            CacheCommands::List => {
+                context.lock().await.list_cache().await;
+            }
+            CacheCommands::Clear => {
+                if Confirm::new("Remove all cached data?")
+                    .with_default(false)
+                    .prompt()?
+                {
+                    context.lock().await.clear_cache().await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
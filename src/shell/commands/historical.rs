@@ -1,9 +1,15 @@
+use crate::cache::CachePolicy;
+use crate::retrieve::ChunkOptions;
+use crate::vendors::midas::export::{ExportFormat, ExportOptions};
+use crate::vendors::midas::plot::{ChartKind, PlotOptions};
+use crate::vendors::midas::slice::SliceFilter;
 use crate::{Result, TaskManager};
-use inquire::{DateSelect, Select, Text};
+use inquire::{Confirm, CustomType, Select, Text};
 use mbinary::enums::{Dataset, Schema, Stype};
 use mbinary::params::RetrieveParams;
 pub struct Commands;
-use super::{Datasets, Schemas, Stypes};
+use super::{date_parser, date_prompt, Datasets, Schemas, Stypes};
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -20,25 +26,57 @@ impl HistoricalArgs {
         )?;
         let schema = Schema::from_str(Select::new("Schema:", Schemas::list()).prompt()?)?;
         let stype = Stype::from_str(Select::new("Stype:", Stypes::list()).prompt()?)?;
-        let start_date = DateSelect::new("Start Date:").prompt()?.to_string();
-        let end_date = DateSelect::new("Start Date:").prompt()?.to_string();
-        let file_path = Text::new("File Path:").prompt()?;
+        let dates = date_parser("Timezone:", "UTC")?;
+        let start_ts = date_prompt("Start Date:", &dates)?;
+        let end_ts = date_prompt("End Date:", &dates)?;
+        let file_path = Text::new("File Path:")
+            .with_help_message("- prints to the terminal")
+            .prompt()?;
+        let format = Select::new("Format:", ExportFormat::list()).prompt()?;
+        let export = ExportOptions {
+            format: ExportFormat::try_from(format)?,
+            tz: dates.tz(),
+        };
+        let policy = CachePolicy::try_from(Select::new("Cache:", CachePolicy::list()).prompt()?)?;
+        let chunk = Text::new("Chunk:")
+            .with_default("1d")
+            .with_help_message("Length of each request ex. 6h, 1d")
+            .prompt()?;
+        let concurrency = CustomType::<usize>::new("Concurrency:")
+            .with_default(4)
+            .prompt()?;
+        let options = ChunkOptions::new(&chunk, concurrency)?;
+        let plot = file_path != "-"
+            && export.format == ExportFormat::Mbinary
+            && Confirm::new("Plot result?").with_default(false).prompt()?;
 
         let symbols = symbols
             .split_terminator(",")
             .map(|opt| opt.to_string())
             .collect::<Vec<String>>(); // Split by newline characters
 
-        // Attempt to create RetrieveParams and handle errors gracefully
-        let params = RetrieveParams::new(symbols, &start_date, &end_date, schema, dataset, stype)?;
+        let params = RetrieveParams {
+            symbols,
+            start_ts,
+            end_ts,
+            schema,
+            dataset,
+            stype,
+        };
 
         // Likely want to add a read lock so potentialy move this to anther tread or window if long
         // running
-        context
-            .lock()
+        let context = context.lock().await;
+        if context
+            .get_historical(params, &file_path, policy, options, export)
             .await
-            .get_historical(params, &file_path)
-            .await;
+            && plot
+        {
+            let options = PlotOptions::new(ChartKind::Line, None, 15)?;
+            context
+                .plot_mbinary_file(PathBuf::from(&file_path), SliceFilter::default(), options)
+                .await;
+        }
 
         Ok(())
     }
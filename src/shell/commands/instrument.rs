@@ -1,15 +1,31 @@
-use super::{Datasets, Vendor};
+use super::midas::split_list;
+use super::{
+    date_parser, date_prompt, edit_date_prompt, optional_date_prompt, Datasets, Schemas, Vendor,
+};
 use crate::commands::TaskManager;
 use crate::error::{Error, Result};
-use crate::utils::date_to_unix_nanos;
+use crate::instruments::futures::{parse_months, parse_years, FuturesOptions};
+use crate::instruments::import::ImportOptions;
+use crate::instruments::options::{
+    definition_file, parse_moneyness, ChainFilter, ChainOptions, OptionKind,
+};
+use crate::instruments::plan::{instrument_changes, PlanOptions};
+use crate::instruments::search::{ticker_pattern, InstrumentFilter};
+use crate::table::{TableOptions, Tabular};
+use crate::utils::get_ticker_file;
+use crate::vendors::midas::coverage::CoverageOptions;
+use crate::vendors::midas::report::ReportFormat;
+use chrono::TimeZone;
 use clap::{Args, Subcommand};
 use dbn;
-use inquire::{Confirm, CustomType};
-use inquire::{DateSelect, Select, Text};
-use mbinary::enums::Dataset;
+use inquire::{Confirm, CustomType, MultiSelect};
+use inquire::{Select, Text};
+use mbinary::enums::{Dataset, Schema};
 use mbinary::symbols::Instrument;
 use mbinary::vendors::{DatabentoData, VendorData, Vendors};
+use std::fmt;
 use std::fmt::Debug;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -25,20 +41,29 @@ use tokio::sync::Mutex;
 //     Ok(unix_nanos)
 // }
 
-fn construct_vendor_data(vendor: &Vendors) -> Result<VendorData> {
+/// Prompts for the vendor data of `vendor`, pre-filled with `current` when it is of the same vendor.
+fn construct_vendor_data(vendor: &Vendors, current: Option<VendorData>) -> Result<VendorData> {
     match vendor {
         Vendors::Databento => {
-            let stype = Text::new("Stype:").prompt()?;
-            let schema = Text::new("Schema:").prompt()?;
-            let dataset = Text::new("Dataset:").prompt()?;
+            let (stype, schema, dataset) = match current {
+                Some(VendorData::Databento(data)) => (
+                    data.stype.as_str(),
+                    data.schema.as_str(),
+                    data.dataset.as_str(),
+                ),
+                _ => ("", "", ""),
+            };
+            let stype = Text::new("Stype:").with_initial_value(stype).prompt()?;
+            let schema = Text::new("Schema:").with_initial_value(schema).prompt()?;
+            let dataset = Text::new("Dataset:").with_initial_value(dataset).prompt()?;
 
             // Convert `stype` to the correct type (e.g., dbn::SType)
             let stype_enum = dbn::SType::from_str(&stype)
                 .map_err(|_| Error::CustomError(format!("Invalid 'stype': {}", stype)))?;
             let schema_enum = dbn::Schema::from_str(&schema)
-                .map_err(|_| Error::CustomError(format!("Invalid 'schema': {}", stype)))?;
+                .map_err(|_| Error::CustomError(format!("Invalid 'schema': {}", schema)))?;
             let dataset_enum = dbn::Dataset::from_str(&dataset)
-                .map_err(|_| Error::CustomError(format!("Invalid 'dataset': {}", stype)))?;
+                .map_err(|_| Error::CustomError(format!("Invalid 'dataset': {}", dataset)))?;
 
             Ok(VendorData::Databento(DatabentoData {
                 stype: stype_enum,
@@ -51,6 +76,168 @@ fn construct_vendor_data(vendor: &Vendors) -> Result<VendorData> {
     }
 }
 
+/// Server instrument as a list entry, the list is searched on this text.
+struct InstrumentChoice(Instrument);
+
+impl fmt::Display for InstrumentChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} ({}, {})",
+            self.0.ticker,
+            self.0.name,
+            self.0.vendor.as_str(),
+            if self.0.active { "active" } else { "inactive" }
+        )
+    }
+}
+
+/// Lets the user pick an instrument of a dataset, None if the dataset has none.
+async fn pick_instrument(
+    context: &Arc<Mutex<TaskManager>>,
+    message: &str,
+) -> Result<Option<Instrument>> {
+    let dataset = Dataset::from_str(
+        &Select::new("Dataset:", Datasets::list())
+            .prompt()?
+            .to_lowercase(),
+    )?;
+    let mut instruments = context.lock().await.list_instruments(dataset, None).await?;
+    if instruments.is_empty() {
+        println!("No {} instruments found.", dataset.as_str());
+        return Ok(None);
+    }
+    instruments.sort_by(|a, b| a.ticker.cmp(&b.ticker));
+
+    let choices = instruments.into_iter().map(InstrumentChoice).collect();
+    let choice = Select::new(message, choices)
+        .with_help_message("Type to search, enter to select")
+        .prompt()?;
+    Ok(Some(choice.0))
+}
+
+/// Position of `current` in a prompt's options, ignoring case.
+fn cursor(options: &[&str], current: &str) -> usize {
+    options
+        .iter()
+        .position(|option| option.eq_ignore_ascii_case(current))
+        .unwrap_or_default()
+}
+
+/// Prompts for every field of `current`, each pre-filled with its current value.
+fn edit_instrument(current: &Instrument) -> Result<Instrument> {
+    let ticker = Text::new("Ticker:")
+        .with_initial_value(&current.ticker)
+        .prompt()?;
+    let name = Text::new("Name:")
+        .with_initial_value(&current.name)
+        .prompt()?;
+    let datasets = Datasets::list();
+    let dataset = Dataset::from_str(
+        &Select::new("Dataset:", datasets.clone())
+            .with_starting_cursor(cursor(&datasets, current.dataset.as_str()))
+            .prompt()?
+            .to_lowercase(),
+    )?;
+    let vendors = Vendor::list();
+    let vendor = Vendors::from_str(
+        Select::new("Vendor:", vendors.clone())
+            .with_starting_cursor(cursor(&vendors, current.vendor.as_str()))
+            .prompt()?,
+    )?;
+    let vendor_data = construct_vendor_data(
+        &vendor,
+        Some(VendorData::decode(current.vendor_data, &current.vendor)),
+    )?;
+    let is_continuous = Confirm::new("Is instrument continuous : ")
+        .with_default(current.is_continuous)
+        .prompt()?;
+    let dates = date_parser("Timezone:", "UTC")?;
+    let first_available = edit_date_prompt("First Available:", &dates, current.first_available)?;
+    let last_available = edit_date_prompt("Last Available:", &dates, current.last_available)?;
+    let expiry_dates = date_parser("Expiration Timezone:", "America/New_York")?;
+    let expiration_date =
+        edit_date_prompt("Expiration Date:", &expiry_dates, current.expiration_date)?;
+    let active = Confirm::new("Set instrument active : ")
+        .with_default(current.active)
+        .prompt()?;
+
+    Ok(Instrument {
+        instrument_id: current.instrument_id,
+        ticker,
+        name,
+        dataset,
+        vendor,
+        vendor_data: vendor_data.encode(),
+        last_available,
+        first_available,
+        expiration_date,
+        is_continuous,
+        active,
+    })
+}
+
+fn plan_options() -> Result<(PathBuf, PlanOptions)> {
+    let file = Text::new("Manifest Path:")
+        .with_help_message(".toml, .json or .csv")
+        .prompt()?;
+    let dates = date_parser("Timezone:", "UTC")?;
+    let expiry_dates = date_parser("Expiration Timezone:", "America/New_York")?;
+    let prune = Confirm::new("Delete instruments missing from the manifest?")
+        .with_help_message("Otherwise they are deactivated")
+        .with_default(false)
+        .prompt()?;
+
+    let options = PlanOptions {
+        prune,
+        dates,
+        expiry_dates,
+    };
+    Ok((PathBuf::from(file), options))
+}
+
+/// Yes/no filter, None for any.
+fn flag_filter(message: &str, yes: &str, no: &str) -> Result<Option<bool>> {
+    let choice = Select::new(message, vec!["any", yes, no]).prompt()?;
+    Ok((choice != "any").then_some(choice == yes))
+}
+
+fn instrument_filter() -> Result<InstrumentFilter> {
+    let pattern = Text::new("Ticker:")
+        .with_help_message("Glob ex. HE*, or /regex/, leave blank for all")
+        .prompt()?;
+    let pattern = pattern.trim();
+    let ticker = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+        Some(regex) => Some(ticker_pattern(regex, true)?),
+        None if pattern.is_empty() => None,
+        None => Some(ticker_pattern(pattern, false)?),
+    };
+    let active = flag_filter("Active:", "active", "inactive")?;
+    let continuous = flag_filter("Continuous:", "continuous", "dated")?;
+    let dates = date_parser("Timezone:", "UTC")?;
+    let expiring_after = optional_date_prompt("Expiring After:", &dates)?;
+    let expiring_before = optional_date_prompt("Expiring Before:", &dates)?;
+    let stale = CustomType::<u64>::new("Stale Days:")
+        .with_help_message("Data ending more than N days ago, leave blank for any")
+        .with_error_message("Please type a valid number")
+        .prompt_skippable()?;
+
+    Ok(InstrumentFilter {
+        ticker,
+        active,
+        continuous,
+        expiring_before,
+        expiring_after,
+        stale_before: match stale {
+            Some(days) => Some(InstrumentFilter::stale_before(
+                dates.parse_u64("now")?,
+                days,
+            )),
+            None => None,
+        },
+    })
+}
+
 pub fn create_instrument() -> Result<Instrument> {
     let ticker = Text::new("Ticker:").prompt()?;
     let name = Text::new("Name:").prompt()?;
@@ -65,7 +252,7 @@ pub fn create_instrument() -> Result<Instrument> {
             .to_lowercase(),
     )?;
 
-    let vendor_data = construct_vendor_data(&vendor)?;
+    let vendor_data = construct_vendor_data(&vendor, None)?;
 
     let is_continuous = Confirm::new("Is instrument continuous : ")
         .with_default(false)
@@ -77,12 +264,10 @@ pub fn create_instrument() -> Result<Instrument> {
         first_available = 0;
         expiration_date = 0;
     } else {
-        first_available =
-            date_to_unix_nanos(&DateSelect::new("Start Date:").prompt()?.to_string(), None)?;
-        expiration_date = date_to_unix_nanos(
-            &DateSelect::new("Expiration Date:").prompt()?.to_string(),
-            None,
-        )?;
+        let dates = date_parser("Timezone:", "UTC")?;
+        first_available = date_prompt("Start Date:", &dates)?;
+        let expiry_dates = date_parser("Expiration Timezone:", "America/New_York")?;
+        expiration_date = date_prompt("Expiration Date:", &expiry_dates)?;
     }
 
     let active = Confirm::new("Set instrument active : ")
@@ -114,14 +299,21 @@ pub struct InstrumentArgs {
 pub enum InstrumentCommands {
     Create,
     Get,
+    Update,
     Delete,
+    Coverage,
+    Import,
+    Export,
+    Plan,
+    Apply,
+    GenerateFutures,
+    OptionChain,
 }
 
 impl InstrumentCommands {
     pub async fn process_command(&self, context: Arc<Mutex<TaskManager>>) -> Result<()> {
         match self {
             InstrumentCommands::Create => {
-                context.lock().await.list_backtest().await;
                 let instrument = create_instrument()?;
 
                 context.lock().await.create_instrument(instrument).await;
@@ -132,17 +324,324 @@ impl InstrumentCommands {
                         .prompt()?
                         .to_lowercase(),
                 )?;
+                let mut vendors = vec!["all"];
+                vendors.extend(Vendor::list());
+                let vendor = match Select::new("Vendor:", vendors).prompt()? {
+                    "all" => None,
+                    vendor => Some(Vendors::from_str(vendor)?),
+                };
+                let filter = match Confirm::new("Filter instruments?")
+                    .with_default(false)
+                    .prompt()?
+                {
+                    true => instrument_filter()?,
+                    false => InstrumentFilter::default(),
+                };
+
+                let mut columns = vec!["none"];
+                columns.extend(Instrument::columns());
+                let sort = match Select::new("Sort By:", columns).prompt()? {
+                    "none" => None,
+                    column => Some(column),
+                };
+                let descending =
+                    sort.is_some() && Confirm::new("Descending?").with_default(false).prompt()?;
+                let columns = MultiSelect::new("Columns:", Instrument::columns().to_vec())
+                    .with_all_selected_by_default()
+                    .prompt()?
+                    .join(",");
+                let json = Select::new("Format:", vec!["text", "json"]).prompt()? == "json";
 
-                context.lock().await.get_instruments(dataset, None).await;
+                let options = TableOptions::new(sort, descending, Some(&columns), json);
+
+                context
+                    .lock()
+                    .await
+                    .get_instruments(dataset, vendor, &filter, &options)
+                    .await;
+            }
+            InstrumentCommands::Update => {
+                let current = match pick_instrument(&context, "Instrument:").await? {
+                    Some(instrument) => instrument,
+                    None => return Ok(()),
+                };
+                let instrument = edit_instrument(&current)?;
+
+                let changes = instrument_changes(&current, &instrument);
+                if changes.is_empty() {
+                    println!("No changes.");
+                    return Ok(());
+                }
+                println!("~ update {} ({})", current.ticker, current.dataset.as_str());
+                for change in &changes {
+                    println!("      {}", change);
+                }
+
+                if Confirm::new("Update instrument?")
+                    .with_default(false)
+                    .prompt()?
+                {
+                    context.lock().await.update_instrument(instrument).await;
+                }
             }
             InstrumentCommands::Delete => {
-                let id: i32 = CustomType::new("Instrument ID:")
-                    .with_formatter(&|i: i32| format!("{i}"))
-                    .with_error_message("Please type a valid number")
-                    .prompt()
-                    .unwrap();
+                let instrument = match pick_instrument(&context, "Instrument:").await? {
+                    Some(instrument) => instrument,
+                    None => return Ok(()),
+                };
+                let id = instrument.instrument_id.ok_or_else(|| {
+                    Error::CustomError(format!("{} has no instrument id", instrument.ticker))
+                })?;
+                let id = i32::try_from(id).map_err(|_| {
+                    Error::CustomError(format!("Instrument id {} is out of range", id))
+                })?;
+
+                if Confirm::new(&format!("Delete {} ({})?", instrument.ticker, id))
+                    .with_default(false)
+                    .prompt()?
+                {
+                    context.lock().await.delete_instrument(id).await;
+                }
+            }
+            InstrumentCommands::Coverage => {
+                let dataset = Dataset::from_str(
+                    &Select::new("Dataset:", Datasets::list())
+                        .prompt()?
+                        .to_lowercase(),
+                )?;
+                let mut vendors = vec!["all"];
+                vendors.extend(Vendor::list());
+                let vendor = match Select::new("Vendor:", vendors).prompt()? {
+                    "all" => None,
+                    vendor => Some(Vendors::from_str(vendor)?),
+                };
+                let tickers = Text::new("Tickers:")
+                    .with_help_message("ex. HE.n.0,ZC.n.0, leave blank for all")
+                    .prompt()?;
+                let schemas = Schemas::list();
+                let schema = Schema::from_str(
+                    Select::new("Schema:", schemas.clone())
+                        .with_starting_cursor(cursor(&schemas, "ohlcv-1h"))
+                        .prompt()?,
+                )?;
+                let dates = date_parser("Timezone:", "UTC")?;
+                let start = optional_date_prompt("Start:", &dates)?;
+                let end = optional_date_prompt("End:", &dates)?;
+                let format =
+                    ReportFormat::try_from(Select::new("Format:", vec!["text", "json"]).prompt()?)?;
+
+                let options = CoverageOptions {
+                    schema,
+                    start,
+                    end,
+                    tz: dates.tz(),
+                };
+
+                context
+                    .lock()
+                    .await
+                    .instrument_coverage(dataset, vendor, split_list(&tickers), options, format)
+                    .await;
+            }
+            InstrumentCommands::Import => {
+                let file = Text::new("File Path:")
+                    .with_default(&get_ticker_file()?.to_string_lossy())
+                    .with_help_message(".toml, .json or .csv")
+                    .prompt()?;
+                let dates = date_parser("Timezone:", "UTC")?;
+                let expiry_dates = date_parser("Expiration Timezone:", "America/New_York")?;
+                let upsert = Confirm::new("Update instruments that already exist?")
+                    .with_default(false)
+                    .prompt()?;
+                let dry_run = Confirm::new("Dry run?").with_default(true).prompt()?;
+
+                let options = ImportOptions {
+                    upsert,
+                    dry_run,
+                    dates,
+                    expiry_dates,
+                };
+
+                context
+                    .lock()
+                    .await
+                    .import_instruments(PathBuf::from(file), options)
+                    .await;
+            }
+            InstrumentCommands::Export => {
+                let file = Text::new("File Path:")
+                    .with_help_message("The extension picks the format ex. universe.toml")
+                    .prompt()?;
+                let mut datasets = vec!["all"];
+                datasets.extend(Datasets::list());
+                let datasets = match Select::new("Dataset:", datasets).prompt()? {
+                    "all" => vec![Dataset::Futures, Dataset::Equities, Dataset::Option],
+                    dataset => vec![Dataset::from_str(&dataset.to_lowercase())?],
+                };
+                let mut vendors = vec!["all"];
+                vendors.extend(Vendor::list());
+                let vendor = match Select::new("Vendor:", vendors).prompt()? {
+                    "all" => None,
+                    vendor => Some(Vendors::from_str(vendor)?),
+                };
+
+                context
+                    .lock()
+                    .await
+                    .export_instruments(PathBuf::from(file), datasets, vendor)
+                    .await;
+            }
+            InstrumentCommands::Plan => {
+                let (file, options) = plan_options()?;
+
+                context.lock().await.plan_instruments(file, options).await;
+            }
+            InstrumentCommands::Apply => {
+                let (file, options) = plan_options()?;
+
+                context
+                    .lock()
+                    .await
+                    .apply_instruments(file, options, false)
+                    .await;
+            }
+            InstrumentCommands::GenerateFutures => {
+                let root = Text::new("Root:")
+                    .with_help_message(
+                        "ex. HE, expiration rules come from config.toml or the built in ones",
+                    )
+                    .prompt()?;
+                let months =
+                    parse_months(&Text::new("Months:").with_default("FGHJKMNQUVXZ").prompt()?)?;
+                let years = parse_years(
+                    &Text::new("Years:")
+                        .with_help_message("ex. 2024 or 2024-2026")
+                        .prompt()?,
+                )?;
+                let schema = Text::new("Schema:").with_default("mbp-1").prompt()?;
+                let schema = dbn::Schema::from_str(&schema)
+                    .map_err(|_| Error::CustomError(format!("Invalid 'schema': {}", schema)))?;
+                let dates = date_parser("Timezone:", "UTC")?;
+                let first_available = optional_date_prompt("First Available:", &dates)?;
+                let active = Confirm::new("Set contracts active : ")
+                    .with_default(false)
+                    .prompt()?;
+
+                let options = FuturesOptions {
+                    months,
+                    years,
+                    schema,
+                    first_available: first_available.unwrap_or_default(),
+                    active,
+                };
+
+                let action =
+                    Select::new("Action:", vec!["preview", "create", "write to file"]).prompt()?;
+                let output = match action {
+                    "write to file" => Some(PathBuf::from(
+                        Text::new("File Path:")
+                            .with_help_message(".toml, .json or .csv")
+                            .prompt()?,
+                    )),
+                    _ => None,
+                };
+                let upsert = action == "create"
+                    && Confirm::new("Update contracts that already exist?")
+                        .with_default(false)
+                        .prompt()?;
+
+                context
+                    .lock()
+                    .await
+                    .generate_futures(
+                        root.to_uppercase(),
+                        options,
+                        upsert,
+                        action == "preview",
+                        output,
+                    )
+                    .await;
+            }
+            InstrumentCommands::OptionChain => {
+                let underlying = Text::new("Underlying:")
+                    .with_help_message("ex. SPY")
+                    .prompt()?
+                    .to_uppercase();
+                let dates = date_parser("Timezone:", "UTC")?;
+                let date = date_prompt("Date:", &dates)?;
+                let day = dates.tz().timestamp_nanos(date).date_naive();
+                let file = Text::new("File Path:")
+                    .with_help_message(
+                        "Leave blank for the file `databento download` wrote to RAW_DIR",
+                    )
+                    .prompt()?;
+                let file = definition_file(
+                    Some(file.trim()).filter(|file| !file.is_empty()),
+                    None,
+                    &underlying,
+                    day,
+                )?;
+                let kind =
+                    OptionKind::from_str(Select::new("Type:", OptionKind::list()).prompt()?)?;
+                let expiry_start = optional_date_prompt("Earliest Expiration:", &dates)?;
+                let expiry_end = optional_date_prompt("Latest Expiration:", &dates)?;
+                let band = Text::new("Moneyness:")
+                    .with_help_message(
+                        "Strike over spot band ex. 0.9-1.1, leave blank for all strikes",
+                    )
+                    .prompt()?;
+                let (moneyness, spot) = match band.trim() {
+                    "" => (None, None),
+                    band => {
+                        let moneyness = parse_moneyness(band)?;
+                        let spot: f64 = CustomType::new("Spot:")
+                            .with_error_message("Please type a valid number")
+                            .prompt()?;
+                        (Some(moneyness), Some(spot))
+                    }
+                };
+                let schema = Text::new("Schema:").with_default("mbp-1").prompt()?;
+                let schema = dbn::Schema::from_str(&schema)
+                    .map_err(|_| Error::CustomError(format!("Invalid 'schema': {}", schema)))?;
+                let active = Confirm::new("Set contracts active : ")
+                    .with_default(false)
+                    .prompt()?;
+
+                let action =
+                    Select::new("Action:", vec!["preview", "create", "write to file"]).prompt()?;
+                let output = match action {
+                    "write to file" => Some(PathBuf::from(
+                        Text::new("File Path:")
+                            .with_help_message(".toml, .json or .csv")
+                            .prompt()?,
+                    )),
+                    _ => None,
+                };
+                let upsert = action == "create"
+                    && Confirm::new("Update contracts that already exist?")
+                        .with_default(false)
+                        .prompt()?;
+
+                let filter = ChainFilter {
+                    kind,
+                    expiry_start,
+                    expiry_end,
+                    moneyness,
+                    spot,
+                };
+                let options = ChainOptions {
+                    schema,
+                    first_available: date as u64,
+                    active,
+                    upsert,
+                    dry_run: action == "preview",
+                };
 
-                context.lock().await.delete_instrument(id).await;
+                context
+                    .lock()
+                    .await
+                    .option_chain(file, underlying, filter, options, output)
+                    .await;
             }
         }
         Ok(())
@@ -1,11 +1,77 @@
 use crate::commands::TaskManager;
+use crate::dates::{DateParser, DATE_HELP};
 use crate::error::Result;
+use crate::shell::commands::{date_parser, optional_date_prompt, Datasets, Schemas};
+use crate::vendors::midas::checks::DUPLICATE_WINDOW;
+use crate::vendors::midas::generate::GenerateOptions;
+use crate::vendors::midas::plot::{ChartKind, PlotOptions};
+use crate::vendors::midas::remap::parse_id_map;
+use crate::vendors::midas::report::{CompareOptions, ReportFormat, DEFAULT_MAX_EXAMPLES};
+use crate::vendors::midas::resample::{EmptyIntervals, Interval, ResampleOptions, Session};
+use crate::vendors::midas::sample::SampleMode;
+use crate::vendors::midas::slice::SliceFilter;
+use crate::vendors::midas::split::SplitBy;
 use clap::{Args, Subcommand};
-use inquire::Text;
+use inquire::{Confirm, CustomType, Select, Text};
+use mbinary::enums::{Dataset, Schema};
+use mbinary::PRICE_SCALE;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// Prompts for where and how to write a comparison report.
+pub fn compare_options() -> Result<CompareOptions> {
+    let output = Text::new("Report Path:")
+        .with_default("compare_results.txt")
+        .prompt()?;
+    let format = Select::new("Report Format:", vec!["text", "json"]).prompt()?;
+    let tolerances = Text::new("Tolerances:")
+        .with_help_message("ex. ts_in_delta=100,price=0, leave blank for exact matching")
+        .prompt()?;
+    let max_examples = CustomType::<usize>::new("Max Examples:")
+        .with_default(DEFAULT_MAX_EXAMPLES)
+        .with_help_message("Near matches and unmatched records kept in the report")
+        .prompt()?;
+
+    CompareOptions::new(&output, format, Some(&tolerances), max_examples)
+}
+
+/// Prompts for how to sample a file.
+pub fn sample_mode() -> Result<SampleMode> {
+    let by = Select::new("Sample By:", vec!["minutes", "every", "window"]).prompt()?;
+
+    match by {
+        "minutes" => {
+            let minutes: u64 = CustomType::new("Minutes:")
+                .with_help_message("Minutes kept from each instrument's first record")
+                .prompt()?;
+            SampleMode::new(Some(minutes), None, None, None)
+        }
+        "every" => {
+            let every: usize = CustomType::new("Every:")
+                .with_help_message("Keep every k-th record of each instrument")
+                .prompt()?;
+            SampleMode::new(None, Some(every), None, None)
+        }
+        _ => {
+            let dates = date_parser("Timezone:", "UTC")?;
+            let start = optional_date_prompt("Start:", &dates)?;
+            let end = optional_date_prompt("End:", &dates)?;
+            SampleMode::new(None, None, start, end)
+        }
+    }
+}
+
+/// Splits comma separated input into trimmed, non-empty values.
+pub fn split_list(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
 #[derive(Debug, Args)]
 pub struct MidasArgs {
     #[command(subcommand)]
@@ -18,6 +84,32 @@ pub enum MidasCommands {
     Compare,
     /// Compare databento and midas data
     Duplicates,
+    /// Extract instruments and/or a time window from a file
+    Slice,
+    /// Merge files into one timestamp ordered file
+    Merge,
+    /// Split a file by instrument or by day
+    Split,
+    /// Build OHLCV bars from a mbp-1, tbbo or trades file
+    Resample,
+    /// Convert a file to databento's dbn format
+    ToDbn,
+    /// Check files for truncation, corrupt records and metadata mismatches
+    Verify,
+    /// Rewrite instrument ids in a file's records and symbol map
+    Remap,
+    /// Per instrument record counts, time coverage, prices and volume
+    Stats,
+    /// Chart prices and volume per instrument in the terminal
+    Plot,
+    /// Cut a small file from a large one, keeping its metadata
+    Sample,
+    /// Write a synthetic file of random walk prices for testing
+    Generate,
+    /// Write a zstd compressed copy of a file
+    Compress,
+    /// Write an uncompressed copy of a .zst file
+    Decompress,
 }
 
 impl MidasCommands {
@@ -26,21 +118,318 @@ impl MidasCommands {
             MidasCommands::Compare => {
                 let filepath1 = Text::new("File1:").prompt()?;
                 let filepath2 = Text::new("File2:").prompt()?;
+                let options = compare_options()?;
+
                 context
                     .lock()
                     .await
-                    .compare_mbinay_files(PathBuf::from(filepath1), PathBuf::from(filepath2))
+                    .compare_mbinay_files(
+                        PathBuf::from(filepath1),
+                        PathBuf::from(filepath2),
+                        options,
+                    )
                     .await;
 
                 Ok(())
             }
             MidasCommands::Duplicates => {
                 let filepath = Text::new("FilePath:").prompt()?;
+                let deduped = Text::new("Deduped Output Path:")
+                    .with_help_message("Leave blank to only report duplicates")
+                    .prompt()?;
+                let deduped = Some(deduped.trim())
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from);
+
+                context
+                    .lock()
+                    .await
+                    .check_duplicates(PathBuf::from(filepath), DUPLICATE_WINDOW, deduped)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Slice => {
+                let filepath = Text::new("FilePath:").prompt()?;
+                let output = Text::new("Output Path:").prompt()?;
+                let tickers = Text::new("Tickers:")
+                    .with_help_message("Comma separated, leave blank for all instruments")
+                    .prompt()?;
+                let dates = date_parser("Timezone:", "UTC")?;
+                let start = optional_date_prompt("Start:", &dates)?;
+                let end = optional_date_prompt("End:", &dates)?;
+
+                let filter = SliceFilter {
+                    instrument_ids: Vec::new(),
+                    tickers: split_list(&tickers),
+                    start,
+                    end,
+                };
+
+                context
+                    .lock()
+                    .await
+                    .slice_mbinary_file(PathBuf::from(filepath), PathBuf::from(output), filter)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Merge => {
+                let filepaths = Text::new("FilePaths:")
+                    .with_help_message("Comma separated")
+                    .prompt()?;
+                let output = Text::new("Output Path:").prompt()?;
+                let files = split_list(&filepaths)
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .collect();
+
+                context
+                    .lock()
+                    .await
+                    .merge_mbinary_files(files, PathBuf::from(output))
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Split => {
+                let filepath = Text::new("FilePath:").prompt()?;
+                let output_dir = Text::new("Output Directory:").prompt()?;
+                let by = SplitBy::try_from(
+                    Select::new("Split by:", vec!["instrument", "day"]).prompt()?,
+                )?;
+
+                context
+                    .lock()
+                    .await
+                    .split_mbinary_file(PathBuf::from(filepath), PathBuf::from(output_dir), by)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Resample => {
+                let filepath = Text::new("FilePath:").prompt()?;
+                let output_dir = Text::new("Output Directory:").prompt()?;
+                let interval = Select::new("Interval:", vec!["1s", "1m", "1h", "1d"]).prompt()?;
+                let session_start = Text::new("Session Start:")
+                    .with_default("00:00")
+                    .with_help_message("HH:MM, bars are aligned to the session open")
+                    .prompt()?;
+                let tz = Text::new("Timezone:").with_default("UTC").prompt()?;
+                let empty = Select::new("Empty Intervals:", vec!["skip", "fill"]).prompt()?;
+
+                let options = ResampleOptions {
+                    interval: Interval::try_from(interval)?,
+                    session: Session::new(&session_start, &tz)?,
+                    empty: EmptyIntervals::try_from(empty)?,
+                };
+
+                context
+                    .lock()
+                    .await
+                    .resample_mbinary_file(
+                        PathBuf::from(filepath),
+                        PathBuf::from(output_dir),
+                        options,
+                    )
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::ToDbn => {
+                let filepath = Text::new("FilePath:").prompt()?;
+                let output = Text::new("Output Path:").prompt()?;
+
+                context
+                    .lock()
+                    .await
+                    .mbinary_to_dbn(PathBuf::from(filepath), PathBuf::from(output))
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Remap => {
+                let filepath = Text::new("FilePath:").prompt()?;
+                let source = Select::new("Ids from:", vec!["map", "server"]).prompt()?;
+                let map = if source == "map" {
+                    let map = Text::new("Map:")
+                        .with_help_message("ex. 20=45,21=46")
+                        .prompt()?;
+                    Some(parse_id_map(&map)?)
+                } else {
+                    None
+                };
+                let output = Text::new("Output Path:")
+                    .with_help_message("Leave blank to rewrite the file in place")
+                    .prompt()?;
+                let output = Some(output.trim())
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from);
+
+                context
+                    .lock()
+                    .await
+                    .remap_mbinary_file(PathBuf::from(filepath), output, map)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Stats => {
+                let filepath = Text::new("FilePath:").prompt()?;
+                let format =
+                    ReportFormat::try_from(Select::new("Format:", vec!["text", "json"]).prompt()?)?;
+                let tz = Text::new("Timezone:")
+                    .with_default("UTC")
+                    .with_help_message("Calendar days counted as trading days ex. America/Chicago")
+                    .prompt()?;
+
+                context
+                    .lock()
+                    .await
+                    .mbinary_file_stats(PathBuf::from(filepath), format, &tz)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Plot => {
+                let filepath = Text::new("FilePath:").prompt()?;
+                let tickers = Text::new("Tickers:")
+                    .with_help_message("ex. HE.n.0,ZC.n.0, leave blank for all")
+                    .prompt()?;
+                let dates = date_parser("Timezone:", "UTC")?;
+                let start = optional_date_prompt("Start:", &dates)?;
+                let end = optional_date_prompt("End:", &dates)?;
+                let kind =
+                    ChartKind::try_from(Select::new("Chart:", vec!["line", "candles"]).prompt()?)?;
+
+                let filter = SliceFilter {
+                    instrument_ids: vec![],
+                    tickers: split_list(&tickers),
+                    start,
+                    end,
+                };
+                let options = PlotOptions::new(kind, None, 15)?;
+
+                context
+                    .lock()
+                    .await
+                    .plot_mbinary_file(PathBuf::from(filepath), filter, options)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Sample => {
+                let filepath = Text::new("FilePath:").prompt()?;
+                let output = Text::new("Output Path:").prompt()?;
+                let mode = sample_mode()?;
+
+                context
+                    .lock()
+                    .await
+                    .sample_mbinary_file(PathBuf::from(filepath), PathBuf::from(output), mode)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Generate => {
+                let output = Text::new("Output Path:").prompt()?;
+                let schema = Schema::from_str(Select::new("Schema:", Schemas::list()).prompt()?)?;
+                let dataset = Dataset::from_str(
+                    &Select::new("Dataset:", Datasets::list())
+                        .prompt()?
+                        .to_lowercase(),
+                )?;
+                let tickers = Text::new("Tickers:")
+                    .with_default("HE.n.0")
+                    .with_help_message("ex. HE.n.0,ZC.n.0, ids are assigned 1, 2, ... in order")
+                    .prompt()?;
+                let start = Text::new("Start:")
+                    .with_default("2024-01-02 00:00:00")
+                    .with_help_message(DATE_HELP)
+                    .prompt()?;
+                let records: usize = CustomType::new("Records:")
+                    .with_default(1000)
+                    .with_error_message("Please type a valid number")
+                    .prompt()?;
+                let start_price: f64 = CustomType::new("Start Price:")
+                    .with_default(100.0)
+                    .prompt()?;
+                let tick_size: f64 = CustomType::new("Tick Size:").with_default(0.25).prompt()?;
+                let seed: u64 = CustomType::new("Seed:").with_default(0).prompt()?;
+                let duplicates: usize = CustomType::new("Duplicates:").with_default(0).prompt()?;
+                let gaps: usize = CustomType::new("Gaps:").with_default(0).prompt()?;
+
+                let options = GenerateOptions {
+                    schema,
+                    dataset,
+                    tickers: split_list(&tickers),
+                    start: DateParser::new(None)?.parse_u64(&start)?,
+                    records,
+                    start_price: (start_price * PRICE_SCALE as f64).round() as i64,
+                    tick_size: (tick_size * PRICE_SCALE as f64).round() as i64,
+                    seed,
+                    duplicates,
+                    gaps,
+                    ..Default::default()
+                };
+
+                context
+                    .lock()
+                    .await
+                    .generate_mbinary_file(PathBuf::from(output), options)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Compress => {
+                let filepath = Text::new("File Path:").prompt()?;
+                let output = Text::new("Output Path:")
+                    .with_help_message("Leave blank to append .zst to the file path")
+                    .prompt()?;
+                let output = Some(output.trim())
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from);
+                let remove = Confirm::new("Delete the source file?")
+                    .with_default(false)
+                    .prompt()?;
+
+                context
+                    .lock()
+                    .await
+                    .compress_mbinary_file(PathBuf::from(filepath), output, remove)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Decompress => {
+                let filepath = Text::new("File Path:").prompt()?;
+                let output = Text::new("Output Path:")
+                    .with_help_message("Leave blank to drop .zst from the file path")
+                    .prompt()?;
+                let output = Some(output.trim())
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from);
+                let remove = Confirm::new("Delete the source file?")
+                    .with_default(false)
+                    .prompt()?;
+
+                context
+                    .lock()
+                    .await
+                    .decompress_mbinary_file(PathBuf::from(filepath), output, remove)
+                    .await;
+
+                Ok(())
+            }
+            MidasCommands::Verify => {
+                let path = Text::new("Path:")
+                    .with_help_message("File or directory")
+                    .prompt()?;
 
                 context
                     .lock()
                     .await
-                    .check_duplicates(PathBuf::from(filepath))
+                    .verify_mbinary_files(PathBuf::from(path))
                     .await;
 
                 Ok(())
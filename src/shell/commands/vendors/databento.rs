@@ -1,14 +1,15 @@
-use super::super::Datasets;
+use super::super::midas::{compare_options, sample_mode};
+use super::super::{date_parser, date_prompt, Datasets};
 use crate::commands::TaskManager;
-use crate::error::{Error, Result};
+use crate::dates::to_offset_datetime;
+use crate::error::Result;
 use clap::{Args, Subcommand};
 use dbn;
 use inquire::Confirm;
-use inquire::{DateSelect, Select, Text};
+use inquire::{Select, Text};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use tokio::sync::Mutex;
 
 // Data options
@@ -99,6 +100,7 @@ pub enum DatabentoCommands {
     Download,
     Transform,
     Compare,
+    Sample,
 }
 
 impl DatabentoCommands {
@@ -112,25 +114,9 @@ impl DatabentoCommands {
                     .map(|opt| opt.to_string())
                     .collect::<Vec<String>>(); // Split by newline characters
 
-                let mut start = DateSelect::new("Start Date:").prompt()?.to_string();
-                let mut end = DateSelect::new("End Date:").prompt()?.to_string();
-                println!("Date {:?}", start);
-
-                // Try to parse the datetime string as an OffsetDateTime
-                start = format!("{}T00:00:00Z", start);
-                let start_date = OffsetDateTime::parse(&start, &Rfc3339).map_err(|_| {
-                    Error::DateError(
-                        "Error: Invalid start date format. Expected format: YYYY-MM-DD".to_string(),
-                    )
-                })?;
-
-                // Try to parse the datetime string as an OffsetDateTime
-                end = format!("{}T00:00:00Z", end);
-                let end_date = OffsetDateTime::parse(&end, &Rfc3339).map_err(|_| {
-                    Error::DateError(
-                        "Error: Invalid end date format. Expected format: YYYY-MM-DD".to_string(),
-                    )
-                })?;
+                let dates = date_parser("Timezone:", "UTC")?;
+                let start_date = to_offset_datetime(date_prompt("Start Date:", &dates)?)?;
+                let end_date = to_offset_datetime(date_prompt("End Date:", &dates)?)?;
 
                 let schema =
                     dbn::Schema::from_str(Select::new("Schema:", Schema::list()).prompt()?)?;
@@ -168,20 +154,34 @@ impl DatabentoCommands {
 
                 let dbn_filepath = PathBuf::from(Text::new("File Path:").prompt()?);
                 let midas_filepath = PathBuf::from(Text::new("File Path:").prompt()?);
+                let compress = Confirm::new("Compress?").with_default(false).prompt()?;
 
                 context
                     .lock()
                     .await
-                    .transform(dataset, dbn_filepath, midas_filepath)
+                    .transform(dataset, dbn_filepath, midas_filepath, compress)
                     .await;
             }
             DatabentoCommands::Compare => {
                 let dbn_filepath = PathBuf::from(Text::new("File Path:").prompt()?);
                 let midas_filepath = PathBuf::from(Text::new("File Path:").prompt()?);
+                let options = compare_options()?;
+
+                context
+                    .lock()
+                    .await
+                    .dbn_compare(dbn_filepath, midas_filepath, options)
+                    .await;
+            }
+            DatabentoCommands::Sample => {
+                let filepath = PathBuf::from(Text::new("File Path:").prompt()?);
+                let output = PathBuf::from(Text::new("Output Path:").prompt()?);
+                let mode = sample_mode()?;
+
                 context
                     .lock()
                     .await
-                    .dbn_compare(dbn_filepath, midas_filepath)
+                    .sample_dbn_file(filepath, output, mode)
                     .await;
             }
         }
@@ -33,7 +33,7 @@ pub(crate) fn print_menu() {
             "{}{}         {}",
             arrow,
             "Instrument".green(),
-            "Create instruments.".white()
+            "Manage instruments.".white()
         ),
         format!(
             "{}{}           {}",
@@ -2,7 +2,8 @@ pub mod commands;
 pub mod completer;
 pub mod header;
 pub mod prompt;
-pub mod table;
+
+pub use crate::table;
 
 use crate::context::Context;
 use crate::error::Result;
@@ -0,0 +1,251 @@
+use crate::error;
+use crate::error::{Error, Result};
+use prettytable::{format, Cell, Row, Table};
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// Rows that can be shown by `render_table`.
+pub trait Tabular {
+    /// Column names, in display order.
+    fn columns() -> &'static [&'static str];
+    /// Values of each column, in `columns` order. Null shows as `-`.
+    fn cells(&self) -> Vec<Value>;
+}
+
+/// Sorting, column selection and output format of a table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableOptions {
+    pub sort: Option<String>,
+    pub descending: bool,
+    /// Columns to show, all of them when empty.
+    pub columns: Vec<String>,
+    pub json: bool,
+}
+
+impl TableOptions {
+    /// Options from comma separated column names ex. "ticker,name".
+    pub fn new(sort: Option<&str>, descending: bool, columns: Option<&str>, json: bool) -> Self {
+        TableOptions {
+            sort: sort.map(|column| column.trim().to_lowercase()),
+            descending,
+            columns: columns
+                .unwrap_or_default()
+                .split(',')
+                .map(|column| column.trim().to_lowercase())
+                .filter(|column| !column.is_empty())
+                .collect(),
+            json,
+        }
+    }
+}
+
+fn column_index<T: Tabular>(column: &str) -> Result<usize> {
+    T::columns()
+        .iter()
+        .position(|name| *name == column)
+        .ok_or_else(|| {
+            error!(
+                CustomError,
+                "Unknown column '{}', expected one of: {}",
+                column,
+                T::columns().join(", ")
+            )
+        })
+}
+
+/// Numbers compare by value, nulls sort first, anything else by its text.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .unwrap_or_default()
+            .total_cmp(&b.as_f64().unwrap_or_default()),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        _ => cell_text(a).cmp(&cell_text(b)),
+    }
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Keeps the column order in json output.
+struct JsonRow<'a> {
+    columns: &'a [&'static str],
+    cells: Vec<Value>,
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, cell) in self.columns.iter().zip(&self.cells) {
+            map.serialize_entry(column, cell)?;
+        }
+        map.end()
+    }
+}
+
+/// Selected columns of the rows, sorted as asked, as a table or a json array.
+pub fn render_table<T: Tabular>(rows: &[T], options: &TableOptions) -> Result<String> {
+    let indexes = if options.columns.is_empty() {
+        (0..T::columns().len()).collect()
+    } else {
+        options
+            .columns
+            .iter()
+            .map(|column| column_index::<T>(column))
+            .collect::<Result<Vec<usize>>>()?
+    };
+
+    let mut cells: Vec<Vec<Value>> = rows.iter().map(Tabular::cells).collect();
+    if let Some(sort) = &options.sort {
+        let index = column_index::<T>(sort)?;
+        cells.sort_by(|a, b| match options.descending {
+            true => compare_values(&b[index], &a[index]),
+            false => compare_values(&a[index], &b[index]),
+        });
+    }
+
+    let columns: Vec<&'static str> = indexes.iter().map(|i| T::columns()[*i]).collect();
+    let cells = cells
+        .into_iter()
+        .map(|row| indexes.iter().map(|i| row[*i].clone()).collect::<Vec<_>>());
+
+    if options.json {
+        let rows: Vec<JsonRow> = cells
+            .map(|cells| JsonRow {
+                columns: &columns,
+                cells,
+            })
+            .collect();
+        return Ok(serde_json::to_string_pretty(&rows)?);
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(Row::new(
+        columns
+            .iter()
+            .map(|column| Cell::new(column).style_spec("bFg"))
+            .collect(),
+    ));
+    for row in cells {
+        table.add_row(Row::new(
+            row.iter()
+                .map(|value| match value {
+                    Value::Number(_) => Cell::new(&cell_text(value)).style_spec("r"),
+                    _ => Cell::new(&cell_text(value)),
+                })
+                .collect(),
+        ));
+    }
+    Ok(table.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Item {
+        id: u32,
+        name: &'static str,
+        size: Option<f64>,
+    }
+
+    impl Tabular for Item {
+        fn columns() -> &'static [&'static str] {
+            &["id", "name", "size"]
+        }
+
+        fn cells(&self) -> Vec<Value> {
+            vec![json!(self.id), json!(self.name), json!(self.size)]
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item {
+                id: 10,
+                name: "b",
+                size: Some(1.5),
+            },
+            Item {
+                id: 9,
+                name: "a",
+                size: None,
+            },
+            Item {
+                id: 11,
+                name: "c",
+                size: Some(0.5),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_table() -> Result<()> {
+        let options = TableOptions::new(Some("id"), true, Some("name, id"), false);
+
+        // Test
+        let table = render_table(&items(), &options)?;
+
+        // Validate
+        let lines: Vec<Vec<&str>> = table
+            .lines()
+            .map(|line| line.split_whitespace().filter(|c| *c != "|").collect())
+            .collect();
+        assert_eq!(lines[0], vec!["name", "id"]);
+        assert_eq!(
+            &lines[2..],
+            &[vec!["c", "11"], vec!["b", "10"], vec!["a", "9"]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_json() -> Result<()> {
+        let options = TableOptions::new(Some("size"), false, None, true);
+
+        // Test
+        let output = render_table(&items(), &options)?;
+
+        // Validate
+        assert!(output.find("\"id\"").unwrap() < output.find("\"size\"").unwrap());
+        let rows: Value = serde_json::from_str(&output)?;
+        assert_eq!(
+            rows,
+            json!([
+                {"id": 9, "name": "a", "size": null},
+                {"id": 11, "name": "c", "size": 0.5},
+                {"id": 10, "name": "b", "size": 1.5},
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_column() {
+        let options = TableOptions::new(Some("ticker"), false, None, false);
+
+        // Test
+        let result = render_table(&items(), &options);
+
+        // Validate
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Custom error: Unknown column 'ticker', expected one of: id, name, size"
+        );
+    }
+}
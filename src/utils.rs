@@ -1,6 +1,6 @@
+use crate::dates::DateParser;
 use crate::error::{Error, Result};
-use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
-use chrono_tz::Tz;
+use chrono::{DateTime, TimeZone, Utc};
 use midas_client::historical::Historical;
 use std::env;
 use std::io::Write;
@@ -22,55 +22,9 @@ pub fn get_earlier_of_year_end_or_date(
     next_year_start.min(compare_date)
 }
 
+/// Parses a date in `timezone` (UTC by default), see `DateParser` for accepted formats.
 pub fn date_to_unix_nanos(date_str: &str, timezone: Option<&str>) -> Result<i64> {
-    // Parse the timezone or default to UTC
-    let tz: Tz = match timezone {
-        Some(tz_str) => tz_str
-            .parse()
-            .map_err(|_| Error::CustomError(format!("Invalid timezone: {}", tz_str)))?,
-        None => chrono_tz::UTC,
-    };
-
-    let naive_datetime = if date_str.len() == 10 {
-        // Attempt to parse date-only format YYYY-MM-DD
-        match NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
-            Ok(naive_date) => naive_date.and_hms_opt(0, 0, 0).unwrap(), // Set time to midnight
-            Err(_) => {
-                return Err(Error::InvalidDateFormat(format!(
-                    "Invalid date format '{}'. Expected format: YYYY-MM-DD",
-                    date_str
-                )));
-            }
-        }
-    } else {
-        // Attempt to parse datetime format YYYY-MM-DD HH:MM:SS
-        match NaiveDateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M:%S") {
-            Ok(datetime) => datetime,
-            Err(_) => {
-                return Err(Error::InvalidDateFormat(format!(
-                    "Invalid datetime format '{}'. Expected format: YYYY-MM-DD HH:MM:SS",
-                    date_str
-                )));
-            }
-        }
-    };
-    // Convert to timezone-aware datetime
-    let datetime_with_tz = tz
-        .from_local_datetime(&naive_datetime)
-        .single()
-        .ok_or_else(|| {
-            Error::CustomError(format!(
-                "Failed to interpret '{}' in timezone '{}'",
-                date_str,
-                timezone.unwrap_or("UTC")
-            ))
-        })?;
-
-    // // Convert to Unix time in nanoseconds
-    let unix_nanos = datetime_with_tz.timestamp_nanos_opt().unwrap();
-
-    // Convert to Unix nanoseconds
-    Ok(unix_nanos)
+    DateParser::new(timezone)?.parse(date_str)
 }
 
 pub fn unix_nanos_to_date(unix_nanos: i64) -> Result<String> {
@@ -83,6 +37,11 @@ pub fn unix_nanos_to_date(unix_nanos: i64) -> Result<String> {
     Ok(formatted_date)
 }
 
+/// True if `ts` is within the `[start, end)` window, missing bounds are open.
+pub fn in_window(ts: u64, start: Option<u64>, end: Option<u64>) -> bool {
+    start.is_none_or(|start| ts >= start) && end.is_none_or(|end| ts < end)
+}
+
 // Function to get the full path for the tickers.json file
 pub fn get_ticker_file() -> crate::Result<PathBuf> {
     let ticker_path: PathBuf;
@@ -236,6 +195,14 @@ pub fn get_dashboard_path() -> Result<PathBuf> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_in_window() {
+        assert!(in_window(10, Some(10), Some(20)));
+        assert!(!in_window(20, Some(10), Some(20)));
+        assert!(!in_window(9, Some(10), None));
+        assert!(in_window(u64::MAX, None, None));
+    }
+
     #[test]
     fn test_datetime_to_unix_nanos_utc() -> Result<()> {
         let date_str = "2021-11-01 01:01:01";
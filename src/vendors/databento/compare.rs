@@ -1,97 +1,265 @@
+use crate::error;
 use crate::error::{Error, Result};
 use crate::vendors::databento::extract::read_dbn_file;
-use crate::vendors::midas::load::read_mbinary_file;
+use crate::vendors::midas::compare::RecordBuckets;
+use crate::vendors::midas::report::{CompareFields, CompareOptions, CompareReport};
+use async_compression::tokio::bufread::ZstdDecoder;
+use dbn::decode::AsyncDbnDecoder;
 use dbn::Record as dbnRecord;
-use mbinary::record_enum::RecordEnum;
-use mbinary::records::Record;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-
-pub async fn compare_dbn(dbn_filepath: PathBuf, mbinary_filepath: &PathBuf) -> Result<()> {
-    let batch_size = 100000; // New parameter to control batch size
-    let mut mbinary_decoder = read_mbinary_file(mbinary_filepath).await?;
-    let (mut dbn_decoder, _map) = read_dbn_file(dbn_filepath).await?;
-
-    let mut mbinary_batch: HashMap<u64, Vec<RecordEnum>> = HashMap::new();
-    let mut mbinary_decoder_done = false;
-    let mut unmatched_dbn_records = Vec::new();
-
-    // Start decoding and comparing
-    while let Some(dbn_record) = dbn_decoder.decode_record_ref().await? {
-        // If MBN batch is empty, refill it
-        if mbinary_batch.len() < batch_size && !mbinary_decoder_done {
-            while let Some(mbinary_record) = mbinary_decoder.decode_ref().await? {
-                let record_enum = RecordEnum::from_ref(mbinary_record)?;
-                let ts_event = record_enum.header().ts_event;
-                mbinary_batch.entry(ts_event).or_default().push(record_enum);
-            }
-            if mbinary_batch.is_empty() {
-                mbinary_decoder_done = true; // No more MBN records
-            }
+use tokio::io::BufReader;
+
+/// A dbn record with the discriminator the mbinary conversion gives it, the count of identical
+/// mbp-1 records before it in the same timestamp.
+struct DbnRecord {
+    record: dbn::RecordEnum,
+    discriminator: u32,
+}
+
+impl fmt::Debug for DbnRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.record)?;
+        if self.kind() == "mbp1" {
+            write!(f, " discriminator: {}", self.discriminator)?;
         }
-        let dbn_record_enum: dbn::RecordEnum = dbn_record.as_enum()?.to_owned();
-        let ts_event = match &dbn_record_enum.rtype().unwrap() {
-            dbn::RType::Bbo1S | dbn::RType::Bbo1M => match &dbn_record_enum {
-                dbn::RecordEnum::Mbp1(msg) => msg.ts_recv,
-                _ => return Err(Error::CustomError("Invalid record type".into())),
+        Ok(())
+    }
+}
+
+/// Field view matching the mbinary conversion, fields dropped by the conversion are left out.
+impl CompareFields for DbnRecord {
+    fn kind(&self) -> &'static str {
+        match &self.record {
+            dbn::RecordEnum::Mbp1(msg) => match msg.hd.rtype() {
+                Ok(dbn::RType::Bbo1S | dbn::RType::Bbo1M) => "bbo",
+                _ => "mbp1",
             },
-            _ => dbn_record_enum.header().ts_event,
+            dbn::RecordEnum::Trade(_) => "trade",
+            dbn::RecordEnum::Bbo(_) => "bbo",
+            dbn::RecordEnum::Ohlcv(_) => "ohlcv",
+            _ => "unsupported",
+        }
+    }
+
+    fn instrument_id(&self) -> u32 {
+        self.record.header().instrument_id
+    }
+
+    fn fields(&self) -> Vec<(&'static str, i128)> {
+        let level_fields = |level: &dbn::BidAskPair| {
+            [
+                ("bid_px", level.bid_px as i128),
+                ("ask_px", level.ask_px as i128),
+                ("bid_sz", level.bid_sz as i128),
+                ("ask_sz", level.ask_sz as i128),
+                ("bid_ct", level.bid_ct as i128),
+                ("ask_ct", level.ask_ct as i128),
+            ]
         };
 
-        // Check if the ts_event exists in the MBN map
-        if let Some(mbinary_group) = mbinary_batch.get_mut(&ts_event) {
-            // Try to find a match within the group
-            if let Some(pos) = mbinary_group
-                .iter()
-                .position(|mbinary_record| mbinary_record == &dbn_record_enum)
-            {
-                mbinary_group.remove(pos); // Remove matched record
-                if mbinary_group.is_empty() {
-                    mbinary_batch.remove(&ts_event); // Remove the key if the group is empty
-                }
-            } else {
-                unmatched_dbn_records.push(dbn_record_enum); // No match found in the group
+        match &self.record {
+            dbn::RecordEnum::Mbp1(msg) if self.kind() == "bbo" => {
+                let mut fields = vec![("ts_event", msg.ts_recv as i128)];
+                fields.extend(level_fields(&msg.levels[0]));
+                fields
             }
-        } else {
-            unmatched_dbn_records.push(dbn_record_enum); // No group found for the ts_event
+            dbn::RecordEnum::Bbo(msg) => {
+                let mut fields = vec![("ts_event", msg.ts_recv as i128)];
+                fields.extend(level_fields(&msg.levels[0]));
+                fields
+            }
+            dbn::RecordEnum::Mbp1(msg) => {
+                let mut fields = vec![
+                    ("ts_event", msg.hd.ts_event as i128),
+                    ("price", msg.price as i128),
+                    ("size", msg.size as i128),
+                    ("action", msg.action as i128),
+                    ("side", msg.side as i128),
+                    ("depth", msg.depth as i128),
+                    ("flags", msg.flags.raw() as i128),
+                    ("ts_recv", msg.ts_recv as i128),
+                    ("ts_in_delta", msg.ts_in_delta as i128),
+                    ("sequence", msg.sequence as i128),
+                    ("discriminator", self.discriminator as i128),
+                ];
+                fields.extend(level_fields(&msg.levels[0]));
+                fields
+            }
+            dbn::RecordEnum::Trade(msg) => vec![
+                ("ts_event", msg.hd.ts_event as i128),
+                ("price", msg.price as i128),
+                ("size", msg.size as i128),
+                ("action", msg.action as i128),
+                ("side", msg.side as i128),
+                ("depth", msg.depth as i128),
+                ("flags", msg.flags.raw() as i128),
+                ("ts_recv", msg.ts_recv as i128),
+                ("ts_in_delta", msg.ts_in_delta as i128),
+                ("sequence", msg.sequence as i128),
+            ],
+            dbn::RecordEnum::Ohlcv(msg) => vec![
+                ("ts_event", msg.hd.ts_event as i128),
+                ("open", msg.open as i128),
+                ("high", msg.high as i128),
+                ("low", msg.low as i128),
+                ("close", msg.close as i128),
+                ("volume", msg.volume as i128),
+            ],
+            record => vec![("ts_event", record.header().ts_event as i128)],
         }
     }
+}
 
-    // Create or truncate the output file
-    let output_file = "compare_results.txt";
-    let mut file = File::create(&output_file).await?;
+/// Streams a dbn file as runs of records sharing an index timestamp, the file must be sorted by it.
+struct DbnBuckets {
+    decoder: AsyncDbnDecoder<ZstdDecoder<BufReader<File>>>,
+    next: Option<dbn::RecordEnum>,
+    map: HashMap<String, String>,
+    filepath: PathBuf,
+}
 
-    // Check for remaining unmatched MBN records and write them to the file
-    if !mbinary_batch.is_empty() {
-        file.write_all(b"Unmatched MBN Records:\n").await?;
-        for mbinary_record in &mbinary_batch {
-            file.write_all(format!("{:?}\n", mbinary_record).as_bytes())
-                .await?;
-        }
+impl DbnBuckets {
+    async fn new(filepath: PathBuf) -> Result<Self> {
+        let (mut decoder, map) = read_dbn_file(filepath.clone()).await?;
+        let next = match decoder.decode_record_ref().await? {
+            Some(record_ref) => Some(record_ref.as_enum()?.to_owned()),
+            None => None,
+        };
+
+        Ok(DbnBuckets {
+            decoder,
+            next,
+            map,
+            filepath,
+        })
     }
 
-    // Check for remaining unmatched DBN records and write them to the file
-    if !unmatched_dbn_records.is_empty() {
-        file.write_all(b"Unmatched DBN Records:\n").await?;
-        for dbn_record in &unmatched_dbn_records {
-            file.write_all(format!("{:?}\n", dbn_record).as_bytes())
-                .await?;
+    /// Ticker of the instrument from the file symbology, falls back to the id.
+    fn ticker(&self, instrument_id: u32) -> String {
+        let id = instrument_id.to_string();
+        self.map.get(&id).cloned().unwrap_or(id)
+    }
+
+    /// Returns the next index timestamp and all consecutive records with that timestamp.
+    async fn next_bucket(&mut self) -> Result<Option<(u64, Vec<DbnRecord>)>> {
+        let first = match self.next.take() {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let ts = first.raw_index_ts();
+        let mut bucket = vec![first];
+
+        while let Some(record_ref) = self.decoder.decode_record_ref().await? {
+            let record: dbn::RecordEnum = record_ref.as_enum()?.to_owned();
+            let record_ts = record.raw_index_ts();
+
+            if record_ts == ts {
+                bucket.push(record);
+                continue;
+            }
+            if record_ts < ts {
+                return Err(error!(
+                    CustomError,
+                    "File {} is not sorted by timestamp, {} follows {}.",
+                    self.filepath.display(),
+                    record_ts,
+                    ts
+                ));
+            }
+            self.next = Some(record);
+            break;
         }
+
+        // Identical mbp-1 records of a timestamp are told apart by their order, like to_mbinary
+        let mut seen: HashMap<dbn::Mbp1Msg, u32> = HashMap::new();
+        let bucket = bucket
+            .into_iter()
+            .map(|record| {
+                let mut record = DbnRecord {
+                    record,
+                    discriminator: 0,
+                };
+                if let (dbn::RecordEnum::Mbp1(msg), "mbp1") = (&record.record, record.kind()) {
+                    record.discriminator = *seen
+                        .entry(msg.clone())
+                        .and_modify(|count| *count += 1)
+                        .or_insert(0);
+                }
+                record
+            })
+            .collect();
+
+        Ok(Some((ts, bucket)))
+    }
+}
+
+/// Merge-joins a dbn file and its mbinary conversion, holding only one timestamp bucket from each side in memory.
+pub async fn compare_dbn(
+    dbn_filepath: PathBuf,
+    mbinary_filepath: &Path,
+    options: &CompareOptions,
+) -> Result<CompareReport> {
+    let mut report = CompareReport::new(
+        &mbinary_filepath.display().to_string(),
+        &dbn_filepath.display().to_string(),
+        options.max_examples,
+    );
+    let mut mbinary_buckets = RecordBuckets::new(mbinary_filepath).await?;
+    // Instruments are matched by ticker, the ids of the two files have nothing in common
+    if !mbinary_buckets.has_mappings() {
+        return Err(error!(
+            CustomError,
+            "File {} has no symbol mappings to match its instruments to the dbn file.",
+            mbinary_filepath.display()
+        ));
     }
+    let mut dbn_buckets = DbnBuckets::new(dbn_filepath).await?;
 
-    // Return an error if there are unmatched records in either batch
-    if mbinary_batch.is_empty() && unmatched_dbn_records.is_empty() {
-        println!("All records match successfully.");
-    } else {
-        eprintln!(
-            "Unmatched records detected. Check the output file: {:?}",
-            output_file
+    let mut mbinary_bucket = mbinary_buckets.next_bucket().await?;
+    let mut dbn_bucket = dbn_buckets.next_bucket().await?;
+
+    loop {
+        let (ts, mbinary_records, dbn_records) = match (mbinary_bucket.take(), dbn_bucket.take()) {
+            (None, None) => break,
+            (Some((mbinary_ts, mbinary_records)), None) => {
+                mbinary_bucket = mbinary_buckets.next_bucket().await?;
+                (mbinary_ts, mbinary_records, Vec::new())
+            }
+            (None, Some((dbn_ts, dbn_records))) => {
+                dbn_bucket = dbn_buckets.next_bucket().await?;
+                (dbn_ts, Vec::new(), dbn_records)
+            }
+            (Some((mbinary_ts, mbinary_records)), Some((dbn_ts, dbn_records))) => {
+                if mbinary_ts < dbn_ts {
+                    mbinary_bucket = mbinary_buckets.next_bucket().await?;
+                    dbn_bucket = Some((dbn_ts, dbn_records));
+                    (mbinary_ts, mbinary_records, Vec::new())
+                } else if mbinary_ts > dbn_ts {
+                    mbinary_bucket = Some((mbinary_ts, mbinary_records));
+                    dbn_bucket = dbn_buckets.next_bucket().await?;
+                    (dbn_ts, Vec::new(), dbn_records)
+                } else {
+                    mbinary_bucket = mbinary_buckets.next_bucket().await?;
+                    dbn_bucket = dbn_buckets.next_bucket().await?;
+                    (mbinary_ts, mbinary_records, dbn_records)
+                }
+            }
+        };
+
+        report.add_bucket(
+            ts,
+            mbinary_records,
+            dbn_records,
+            &options.tolerances,
+            |id| mbinary_buckets.ticker(id),
+            |id| dbn_buckets.ticker(id),
         );
     }
 
-    Ok(())
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -105,7 +273,7 @@ mod tests {
     use mbinary::metadata::Metadata;
     use mbinary::symbols::SymbolMap;
 
-    async fn dummy_file() -> Result<PathBuf> {
+    async fn dummy_file(tickers: [(&str, u32); 2]) -> Result<PathBuf> {
         // Load DBN file
         let file_path = PathBuf::from(
             "tests/data/databento/GLBX.MDP3_mbp-1_ZM.n.0_GC.n.0_2024-08-20T00:00:00Z_2024-08-20T05:00:00Z.dbn",
@@ -118,9 +286,15 @@ mod tests {
         mbinary_map.insert("ZM.n.0".to_string(), 20 as u32);
         mbinary_map.insert("GC.n.0".to_string(), 21 as u32);
 
+        // Tickers the mbinary file carries for its ids
+        let mut symbol_map = SymbolMap::new();
+        for (ticker, id) in tickers {
+            symbol_map.add_instrument(ticker, id);
+        }
+
         // Map DBN instrument to MBN insturment
         let new_map = instrument_id_map(map, mbinary_map)?;
-        let metadata = Metadata::new(Schema::Mbp1, Dataset::Futures, 0, 0, SymbolMap::new());
+        let metadata = Metadata::new(Schema::Mbp1, Dataset::Futures, 0, 0, symbol_map);
 
         // Test
         let mbinary_file_name =
@@ -135,17 +309,18 @@ mod tests {
     #[serial_test::serial]
     // #[ignore]
     async fn test_compare_dbn() -> Result<()> {
-        let mbinary_path = dummy_file().await?;
+        let mbinary_path = dummy_file([("ZM.n.0", 20), ("GC.n.0", 21)]).await?;
 
         let dbn_file_path = PathBuf::from(
             "tests/data/databento/GLBX.MDP3_mbp-1_ZM.n.0_GC.n.0_2024-08-20T00:00:00Z_2024-08-20T05:00:00Z.dbn",
         );
 
         // Test
-        let x = compare_dbn(dbn_file_path, &mbinary_path).await?;
+        let report = compare_dbn(dbn_file_path, &mbinary_path, &CompareOptions::default()).await?;
 
         // Validate
-        assert!(x == ());
+        assert!(report.is_match());
+        assert!(report.matched() > 0);
 
         //Cleanup
         if mbinary_path.exists() {
@@ -154,4 +329,26 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_compare_dbn_instruments() -> Result<()> {
+        // Ids of the mbinary file point at the other instrument
+        let mbinary_path = dummy_file([("ZM.n.0", 21), ("GC.n.0", 20)]).await?;
+        let dbn_file_path = PathBuf::from(
+            "tests/data/databento/GLBX.MDP3_mbp-1_ZM.n.0_GC.n.0_2024-08-20T00:00:00Z_2024-08-20T05:00:00Z.dbn",
+        );
+
+        // Test
+        let report = compare_dbn(dbn_file_path, &mbinary_path, &CompareOptions::default()).await?;
+
+        // Validate
+        assert!(!report.is_match());
+        assert_eq!(report.matched(), 0);
+
+        // Cleanup
+        std::fs::remove_file(&mbinary_path)?;
+
+        Ok(())
+    }
 }
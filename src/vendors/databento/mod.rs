@@ -1,12 +1,15 @@
 pub mod client;
 pub mod compare;
 pub mod extract;
+pub mod sample;
 pub mod transform;
 pub mod utils;
 
 use super::super::utils::get_earlier_of_year_end_or_date;
 use crate::error;
 use crate::vendors::midas::checks::find_duplicates;
+use crate::vendors::midas::compress::{compressed_path, decompress_mbinary, decompressed_path};
+use crate::vendors::midas::load::is_zstd;
 use crate::vendors::{DownloadType, Vendor};
 use crate::{Error, Result};
 use async_trait::async_trait;
@@ -49,6 +52,7 @@ impl DatabentoVendor {
         client: &Historical,
         instrument_client: &Instruments,
         download_approval: bool,
+        compress: bool,
     ) -> Result<()> {
         // Download
         let (download_type, file_name) = self
@@ -82,6 +86,7 @@ impl DatabentoVendor {
                 &file_name,
                 &mbinary_filename,
                 instrument_client,
+                compress,
             )
             .await?;
 
@@ -133,6 +138,7 @@ impl Vendor for DatabentoVendor {
         hist_client: &Historical,
         instrument_client: &Instruments,
         download_approval: bool,
+        compress: bool,
     ) -> Result<()> {
         // Calculate today at the start of the day once
         let today = OffsetDateTime::now_utc().replace_time(time!(00:00));
@@ -179,6 +185,7 @@ impl Vendor for DatabentoVendor {
                             hist_client,
                             instrument_client,
                             download_approval,
+                            compress,
                         )
                         .await
                     {
@@ -285,8 +292,14 @@ impl Vendor for DatabentoVendor {
             mbinary_filename
         };
 
+        // Tickers are kept in the file so it can be compared against the dbn file later
+        let mut symbol_map = SymbolMap::new();
+        for (ticker, id) in &mbinary_map {
+            symbol_map.add_instrument(ticker, *id);
+        }
+
         let new_map = instrument_id_map(dbn_map, mbinary_map.clone())?;
-        let metadata = Metadata::new(Schema::Mbp1, dataset, 0, 0, SymbolMap::new());
+        let metadata = Metadata::new(Schema::Mbp1, dataset, 0, 0, symbol_map);
         let _ = to_mbinary(&metadata, &mut records, &new_map, mbinary_filepath).await?;
         let _ = drop(records);
 
@@ -309,8 +322,14 @@ impl Vendor for DatabentoVendor {
         download_path: &PathBuf,
         mbinary_filename: &PathBuf,
         instrument_client: &Instruments,
+        compress: bool,
     ) -> Result<Vec<PathBuf>> {
         let mut files_list = Vec::new();
+        let mbinary_filename = &if compress && !is_zstd(mbinary_filename) {
+            compressed_path(mbinary_filename)
+        } else {
+            mbinary_filename.clone()
+        };
 
         if download_type == &DownloadType::Stream {
             let _ = self
@@ -362,6 +381,23 @@ impl Vendor for DatabentoVendor {
         let mut errors = Vec::new(); // To collect errors
 
         for file in &files {
+            let mut path = PathBuf::from(&raw_dir).join(file);
+            let mut file = file.clone();
+
+            // The server reads plain mbinary
+            if is_zstd(&file) {
+                match decompress_mbinary(&path, None, true).await {
+                    Ok((plain, _)) => {
+                        path = plain;
+                        file = decompressed_path(&file)?;
+                    }
+                    Err(e) => {
+                        eprintln!("Error decompressing file {}: {}", path.display(), e);
+                        errors.push((file.clone(), e.to_string()));
+                        continue;
+                    }
+                }
+            }
             let file_string: String = file.to_string_lossy().into_owned();
 
             // Attempt to upload the file
@@ -371,15 +407,14 @@ impl Vendor for DatabentoVendor {
                 }
                 Err(e) => {
                     eprintln!("Error uploading file {}: {}", file.display(), e);
-                    errors.push((file.clone(), e)); // Collect error with filename
+                    errors.push((file.clone(), e.to_string())); // Collect error with filename
                 }
             }
 
-            let path = PathBuf::from(&raw_dir).join(file);
             // Attempt to remove the file, even if upload fails
             if let Err(e) = std::fs::remove_file(path.clone()) {
                 eprintln!("Error removing file {}: {}", path.display(), e);
-                errors.push((file.clone(), e.into())); // Collect error with filename
+                errors.push((file.clone(), e.to_string())); // Collect error with filename
             }
         }
 
@@ -576,6 +611,7 @@ mod tests {
                 &dbn_file,
                 &mbinary_file,
                 &inst_client,
+                false,
             )
             .await?;
 
@@ -633,6 +669,7 @@ mod tests {
                 &dbn_file,
                 &mbinary_file,
                 &inst_client,
+                false,
             )
             .await?;
 
@@ -689,6 +726,7 @@ mod tests {
                 &dbn_file,
                 &mbinary_file,
                 &inst_client,
+                false,
             )
             .await?;
 
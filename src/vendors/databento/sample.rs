@@ -0,0 +1,82 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::databento::extract::read_dbn_file;
+use crate::vendors::midas::sample::{SampleMode, Sampler};
+use dbn::encode::{DbnEncoder, EncodeRecord, EncodeRecordRef};
+use dbn::Record;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
+/// Writes a zstd compressed subset of a dbn file with the source metadata and symbol mappings,
+/// so the sample still transforms and compares like the source. Returns the number of records written.
+pub async fn sample_dbn(filepath: &PathBuf, output: &PathBuf, mode: SampleMode) -> Result<u64> {
+    if filepath == output {
+        return Err(error!(
+            CustomError,
+            "Output must differ from the input file."
+        ));
+    }
+
+    let (mut decoder, _) = read_dbn_file(filepath.clone()).await?;
+    let mut metadata = decoder.metadata().clone();
+
+    let mut sampler = Sampler::new(mode);
+    let end = metadata.end.map_or(u64::MAX, NonZeroU64::get);
+    let (start, end) = sampler.range(metadata.start, end);
+    metadata.start = start;
+    metadata.end = NonZeroU64::new(end).filter(|end| end.get() != u64::MAX);
+
+    let file = std::io::BufWriter::new(std::fs::File::create(output)?);
+    let mut encoder = DbnEncoder::with_zstd(file, &metadata)?;
+    let mut count = 0;
+
+    while let Some(record) = decoder.decode_record_ref().await? {
+        if sampler.keep(record.header().instrument_id, record.raw_index_ts()) {
+            encoder.encode_record_ref(record)?;
+            count += 1;
+        }
+    }
+    encoder.flush()?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_sample_dbn() -> Result<()> {
+        let path = PathBuf::from(
+            "tests/data/databento/GLBX.MDP3_ohlcv-1m_HE.c.0_HE.c.1_LE.c.0_LE.c.1_2024-02-13T00:00:00Z_2024-02-16T00:00:00Z.dbn",
+        );
+        let output = PathBuf::from("tests/data/databento/test_sample.dbn");
+
+        // Test
+        let count = sample_dbn(&path, &output, SampleMode::FirstMinutes(10)).await?;
+
+        // Validate
+        let (source, source_map) = read_dbn_file(path).await?;
+        let (mut decoder, map) = read_dbn_file(output.clone()).await?;
+        assert_eq!(map, source_map);
+        assert_eq!(decoder.metadata().mappings, source.metadata().mappings);
+
+        let mut records = 0;
+        let mut first = std::collections::HashMap::new();
+        while let Some(record) = decoder.decode_record::<dbn::OhlcvMsg>().await? {
+            let ts = record.hd.ts_event;
+            let start = *first.entry(record.hd.instrument_id).or_insert(ts);
+            assert!(ts < start + 600_000_000_000);
+            records += 1;
+        }
+        assert_eq!(records, count);
+        assert!(count > 0);
+
+        // Cleanup
+        std::fs::remove_file(&output)?;
+
+        Ok(())
+    }
+}
@@ -2,12 +2,18 @@ use crate::error;
 use crate::error::{Error, Result};
 use crate::vendors::midas::load::mbinary_to_file;
 use crate::vendors::midas::load::metadata_to_file;
+use crate::vendors::midas::load::read_mbinary_file;
 use async_compression::tokio::bufread::ZstdDecoder;
 use databento::historical::timeseries::AsyncDbnDecoder;
 use dbn;
+use dbn::encode::{DbnEncoder, EncodeRecord};
+use mbinary::enums::{Dataset, Schema};
 use mbinary::metadata::Metadata;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::Record;
 use mbinary::{self, records::Mbp1Msg};
 use std::collections::HashMap;
+use std::num::NonZeroU64;
 use std::path::PathBuf;
 use tokio::fs::File;
 use tokio::io::BufReader;
@@ -90,6 +96,198 @@ pub async fn to_mbinary(
     Ok(())
 }
 
+fn dbn_schema(schema: Schema) -> dbn::Schema {
+    match schema {
+        Schema::Mbp1 => dbn::Schema::Mbp1,
+        Schema::Ohlcv1S => dbn::Schema::Ohlcv1S,
+        Schema::Ohlcv1M => dbn::Schema::Ohlcv1M,
+        Schema::Ohlcv1H => dbn::Schema::Ohlcv1H,
+        Schema::Ohlcv1D => dbn::Schema::Ohlcv1D,
+        Schema::Trades => dbn::Schema::Trades,
+        Schema::Tbbo => dbn::Schema::Tbbo,
+        Schema::Bbo1S => dbn::Schema::Bbo1S,
+        Schema::Bbo1M => dbn::Schema::Bbo1M,
+    }
+}
+
+fn dbn_publisher(dataset: Dataset) -> (dbn::Dataset, dbn::Publisher) {
+    match dataset {
+        Dataset::Futures => (dbn::Dataset::GlbxMdp3, dbn::Publisher::GlbxMdp3Glbx),
+        Dataset::Equities => (dbn::Dataset::XnasItch, dbn::Publisher::XnasItchXnas),
+        Dataset::Option => (dbn::Dataset::OpraPillar, dbn::Publisher::OpraPillarOpra),
+    }
+}
+
+/// Rebuilds dbn metadata, each ticker maps to its mbinary instrument id for the whole range.
+pub fn dbn_metadata(metadata: &Metadata, start: u64, end: u64) -> Result<dbn::Metadata> {
+    let (dataset, _) = dbn_publisher(metadata.dataset);
+    let date = |ts: u64| -> Result<time::Date> {
+        Ok(time::OffsetDateTime::from_unix_timestamp_nanos(ts as i128)
+            .map_err(|e| error!(Conversion, "Invalid timestamp {}: {}", ts, e))?
+            .date())
+    };
+    let start_date = date(start)?;
+    let end_date = date(end.saturating_sub(1).max(start))?
+        .next_day()
+        .ok_or_else(|| error!(Conversion, "Invalid end timestamp {}", end))?;
+
+    let mut mappings: Vec<dbn::SymbolMapping> = metadata
+        .mappings
+        .map
+        .iter()
+        .map(|(id, ticker)| dbn::SymbolMapping {
+            raw_symbol: ticker.clone(),
+            intervals: vec![dbn::MappingInterval {
+                start_date,
+                end_date,
+                symbol: id.to_string(),
+            }],
+        })
+        .collect();
+    mappings.sort_by(|a, b| a.raw_symbol.cmp(&b.raw_symbol));
+
+    Ok(dbn::Metadata::builder()
+        .dataset(dataset.as_str())
+        .schema(Some(dbn_schema(metadata.schema)))
+        .start(start)
+        .end(NonZeroU64::new(end))
+        .stype_in(Some(dbn::SType::RawSymbol))
+        .stype_out(dbn::SType::InstrumentId)
+        .symbols(mappings.iter().map(|m| m.raw_symbol.clone()).collect())
+        .mappings(mappings)
+        .build())
+}
+
+/// First and last record timestamps, used when the file metadata has no time range.
+async fn timestamp_range(filepath: &PathBuf) -> Result<(u64, u64)> {
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let mut range: Option<(u64, u64)> = None;
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let ts = RecordEnum::from_ref(record_ref)?.timestamp();
+        range = Some(match range {
+            Some((start, end)) => (start.min(ts), end.max(ts + 1)),
+            None => (ts, ts + 1),
+        });
+    }
+
+    range.ok_or_else(|| error!(CustomError, "File {} has no records.", filepath.display()))
+}
+
+/// Converts a mbinary file to a zstd compressed dbn file, instrument ids are kept and
+/// the mbinary only discriminator is dropped. Returns the number of records written.
+pub async fn to_dbn(mbinary_filepath: &PathBuf, dbn_filepath: &PathBuf) -> Result<u64> {
+    let mut decoder = read_mbinary_file(mbinary_filepath).await?;
+    let metadata = decoder.metadata().ok_or_else(|| {
+        error!(
+            CustomError,
+            "File {} has no metadata.",
+            mbinary_filepath.display()
+        )
+    })?;
+
+    let (start, end) = if metadata.start == 0 || metadata.end <= metadata.start {
+        timestamp_range(mbinary_filepath).await?
+    } else {
+        (metadata.start, metadata.end)
+    };
+    let publisher = dbn_publisher(metadata.dataset).1 as u16;
+    let rtype = dbn::RType::from(dbn_schema(metadata.schema)) as u8;
+
+    let file = std::io::BufWriter::new(std::fs::File::create(dbn_filepath)?);
+    let mut encoder = DbnEncoder::with_zstd(file, &dbn_metadata(&metadata, start, end)?)?;
+    let mut count = 0;
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+        let hd = record.header();
+
+        match &record {
+            RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) => {
+                encoder.encode_record(&dbn::Mbp1Msg {
+                    hd: dbn::RecordHeader::new::<dbn::Mbp1Msg>(
+                        rtype,
+                        publisher,
+                        hd.instrument_id,
+                        hd.ts_event,
+                    ),
+                    price: msg.price,
+                    size: msg.size,
+                    action: msg.action,
+                    side: msg.side,
+                    flags: dbn::FlagSet::new(msg.flags),
+                    depth: msg.depth,
+                    ts_recv: msg.ts_recv,
+                    ts_in_delta: msg.ts_in_delta,
+                    sequence: msg.sequence,
+                    levels: [dbn_levels(&msg.levels[0])],
+                })?;
+            }
+            RecordEnum::Trade(msg) => {
+                encoder.encode_record(&dbn::TradeMsg {
+                    hd: dbn::RecordHeader::new::<dbn::TradeMsg>(
+                        rtype,
+                        publisher,
+                        hd.instrument_id,
+                        hd.ts_event,
+                    ),
+                    price: msg.price,
+                    size: msg.size,
+                    action: msg.action,
+                    side: msg.side,
+                    flags: dbn::FlagSet::new(msg.flags),
+                    depth: msg.depth,
+                    ts_recv: msg.ts_recv,
+                    ts_in_delta: msg.ts_in_delta,
+                    sequence: msg.sequence,
+                })?;
+            }
+            RecordEnum::Ohlcv(msg) => {
+                encoder.encode_record(&dbn::OhlcvMsg {
+                    hd: dbn::RecordHeader::new::<dbn::OhlcvMsg>(
+                        rtype,
+                        publisher,
+                        hd.instrument_id,
+                        hd.ts_event,
+                    ),
+                    open: msg.open,
+                    high: msg.high,
+                    low: msg.low,
+                    close: msg.close,
+                    volume: msg.volume,
+                })?;
+            }
+            RecordEnum::Bbo(msg) => {
+                let mut bbo = dbn::BboMsg::default_for_schema(dbn_schema(metadata.schema));
+                bbo.hd = dbn::RecordHeader::new::<dbn::BboMsg>(
+                    rtype,
+                    publisher,
+                    hd.instrument_id,
+                    hd.ts_event,
+                );
+                bbo.ts_recv = hd.ts_event;
+                bbo.levels = [dbn_levels(&msg.levels[0])];
+                encoder.encode_record(&bbo)?;
+            }
+        }
+        count += 1;
+    }
+    encoder.flush()?;
+
+    Ok(count)
+}
+
+fn dbn_levels(level: &mbinary::records::BidAskPair) -> dbn::BidAskPair {
+    dbn::BidAskPair {
+        bid_px: level.bid_px,
+        ask_px: level.ask_px,
+        bid_sz: level.bid_sz,
+        ask_sz: level.ask_sz,
+        bid_ct: level.bid_ct,
+        ask_ct: level.ask_ct,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,4 +404,84 @@ mod tests {
         }
         Ok(())
     }
+
+    /// Records of a dbn file with instrument ids mapped like `to_mbinary`.
+    async fn dbn_records(
+        file_path: &PathBuf,
+        map: &HashMap<u32, u32>,
+    ) -> Result<Vec<dbn::Mbp1Msg>> {
+        let (mut decoder, _) = read_dbn_file(file_path.clone()).await?;
+        let mut records = Vec::new();
+        while let Some(mut record) = decoder.decode_record::<dbn::Mbp1Msg>().await?.cloned() {
+            if let Some(id) = map.get(&record.hd.instrument_id) {
+                record.hd.instrument_id = *id;
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Converts a dbn file to mbinary and back, the result must equal the source record for record.
+    async fn assert_round_trip(file: &str, schema: Schema, tickers: &[&str]) -> Result<()> {
+        let file_path = PathBuf::from(file);
+        let (mut decoder, map) = read_dbn_file(file_path.clone()).await?;
+
+        let mut mbinary_map = HashMap::new();
+        let mut symbol_map = SymbolMap::new();
+        for (id, ticker) in (20..).zip(tickers) {
+            mbinary_map.insert(ticker.to_string(), id);
+            symbol_map.add_instrument(ticker, id);
+        }
+        let new_map = instrument_id_map(map, mbinary_map)?;
+        let metadata = Metadata::new(schema, Dataset::Futures, 0, 0, symbol_map);
+
+        let mbinary_path = PathBuf::from("tests/data/test_to_dbn.bin");
+        let dbn_path = PathBuf::from("tests/data/test_to_dbn.dbn");
+        to_mbinary(&metadata, &mut decoder, &new_map, &mbinary_path).await?;
+
+        // Test
+        let count = to_dbn(&mbinary_path, &dbn_path).await?;
+
+        // Validate
+        let (decoder, map) = read_dbn_file(dbn_path.clone()).await?;
+        assert_eq!(decoder.metadata().schema, Some(dbn_schema(schema)));
+        for (id, ticker) in (20..).zip(tickers) {
+            assert_eq!(map.get(&u32::to_string(&id)).unwrap(), ticker);
+        }
+
+        let expected = dbn_records(&file_path, &new_map).await?;
+        let actual = dbn_records(&dbn_path, &HashMap::new()).await?;
+        assert!(!expected.is_empty());
+        assert_eq!(count, expected.len() as u64);
+        assert_eq!(actual.len(), expected.len());
+        for (i, (actual, expected)) in actual.iter().zip(&expected).enumerate() {
+            assert_eq!(actual, expected, "{} record {} differs", schema, i);
+        }
+
+        // Cleanup
+        fs::remove_file(&mbinary_path)?;
+        fs::remove_file(&dbn_path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_to_dbn_round_trip() -> Result<()> {
+        // to_mbinary reads mbp-1 records, which tbbo files share
+        assert_round_trip(
+            "tests/data/databento/GLBX.MDP3_mbp-1_ZM.n.0_GC.n.0_2024-08-20T00:00:00Z_2024-08-20T05:00:00Z.dbn",
+            Schema::Mbp1,
+            &["ZM.n.0", "GC.n.0"],
+        )
+        .await?;
+        assert_round_trip(
+            "tests/data/databento/GLBX.MDP3_tbbo_HEG4_HEJ4_LEG4_LEJ4_LEM4_HEM4_HEK4_2024-02-13T00:00:00Z_2024-02-17T00:00:00Z.dbn",
+            Schema::Tbbo,
+            &["HEG4", "HEJ4", "LEG4", "LEJ4", "LEM4", "HEM4", "HEK4"],
+        )
+        .await?;
+
+        Ok(())
+    }
 }
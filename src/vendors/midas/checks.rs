@@ -1,13 +1,13 @@
-use crate::error::Result;
+use crate::error;
+use crate::error::{Error, Result};
 use crate::vendors::databento::extract::read_dbn_file;
-use crate::vendors::midas::load::read_mbinary_file;
-use mbinary::decode::AsyncDecoder;
+use crate::vendors::midas::load::{mbinary_writer, read_mbinary_file, record_writer};
 use mbinary::record_enum::RecordEnum;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use mbinary::records::{Mbp1Msg, Record};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
-use tokio::io::BufReader;
 
 pub async fn compare_dbn_raw_output(
     dbn_filepath: PathBuf,
@@ -54,40 +54,173 @@ pub async fn compare_dbn_raw_output(
     Ok(())
 }
 
-pub async fn find_duplicates(filepath: &PathBuf) -> Result<usize> {
-    let mut occurrences: HashMap<RecordEnum, usize> = HashMap::new();
-    let mut decoder = AsyncDecoder::<BufReader<File>>::from_file(filepath).await?;
+/// Default lookback in nanoseconds behind the latest timestamp seen when scanning for duplicates.
+pub const DUPLICATE_WINDOW: u64 = 1_000_000_000;
+
+/// Record found more than once and its zero based positions in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    pub record: RecordEnum,
+    pub positions: Vec<usize>,
+}
+
+/// Records seen at one timestamp.
+#[derive(Default)]
+struct TimestampBucket {
+    positions: HashMap<RecordEnum, Vec<usize>>,
+    discriminators: HashMap<RecordEnum, u32>,
+}
+
+/// Record with the discriminator cleared, repeats of it at one timestamp are numbered by the discriminator.
+fn without_discriminator(record: &RecordEnum) -> Option<RecordEnum> {
+    match record {
+        RecordEnum::Mbp1(msg) => Some(RecordEnum::Mbp1(Mbp1Msg {
+            discriminator: 0,
+            ..*msg
+        })),
+        RecordEnum::Tbbo(msg) => Some(RecordEnum::Tbbo(Mbp1Msg {
+            discriminator: 0,
+            ..*msg
+        })),
+        _ => None,
+    }
+}
+
+fn set_discriminator(record: &mut RecordEnum, discriminator: u32) {
+    if let RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) = record {
+        msg.discriminator = discriminator;
+    }
+}
+
+/// Streams the file for identical records, only timestamps within `window` nanoseconds of the latest
+/// timestamp are held in memory. Identical records share a timestamp, so in files sorted by timestamp
+/// every duplicate is found, unsorted files need a window covering how far records are out of order.
+/// A record further behind than the window returns an error, as its timestamp may already be evicted.
+///
+/// If `deduped` is given the file is rewritten there without duplicates, keeping the first occurrence
+/// and renumbering discriminators of repeated records per timestamp from 0.
+pub async fn scan_duplicates(
+    filepath: &Path,
+    window: u64,
+    deduped: Option<&Path>,
+) -> Result<Vec<Duplicate>> {
+    let mut decoder = read_mbinary_file(filepath).await?;
+
+    let mut encoder = match deduped {
+        Some(output) => Some(match decoder.metadata() {
+            Some(metadata) => mbinary_writer(&metadata, output)?,
+            None => record_writer(output)?,
+        }),
+        None => None,
+    };
+
+    let mut buckets: BTreeMap<u64, TimestampBucket> = BTreeMap::new();
+    let mut duplicates = Vec::new();
+    let mut latest_ts: u64 = 0;
+    let mut position = 0;
+
+    let flush = |bucket: TimestampBucket, duplicates: &mut Vec<Duplicate>| {
+        for (record, positions) in bucket.positions {
+            if positions.len() > 1 {
+                duplicates.push(Duplicate { record, positions });
+            }
+        }
+    };
 
-    // Decode and count occurrences of each record
     while let Some(record_ref) = decoder.decode_ref().await? {
-        let record_enum = RecordEnum::from_ref(record_ref)?;
-        *occurrences.entry(record_enum).or_default() += 1;
+        let mut record = RecordEnum::from_ref(record_ref)?;
+        let ts = record.timestamp();
+        if ts < latest_ts.saturating_sub(window) {
+            return Err(error!(
+                CustomError,
+                "Record {} at {} is more than {} ns behind {}, sort the file or widen the window.",
+                position,
+                ts,
+                window,
+                latest_ts
+            ));
+        }
+        latest_ts = latest_ts.max(ts);
+
+        let bucket = buckets.entry(ts).or_default();
+        let positions = bucket.positions.entry(record.clone()).or_default();
+        positions.push(position);
+        position += 1;
+
+        if positions.len() == 1 {
+            if let Some(encoder) = encoder.as_mut() {
+                if let Some(key) = without_discriminator(&record) {
+                    let count = bucket.discriminators.entry(key).or_insert(0);
+                    set_discriminator(&mut record, *count);
+                    *count += 1;
+                }
+                encoder.encode_record(&record.to_record_ref())?;
+            }
+        }
+
+        // Evict timestamps that fell out of the window
+        let cutoff = latest_ts.saturating_sub(window);
+        while let Some(entry) = buckets.first_entry() {
+            if *entry.key() >= cutoff {
+                break;
+            }
+            flush(entry.remove(), &mut duplicates);
+        }
+    }
+
+    for (_, bucket) in buckets {
+        flush(bucket, &mut duplicates);
+    }
+
+    if let Some(encoder) = encoder {
+        encoder.finish()?;
     }
 
-    // Identify duplicates
-    let duplicates: Vec<_> = occurrences
-        .iter()
-        .filter(|&(_, &count)| count > 1)
-        .collect();
+    duplicates.sort_by_key(|duplicate| duplicate.positions[0]);
+
+    Ok(duplicates)
+}
+
+/// Prints the duplicates found in the file, returns the number of distinct duplicated records.
+pub async fn report_duplicates(
+    filepath: &Path,
+    window: u64,
+    deduped: Option<&Path>,
+) -> Result<usize> {
+    let duplicates = scan_duplicates(filepath, window, deduped).await?;
 
     // Respond based on results
     if duplicates.is_empty() {
         println!("No duplicate records found in the file.");
     } else {
         println!("Found {} duplicate records in the file:", duplicates.len());
-        for (record, count) in &duplicates {
-            println!("{:?} - {} occurrences", record, count);
+        for duplicate in &duplicates {
+            println!(
+                "{:?} - {} occurrences at positions {:?}",
+                duplicate.record,
+                duplicate.positions.len(),
+                duplicate.positions
+            );
         }
     }
 
+    if let Some(output) = deduped {
+        println!("Deduplicated file written to {}", output.display());
+    }
+
     Ok(duplicates.len())
 }
 
+pub async fn find_duplicates(filepath: &Path) -> Result<usize> {
+    report_duplicates(filepath, DUPLICATE_WINDOW, None).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::Result;
     use crate::vendors::databento::transform::{instrument_id_map, to_mbinary};
+    use mbinary::decode::AsyncDecoder;
     use mbinary::encode::RecordEncoder;
     use mbinary::enums::{Dataset, Schema};
     use mbinary::metadata::Metadata;
@@ -95,7 +228,7 @@ mod tests {
     use mbinary::symbols::SymbolMap;
     use mbinary::{
         self,
-        records::{BidAskPair, Mbp1Msg, RecordHeader},
+        records::{BidAskPair, RecordHeader},
     };
     use serial_test::serial;
     use std::path::PathBuf;
@@ -396,4 +529,130 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_scan_duplicates_deduped() -> Result<()> {
+        let record = |ts_recv: u64, price: i64, discriminator: u32| Mbp1Msg {
+            hd: RecordHeader::new::<Mbp1Msg>(1333, 1724079906415347717, 0),
+            price,
+            size: 2,
+            action: 84,
+            side: 66,
+            depth: 0,
+            flags: 0,
+            ts_recv,
+            ts_in_delta: 17171,
+            sequence: 900097,
+            discriminator,
+            levels: [BidAskPair {
+                bid_px: 76000000000,
+                ask_px: 76025000000,
+                bid_sz: 7,
+                ask_sz: 3,
+                bid_ct: 6,
+                ask_ct: 3,
+            }],
+        };
+
+        // Legitimate repeat appended twice, then a record with a stale discriminator
+        let records = [
+            record(100, 76025000000, 0),
+            record(100, 76025000000, 1),
+            record(100, 76025000000, 0),
+            record(100, 76025000000, 1),
+            record(200, 76050000000, 5),
+        ];
+        let record_refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = RecordEncoder::new(&mut buffer);
+        encoder
+            .encode_records(&record_refs)
+            .expect("Encoding failed");
+
+        let path = PathBuf::from("tests/data/test_duplicates_input.bin");
+        let output = PathBuf::from("tests/data/test_duplicates_deduped.bin");
+        let _ = encoder.write_to_file(&path, false);
+
+        // Test
+        let duplicates = scan_duplicates(&path, 0, Some(&output)).await?;
+
+        // Validate
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].positions, vec![0, 2]);
+        assert_eq!(duplicates[1].positions, vec![1, 3]);
+
+        let mut decoder = AsyncDecoder::<tokio::io::BufReader<File>>::from_file(&output).await?;
+        let deduped = decoder.decode().await?;
+        let expected: Vec<RecordEnum> = vec![
+            RecordEnum::Mbp1(record(100, 76025000000, 0)),
+            RecordEnum::Mbp1(record(100, 76025000000, 1)),
+            RecordEnum::Mbp1(record(200, 76050000000, 0)),
+        ];
+        assert_eq!(deduped, expected);
+        assert!(scan_duplicates(&output, 0, None).await?.is_empty());
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(&output)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_scan_duplicates_out_of_window() -> Result<()> {
+        let record = |ts_recv: u64| Mbp1Msg {
+            hd: RecordHeader::new::<Mbp1Msg>(1333, 1724079906415347717, 0),
+            price: 76025000000,
+            size: 2,
+            action: 84,
+            side: 66,
+            depth: 0,
+            flags: 0,
+            ts_recv,
+            ts_in_delta: 17171,
+            sequence: 900097,
+            discriminator: 0,
+            levels: [BidAskPair {
+                bid_px: 76000000000,
+                ask_px: 76025000000,
+                bid_sz: 7,
+                ask_sz: 3,
+                bid_ct: 6,
+                ask_ct: 3,
+            }],
+        };
+
+        // Repeat of the first record arriving 200ns after a later timestamp
+        let records = [record(100), record(300), record(100)];
+        let record_refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = RecordEncoder::new(&mut buffer);
+        encoder
+            .encode_records(&record_refs)
+            .expect("Encoding failed");
+
+        let path = PathBuf::from("tests/data/test_duplicates_unsorted.bin");
+        let _ = encoder.write_to_file(&path, false);
+
+        // Test
+        let narrow = scan_duplicates(&path, 100, None).await;
+        let wide = scan_duplicates(&path, 200, None).await?;
+
+        // Validate
+        assert_eq!(
+            narrow.unwrap_err().to_string(),
+            "Custom error: Record 2 at 100 is more than 100 ns behind 300, sort the file or widen the window."
+        );
+        assert_eq!(wide.len(), 1);
+        assert_eq!(wide[0].positions, vec![0, 2]);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
 }
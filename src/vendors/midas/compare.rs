@@ -1,97 +1,161 @@
-use crate::error::Result;
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::{read_mbinary_file, MbinaryReader};
+use crate::vendors::midas::report::{CompareOptions, CompareReport};
 use mbinary::decode::AsyncDecoder;
 use mbinary::record_enum::RecordEnum;
 use mbinary::records::Record;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-use tokio::io::BufReader;
-
-pub async fn compare_mbinary(
-    mbinary_filepath1: &PathBuf,
-    mbinary_filepath2: &PathBuf,
-) -> Result<()> {
-    let batch_size = 1000; // Batch size for processing
-    let mut decoder1 = AsyncDecoder::<BufReader<File>>::from_file(mbinary_filepath1).await?;
-    let mut decoder2 = AsyncDecoder::<BufReader<File>>::from_file(mbinary_filepath2).await?;
-
-    let mut batch1: HashMap<u64, Vec<RecordEnum>> = HashMap::new();
-    let mut decoder_done = false;
-    let mut unmatched_records2 = Vec::new(); // Unmatched records from file 2
-
-    while let Some(record) = decoder2.decode_ref().await? {
-        // Refill batch1 if needed
-        if batch1.len() < batch_size && !decoder_done {
-            while let Some(record1) = decoder1.decode_ref().await? {
-                let record_enum = RecordEnum::from_ref(record1)?;
-                let ts_event = record_enum.header().ts_event;
-                batch1.entry(ts_event).or_default().push(record_enum);
-            }
-            if batch1.is_empty() {
-                decoder_done = true; // No more records to process
-            }
-        }
+use mbinary::symbols::SymbolMap;
+use std::path::{Path, PathBuf};
+
+/// Streams a file as runs of records sharing a timestamp, the file must be sorted by `Record::timestamp`.
+pub struct RecordBuckets {
+    decoder: AsyncDecoder<MbinaryReader>,
+    next: Option<RecordEnum>,
+    mappings: SymbolMap,
+    filepath: PathBuf,
+}
 
-        // Process current record from file 2
-        let record_enum = RecordEnum::from_ref(record)?;
-        let ts_event = record_enum.header().ts_event;
+impl RecordBuckets {
+    pub async fn new(filepath: &Path) -> Result<Self> {
+        let mut decoder = read_mbinary_file(filepath).await?;
+        let mappings = decoder
+            .metadata()
+            .map(|metadata| metadata.mappings)
+            .unwrap_or_else(SymbolMap::new);
+        let next = match decoder.decode_ref().await? {
+            Some(record_ref) => Some(RecordEnum::from_ref(record_ref)?),
+            None => None,
+        };
 
-        if let Some(group) = batch1.get_mut(&ts_event) {
-            // Try to match within the group
-            if let Some(pos) = group.iter().position(|r| r == &record_enum) {
-                group.remove(pos); // Remove matched record
-                if group.is_empty() {
-                    batch1.remove(&ts_event); // Remove empty groups
-                }
-            } else {
-                unmatched_records2.push(record_enum); // No match found
-            }
-        } else {
-            unmatched_records2.push(record_enum); // No group for ts_event
-        }
+        Ok(RecordBuckets {
+            decoder,
+            next,
+            mappings,
+            filepath: filepath.to_path_buf(),
+        })
     }
 
-    // Write unmatched records to an output file
-    write_unmatched_records("compare_results.txt", &batch1, &unmatched_records2).await?;
+    pub fn has_mappings(&self) -> bool {
+        !self.mappings.map.is_empty()
+    }
 
-    // Print match status
-    if batch1.is_empty() && unmatched_records2.is_empty() {
-        println!("All records match successfully.");
-    } else {
-        eprintln!("Unmatched records detected. Check the output file: compare_results.txt");
+    /// Ticker of the instrument from the file symbol map, falls back to the id.
+    pub fn ticker(&self, instrument_id: u32) -> String {
+        self.mappings
+            .get_instrument_ticker(instrument_id)
+            .unwrap_or_else(|| instrument_id.to_string())
     }
 
-    Ok(())
-}
+    /// Returns the next timestamp and all consecutive records with that timestamp.
+    pub async fn next_bucket(&mut self) -> Result<Option<(u64, Vec<RecordEnum>)>> {
+        let first = match self.next.take() {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let ts = first.timestamp();
+        let mut bucket = vec![first];
+
+        while let Some(record_ref) = self.decoder.decode_ref().await? {
+            let record = RecordEnum::from_ref(record_ref)?;
+            let record_ts = record.timestamp();
 
-async fn write_unmatched_records(
-    output_file: &str,
-    unmatched_batch1: &HashMap<u64, Vec<RecordEnum>>,
-    unmatched_records2: &[RecordEnum],
-) -> Result<()> {
-    let mut file = File::create(output_file).await?;
-
-    // Write unmatched records from file 1
-    if !unmatched_batch1.is_empty() {
-        file.write_all(b"Unmatched MBN File 1 Records:\n").await?;
-        for (ts_event, records) in unmatched_batch1 {
-            for record in records {
-                file.write_all(format!("{:?} (ts_event: {})\n", record, ts_event).as_bytes())
-                    .await?;
+            if record_ts == ts {
+                bucket.push(record);
+                continue;
             }
+            if record_ts < ts {
+                return Err(error!(
+                    CustomError,
+                    "File {} is not sorted by timestamp, {} follows {}.",
+                    self.filepath.display(),
+                    record_ts,
+                    ts
+                ));
+            }
+            self.next = Some(record);
+            break;
         }
+
+        Ok(Some((ts, bucket)))
     }
+}
 
-    // Write unmatched records from file 2
-    if !unmatched_records2.is_empty() {
-        file.write_all(b"Unmatched MBN File 2 Records:\n").await?;
-        for record in unmatched_records2 {
-            file.write_all(format!("{:?}\n", record).as_bytes()).await?;
+/// Removes pairs accepted by `eq` from two buckets of the same timestamp, returns the matched left records.
+pub fn match_bucket<L, R>(
+    left: &mut Vec<L>,
+    right: &mut Vec<R>,
+    eq: impl Fn(&L, &R) -> bool,
+) -> Vec<L> {
+    let mut matched = Vec::new();
+
+    right.retain(|r| match left.iter().position(|l| eq(l, r)) {
+        Some(pos) => {
+            matched.push(left.remove(pos));
+            false
         }
+        None => true,
+    });
+
+    matched
+}
+
+/// Merge-joins two timestamp sorted files, holding only one timestamp bucket from each side in memory.
+pub async fn compare_mbinary(
+    mbinary_filepath1: &Path,
+    mbinary_filepath2: &Path,
+    options: &CompareOptions,
+) -> Result<CompareReport> {
+    let mut buckets1 = RecordBuckets::new(mbinary_filepath1).await?;
+    let mut buckets2 = RecordBuckets::new(mbinary_filepath2).await?;
+    let mut report = CompareReport::new(
+        &mbinary_filepath1.display().to_string(),
+        &mbinary_filepath2.display().to_string(),
+        options.max_examples,
+    );
+
+    let mut bucket1 = buckets1.next_bucket().await?;
+    let mut bucket2 = buckets2.next_bucket().await?;
+
+    loop {
+        let (ts, records1, records2) = match (bucket1.take(), bucket2.take()) {
+            (None, None) => break,
+            (Some((ts1, records1)), None) => {
+                bucket1 = buckets1.next_bucket().await?;
+                (ts1, records1, Vec::new())
+            }
+            (None, Some((ts2, records2))) => {
+                bucket2 = buckets2.next_bucket().await?;
+                (ts2, Vec::new(), records2)
+            }
+            (Some((ts1, records1)), Some((ts2, records2))) => {
+                if ts1 < ts2 {
+                    bucket1 = buckets1.next_bucket().await?;
+                    bucket2 = Some((ts2, records2));
+                    (ts1, records1, Vec::new())
+                } else if ts1 > ts2 {
+                    bucket1 = Some((ts1, records1));
+                    bucket2 = buckets2.next_bucket().await?;
+                    (ts2, Vec::new(), records2)
+                } else {
+                    bucket1 = buckets1.next_bucket().await?;
+                    bucket2 = buckets2.next_bucket().await?;
+                    (ts1, records1, records2)
+                }
+            }
+        };
+
+        report.add_bucket(
+            ts,
+            records1,
+            records2,
+            &options.tolerances,
+            |id| buckets1.ticker(id),
+            |id| buckets2.ticker(id),
+        );
     }
 
-    Ok(())
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -109,6 +173,7 @@ mod tests {
         symbols::SymbolMap,
     };
     use mbinary::{metadata::Metadata, records::Mbp1Msg};
+    use std::collections::HashMap;
 
     async fn dummy_file() -> Result<PathBuf> {
         // Load DBN file
@@ -217,10 +282,11 @@ mod tests {
         let path = dummy_file().await?;
 
         // Test
-        let result = compare_mbinary(&path, &path).await?;
+        let report = compare_mbinary(&path, &path, &CompareOptions::default()).await?;
 
         // Validate
-        assert!(result == ());
+        assert!(report.is_match());
+        assert!(report.matched() > 0);
 
         //Cleanup
         if path.exists() {
@@ -238,19 +304,36 @@ mod tests {
         let path = dummy_file().await?;
 
         // Test
-        let x = compare_mbinary(&mbinary_file_path1, &path).await?;
+        let report =
+            compare_mbinary(&mbinary_file_path1, &path, &CompareOptions::default()).await?;
 
         // Validate
-        assert!(x == ());
+        assert!(!report.is_match());
+        assert_eq!(report.matched(), 0);
+        assert_eq!(report.unmatched_left.len(), 2);
 
         //Cleanup
         if path.exists() {
             std::fs::remove_file(&path).expect("Failed to delete the test file.");
         }
-        if path.exists() {
+        if mbinary_file_path1.exists() {
             std::fs::remove_file(&mbinary_file_path1).expect("Failed to delete the test file.");
         }
 
         Ok(())
     }
+
+    #[test]
+    fn test_match_bucket() {
+        let mut left = vec![1, 2, 2, 3];
+        let mut right = vec![2, 4, 2, 2];
+
+        // Test
+        let matched = match_bucket(&mut left, &mut right, |l, r| l == r);
+
+        // Validate
+        assert_eq!(matched, vec![2, 2]);
+        assert_eq!(left, vec![1, 3]);
+        assert_eq!(right, vec![4, 2]);
+    }
 }
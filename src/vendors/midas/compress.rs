@@ -0,0 +1,159 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::{is_zstd, mbinary_writer, read_mbinary_file};
+use std::path::{Path, PathBuf};
+
+/// Copies a file record by record, each side is compressed or not by its own extension.
+/// Returns the number of records copied.
+pub async fn recode_mbinary(filepath: &Path, output: &Path) -> Result<u64> {
+    if filepath == output {
+        return Err(error!(
+            CustomError,
+            "Output must differ from the input file."
+        ));
+    }
+
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+
+    let mut encoder = mbinary_writer(&metadata, output)?;
+    let mut count = 0;
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        encoder.encode_record(&record_ref)?;
+        count += 1;
+    }
+    encoder.finish()?;
+
+    Ok(count)
+}
+
+/// Default output of `compress_mbinary` ex. data.bin -> data.bin.zst
+pub fn compressed_path(filepath: &Path) -> PathBuf {
+    let mut path = filepath.as_os_str().to_os_string();
+    path.push(".zst");
+    PathBuf::from(path)
+}
+
+/// Default output of `decompress_mbinary` ex. data.bin.zst -> data.bin
+pub fn decompressed_path(filepath: &Path) -> Result<PathBuf> {
+    if !is_zstd(filepath) {
+        return Err(error!(
+            CustomError,
+            "File {} does not end in .zst.",
+            filepath.display()
+        ));
+    }
+    Ok(filepath.with_extension(""))
+}
+
+/// Writes a zstd compressed copy and returns its path, the source is deleted if `remove` is set.
+pub async fn compress_mbinary(
+    filepath: &Path,
+    output: Option<PathBuf>,
+    remove: bool,
+) -> Result<(PathBuf, u64)> {
+    if is_zstd(filepath) {
+        return Err(error!(
+            CustomError,
+            "File {} is already compressed.",
+            filepath.display()
+        ));
+    }
+    let output = output.unwrap_or_else(|| compressed_path(filepath));
+    if !is_zstd(&output) {
+        return Err(error!(
+            CustomError,
+            "Output {} must end in .zst.",
+            output.display()
+        ));
+    }
+
+    let count = recode_mbinary(filepath, &output).await?;
+    if remove {
+        std::fs::remove_file(filepath)?;
+    }
+    Ok((output, count))
+}
+
+/// Writes an uncompressed copy and returns its path, the source is deleted if `remove` is set.
+pub async fn decompress_mbinary(
+    filepath: &Path,
+    output: Option<PathBuf>,
+    remove: bool,
+) -> Result<(PathBuf, u64)> {
+    let output = match output {
+        Some(output) => output,
+        None => decompressed_path(filepath)?,
+    };
+    if is_zstd(&output) {
+        return Err(error!(
+            CustomError,
+            "Output {} must not end in .zst.",
+            output.display()
+        ));
+    }
+
+    let count = recode_mbinary(filepath, &output).await?;
+    if remove {
+        std::fs::remove_file(filepath)?;
+    }
+    Ok((output, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::checks::scan_duplicates;
+    use crate::vendors::midas::compare::compare_mbinary;
+    use crate::vendors::midas::generate::{generate_mbinary, GenerateOptions};
+    use crate::vendors::midas::report::CompareOptions;
+    use crate::vendors::midas::verify::verify_mbinary;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_compress_round_trip() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_compress.bin");
+        let restored = PathBuf::from("tests/data/test_compress_restored.bin");
+        let options = GenerateOptions {
+            records: 2000,
+            duplicates: 2,
+            ..Default::default()
+        };
+        generate_mbinary(&options, &path).await?;
+
+        // Test
+        let (compressed, count) = compress_mbinary(&path, None, false).await?;
+        decompress_mbinary(&compressed, Some(restored.clone()), false).await?;
+
+        // Validate
+        assert_eq!(
+            compressed,
+            PathBuf::from("tests/data/test_compress.bin.zst")
+        );
+        assert_eq!(count, 2002);
+        assert!(std::fs::metadata(&compressed)?.len() < std::fs::metadata(&path)?.len());
+        assert_eq!(std::fs::read(&path)?, std::fs::read(&restored)?);
+
+        // Compressed files read like plain ones
+        let report = compare_mbinary(&path, &compressed, &CompareOptions::default()).await?;
+        assert!(report.is_match());
+        assert_eq!(scan_duplicates(&compressed, u64::MAX, None).await?.len(), 2);
+        let verification = verify_mbinary(&compressed);
+        assert!(verification.is_ok(), "{}", verification);
+        assert_eq!(verification.records, 2002);
+
+        assert!(compress_mbinary(&compressed, None, false).await.is_err());
+        assert!(decompress_mbinary(&path, None, false).await.is_err());
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(&compressed)?;
+        std::fs::remove_file(&restored)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,381 @@
+use crate::error::{Error, Result};
+use crate::utils::unix_nanos_to_date;
+use crate::vendors::midas::report::ReportFormat;
+use chrono::{Datelike, Duration, NaiveDate, TimeZone, Weekday};
+use chrono_tz::Tz;
+use mbinary::decode::Decoder;
+use mbinary::enums::{Schema, Stype};
+use mbinary::params::RetrieveParams;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::Record;
+use mbinary::symbols::Instrument;
+use midas_client::historical::Historical;
+use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+/// Missing ranges shown per instrument in the table, the json output lists every day.
+const MAX_RANGES: usize = 4;
+
+/// How an instrument's data on the server is sampled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoverageOptions {
+    /// Schema whose records are counted per day, hourly bars keep the requests small.
+    pub schema: Schema,
+    /// Narrows the window between the instrument's first and last available dates.
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    /// Timezone the days are counted in.
+    pub tz: Tz,
+}
+
+impl Default for CoverageOptions {
+    fn default() -> Self {
+        CoverageOptions {
+            schema: Schema::Ohlcv1H,
+            start: None,
+            end: None,
+            tz: chrono_tz::UTC,
+        }
+    }
+}
+
+/// What the server holds for one instrument and the weekdays in its window without records.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InstrumentCoverage {
+    pub instrument_id: u32,
+    pub ticker: String,
+    pub dataset: String,
+    pub vendor: String,
+    pub first_available: u64,
+    pub last_available: u64,
+    pub expiration_date: u64,
+    pub records: u64,
+    /// Weekdays in the sampled window and how many of them had records.
+    pub sessions: usize,
+    pub sessions_with_data: usize,
+    /// Weekdays without records as YYYY-MM-DD, exchange holidays show up here too.
+    pub missing: Vec<String>,
+}
+
+/// Sampled [start, end) window, None when the instrument has nothing to sample.
+pub fn coverage_window(
+    instrument: &Instrument,
+    options: &CoverageOptions,
+    now: u64,
+) -> Option<(u64, u64)> {
+    if instrument.last_available == 0 {
+        return None;
+    }
+
+    let start = instrument
+        .first_available
+        .max(options.start.unwrap_or_default());
+    let end = (instrument.last_available + 1)
+        .min(options.end.unwrap_or(u64::MAX))
+        .min(now);
+
+    (start < end).then_some((start, end))
+}
+
+/// Counts the records in an mbinary buffer per day in `tz`.
+pub fn daily_counts(data: &[u8], tz: &Tz) -> Result<BTreeMap<NaiveDate, u64>> {
+    let mut counts = BTreeMap::new();
+    if data.is_empty() {
+        return Ok(counts);
+    }
+
+    let mut decoder = Decoder::new(Cursor::new(data))?;
+    while let Some(record_ref) = decoder.decode_ref()? {
+        let ts = RecordEnum::from_ref(record_ref)?.timestamp();
+        let day = tz.timestamp_nanos(ts as i64).date_naive();
+        *counts.entry(day).or_insert(0) += 1;
+    }
+
+    Ok(counts)
+}
+
+/// Weekdays from `start` to `end` inclusive and the ones missing from `counts`.
+pub fn missing_sessions(
+    start: NaiveDate,
+    end: NaiveDate,
+    counts: &BTreeMap<NaiveDate, u64>,
+) -> (usize, Vec<NaiveDate>) {
+    let mut sessions = 0;
+    let mut missing = Vec::new();
+    let mut day = start;
+
+    while day <= end {
+        if !matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+            sessions += 1;
+            if !counts.contains_key(&day) {
+                missing.push(day);
+            }
+        }
+        day += Duration::days(1);
+    }
+
+    (sessions, missing)
+}
+
+/// Collapses missing days into ranges, a weekend does not break a range.
+pub fn missing_ranges(missing: &[NaiveDate]) -> Vec<String> {
+    let mut ranges: Vec<(NaiveDate, NaiveDate)> = Vec::new();
+
+    for day in missing {
+        match ranges.last_mut() {
+            Some((_, last)) if (*day - *last).num_days() <= 3 && weekend_between(*last, *day) => {
+                *last = *day;
+            }
+            _ => ranges.push((*day, *day)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}..{}", first, last)
+            }
+        })
+        .collect()
+}
+
+/// True when every day strictly between the two is a weekend day.
+fn weekend_between(first: NaiveDate, last: NaiveDate) -> bool {
+    first
+        .iter_days()
+        .skip(1)
+        .take_while(|day| *day < last)
+        .all(|day| matches!(day.weekday(), Weekday::Sat | Weekday::Sun))
+}
+
+/// Samples the instrument's records from the server and reports the days without any.
+pub async fn instrument_coverage(
+    instrument: &Instrument,
+    client: &Historical,
+    options: &CoverageOptions,
+    now: u64,
+) -> Result<InstrumentCoverage> {
+    let mut coverage = InstrumentCoverage {
+        instrument_id: instrument.instrument_id.unwrap_or_default(),
+        ticker: instrument.ticker.clone(),
+        dataset: instrument.dataset.to_string(),
+        vendor: format!("{:?}", instrument.vendor),
+        first_available: instrument.first_available,
+        last_available: instrument.last_available,
+        expiration_date: instrument.expiration_date,
+        ..Default::default()
+    };
+
+    let (start, end) = match coverage_window(instrument, options, now) {
+        Some(window) => window,
+        None => return Ok(coverage),
+    };
+
+    let params = RetrieveParams {
+        symbols: vec![instrument.ticker.clone()],
+        start_ts: start as i64,
+        end_ts: end as i64,
+        schema: options.schema,
+        dataset: instrument.dataset,
+        stype: Stype::Raw,
+    };
+    let response = client.get_records(&params).await?;
+    if response.code != 200 {
+        return Err(Error::ApiError(response.message));
+    }
+
+    let counts = daily_counts(&response.data, &options.tz)?;
+    let first_day = options.tz.timestamp_nanos(start as i64).date_naive();
+    let last_day = options.tz.timestamp_nanos(end as i64 - 1).date_naive();
+    let (sessions, missing) = missing_sessions(first_day, last_day, &counts);
+
+    coverage.records = counts.values().sum();
+    coverage.sessions = sessions;
+    coverage.sessions_with_data = sessions - missing.len();
+    coverage.missing = missing.iter().map(|day| day.to_string()).collect();
+
+    Ok(coverage)
+}
+
+fn date(unix_nanos: u64) -> Result<String> {
+    if unix_nanos == 0 {
+        return Ok("-".to_string());
+    }
+    unix_nanos_to_date(unix_nanos as i64)
+}
+
+pub fn coverage_table(coverage: &[InstrumentCoverage]) -> Result<Table> {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.add_row(Row::new(
+        [
+            "Id",
+            "Ticker",
+            "Dataset",
+            "Vendor",
+            "First Available",
+            "Last Available",
+            "Expiration",
+            "Records",
+            "Sessions",
+            "Missing",
+        ]
+        .iter()
+        .map(|title| Cell::new(title).style_spec("bFg"))
+        .collect(),
+    ));
+
+    for c in coverage {
+        let days: Vec<NaiveDate> = c
+            .missing
+            .iter()
+            .filter_map(|day| day.parse().ok())
+            .collect();
+        let ranges = missing_ranges(&days);
+        let mut missing = ranges
+            .iter()
+            .take(MAX_RANGES)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ");
+        if ranges.len() > MAX_RANGES {
+            missing.push_str(&format!(" (+{} more)", ranges.len() - MAX_RANGES));
+        }
+        let style = if c.missing.is_empty() { "Fg" } else { "Fr" };
+
+        table.add_row(Row::new(vec![
+            Cell::new(&c.instrument_id.to_string()).style_spec("Fw"),
+            Cell::new(&c.ticker).style_spec("Fg"),
+            Cell::new(&c.dataset),
+            Cell::new(&c.vendor),
+            Cell::new(&date(c.first_available)?),
+            Cell::new(&date(c.last_available)?),
+            Cell::new(&date(c.expiration_date)?),
+            Cell::new(&c.records.to_string()),
+            Cell::new(&format!("{}/{}", c.sessions_with_data, c.sessions)),
+            Cell::new(if missing.is_empty() { "-" } else { &missing }).style_spec(style),
+        ]));
+    }
+    Ok(table)
+}
+
+pub fn render_coverage(coverage: &[InstrumentCoverage], format: ReportFormat) -> Result<String> {
+    match format {
+        ReportFormat::Text => Ok(coverage_table(coverage)?.to_string()),
+        ReportFormat::Json => Ok(serde_json::to_string_pretty(coverage)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::generate::{generate_mbinary, GenerateOptions};
+    use mbinary::enums::Dataset;
+    use mbinary::vendors::Vendors;
+    use serial_test::serial;
+    use std::path::PathBuf;
+
+    // 2024-01-02 00:00:00 UTC, a Tuesday
+    const START: u64 = 1704153600000000000;
+    const HOUR: u64 = 3_600_000_000_000;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, d).unwrap()
+    }
+
+    fn instrument(first_available: u64, last_available: u64) -> Instrument {
+        Instrument {
+            instrument_id: Some(1),
+            ticker: "HE.n.0".to_string(),
+            name: "Lean Hogs".to_string(),
+            dataset: Dataset::Futures,
+            vendor: Vendors::Internal,
+            vendor_data: 0,
+            last_available,
+            first_available,
+            expiration_date: 0,
+            is_continuous: false,
+            active: true,
+        }
+    }
+
+    #[test]
+    fn test_coverage_window() {
+        let options = CoverageOptions {
+            start: Some(START + HOUR),
+            ..Default::default()
+        };
+
+        // Validate
+        assert_eq!(
+            coverage_window(&instrument(START, START + 10 * HOUR), &options, u64::MAX),
+            Some((START + HOUR, START + 10 * HOUR + 1))
+        );
+        assert_eq!(
+            coverage_window(
+                &instrument(START, START + 10 * HOUR),
+                &options,
+                START + 2 * HOUR
+            ),
+            Some((START + HOUR, START + 2 * HOUR))
+        );
+        assert_eq!(coverage_window(&instrument(0, 0), &options, u64::MAX), None);
+    }
+
+    #[test]
+    fn test_missing_sessions() {
+        let counts: BTreeMap<NaiveDate, u64> = [(day(2), 24), (day(3), 24), (day(9), 24)]
+            .into_iter()
+            .collect();
+
+        // Test
+        let (sessions, missing) = missing_sessions(day(2), day(10), &counts);
+
+        // Validate
+        assert_eq!(sessions, 7);
+        assert_eq!(missing, vec![day(4), day(5), day(8), day(10)]);
+        assert_eq!(
+            missing_ranges(&missing),
+            vec!["2024-01-04..2024-01-08", "2024-01-10"]
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_daily_counts() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_coverage.bin");
+        let options = GenerateOptions {
+            schema: Schema::Ohlcv1H,
+            start: START,
+            records: 30,
+            ..Default::default()
+        };
+        generate_mbinary(&options, &path).await?;
+        let data = std::fs::read(&path)?;
+
+        // Test
+        let utc = daily_counts(&data, &chrono_tz::UTC)?;
+        let new_york = daily_counts(&data, &chrono_tz::America::New_York)?;
+
+        // Validate
+        assert_eq!(
+            utc.into_iter().collect::<Vec<_>>(),
+            vec![(day(2), 24), (day(3), 6)]
+        );
+        assert_eq!(
+            new_york.into_iter().collect::<Vec<_>>(),
+            vec![(day(1), 5), (day(2), 24), (day(3), 1)]
+        );
+        assert!(daily_counts(&[], &chrono_tz::UTC)?.is_empty());
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
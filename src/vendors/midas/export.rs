@@ -0,0 +1,331 @@
+use crate::dates::parse_tz;
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::{mbinary_reader, read_mbinary_file};
+use chrono::{SecondsFormat, TimeZone};
+use chrono_tz::Tz;
+use mbinary::decode::AsyncDecoder;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::{BidAskPair, Record};
+use mbinary::PRICE_SCALE;
+use serde_json::Value;
+use std::io::Write;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, BufReader, ReadBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Mbinary,
+    Csv,
+    Jsonl,
+}
+
+impl TryFrom<&str> for ExportFormat {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "mbinary" => Ok(ExportFormat::Mbinary),
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            _ => Err(error!(CustomError, "Invalid format : {}", value)),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn list() -> Vec<&'static str> {
+        vec!["mbinary", "csv", "jsonl"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Timezone timestamps are shown in by the decoded formats.
+    pub tz: Tz,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            format: ExportFormat::Mbinary,
+            tz: chrono_tz::UTC,
+        }
+    }
+}
+
+impl ExportOptions {
+    pub fn new(format: &str, tz: Option<&str>) -> Result<Self> {
+        let tz = match tz {
+            Some(tz) => parse_tz(tz)?,
+            None => chrono_tz::UTC,
+        };
+
+        Ok(ExportOptions {
+            format: ExportFormat::try_from(format)?,
+            tz,
+        })
+    }
+}
+
+fn timestamp(ts: u64, tz: &Tz) -> Value {
+    Value::from(
+        tz.timestamp_nanos(ts as i64)
+            .to_rfc3339_opts(SecondsFormat::Nanos, true),
+    )
+}
+
+fn price(price: i64) -> Value {
+    Value::from(price as f64 / PRICE_SCALE as f64)
+}
+
+fn character(c: c_char) -> Value {
+    Value::from((c as u8 as char).to_string())
+}
+
+fn level_fields(level: &BidAskPair) -> Vec<(&'static str, Value)> {
+    vec![
+        ("bid_px", price(level.bid_px)),
+        ("ask_px", price(level.ask_px)),
+        ("bid_sz", Value::from(level.bid_sz)),
+        ("ask_sz", Value::from(level.ask_sz)),
+        ("bid_ct", Value::from(level.bid_ct)),
+        ("ask_ct", Value::from(level.ask_ct)),
+    ]
+}
+
+/// Flattens a record into named columns, prices are in units and timestamps in `tz`.
+pub fn record_fields(record: &RecordEnum, ticker: &str, tz: &Tz) -> Vec<(&'static str, Value)> {
+    let hd = record.header();
+    let mut fields = Vec::new();
+
+    match record {
+        RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) => {
+            fields.push(("ts_recv", timestamp(msg.ts_recv, tz)));
+            fields.push(("ts_event", timestamp(hd.ts_event, tz)));
+            fields.push(("instrument_id", Value::from(hd.instrument_id)));
+            fields.push(("ticker", Value::from(ticker)));
+            fields.push(("action", character(msg.action)));
+            fields.push(("side", character(msg.side)));
+            fields.push(("depth", Value::from(msg.depth)));
+            fields.push(("price", price(msg.price)));
+            fields.push(("size", Value::from(msg.size)));
+            fields.push(("flags", Value::from(msg.flags)));
+            fields.push(("ts_in_delta", Value::from(msg.ts_in_delta)));
+            fields.push(("sequence", Value::from(msg.sequence)));
+            fields.extend(level_fields(&msg.levels[0]));
+        }
+        RecordEnum::Trade(msg) => {
+            fields.push(("ts_recv", timestamp(msg.ts_recv, tz)));
+            fields.push(("ts_event", timestamp(hd.ts_event, tz)));
+            fields.push(("instrument_id", Value::from(hd.instrument_id)));
+            fields.push(("ticker", Value::from(ticker)));
+            fields.push(("action", character(msg.action)));
+            fields.push(("side", character(msg.side)));
+            fields.push(("depth", Value::from(msg.depth)));
+            fields.push(("price", price(msg.price)));
+            fields.push(("size", Value::from(msg.size)));
+            fields.push(("flags", Value::from(msg.flags)));
+            fields.push(("ts_in_delta", Value::from(msg.ts_in_delta)));
+            fields.push(("sequence", Value::from(msg.sequence)));
+        }
+        RecordEnum::Ohlcv(msg) => {
+            fields.push(("ts_event", timestamp(hd.ts_event, tz)));
+            fields.push(("instrument_id", Value::from(hd.instrument_id)));
+            fields.push(("ticker", Value::from(ticker)));
+            fields.push(("open", price(msg.open)));
+            fields.push(("high", price(msg.high)));
+            fields.push(("low", price(msg.low)));
+            fields.push(("close", price(msg.close)));
+            fields.push(("volume", Value::from(msg.volume)));
+        }
+        RecordEnum::Bbo(msg) => {
+            fields.push(("ts_event", timestamp(hd.ts_event, tz)));
+            fields.push(("instrument_id", Value::from(hd.instrument_id)));
+            fields.push(("ticker", Value::from(ticker)));
+            fields.extend(level_fields(&msg.levels[0]));
+        }
+    }
+
+    fields
+}
+
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// Reader copying everything read from `reader` into `writer`.
+struct CopyReader<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R: AsyncRead + Unpin, W: Write + Unpin> AsyncRead for CopyReader<R, W> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        let this = &mut *self;
+        match Pin::new(&mut this.reader).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => Poll::Ready(this.writer.write_all(&buf.filled()[filled..])),
+            other => other,
+        }
+    }
+}
+
+/// Writes a file to `writer` in `options.format`, returns the number of records written.
+/// Mbinary is written back record by record, csv and jsonl are decoded with tickers from the file's
+/// symbol map.
+pub async fn export_mbinary<W: Write>(
+    filepath: &Path,
+    writer: &mut W,
+    options: &ExportOptions,
+) -> Result<u64> {
+    if options.format == ExportFormat::Mbinary {
+        // Records are decoded only to be counted, the bytes go out as read so the header keeps its
+        // symbol map order.
+        let reader = CopyReader {
+            reader: mbinary_reader(filepath).await?,
+            writer: &mut *writer,
+        };
+        let mut decoder = AsyncDecoder::new(BufReader::new(reader)).await?;
+        let mut count = 0;
+        while decoder.decode_ref().await?.is_some() {
+            count += 1;
+        }
+        writer.flush()?;
+        return Ok(count);
+    }
+
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+
+    let mut count = 0;
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+        let ticker = metadata
+            .mappings
+            .map
+            .get(&record.header().instrument_id)
+            .map(String::as_str)
+            .unwrap_or("");
+        let fields = record_fields(&record, ticker, &options.tz);
+
+        match options.format {
+            ExportFormat::Csv => {
+                if count == 0 {
+                    let header: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+                    writeln!(writer, "{}", header.join(","))?;
+                }
+                let row: Vec<String> = fields.iter().map(|(_, value)| csv_field(value)).collect();
+                writeln!(writer, "{}", row.join(","))?;
+            }
+            ExportFormat::Jsonl => {
+                // Built by hand to keep the column order of the csv output
+                let members: Vec<String> = fields
+                    .iter()
+                    .map(|(name, value)| format!("\"{}\":{}", name, value))
+                    .collect();
+                writeln!(writer, "{{{}}}", members.join(","))?;
+            }
+            ExportFormat::Mbinary => unreachable!(),
+        }
+        count += 1;
+    }
+    writer.flush()?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::generate::{generate_mbinary, GenerateOptions};
+    use mbinary::enums::Schema;
+    use serial_test::serial;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_export_options() -> Result<()> {
+        let options = ExportOptions::new("CSV", Some("America/New_York"))?;
+
+        // Validate
+        assert_eq!(options.format, ExportFormat::Csv);
+        assert_eq!(options.tz, chrono_tz::America::New_York);
+        assert!(ExportOptions::new("parquet", None).is_err());
+        assert!(ExportOptions::new("csv", Some("Mars/Olympus")).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_export_mbinary() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_export.bin");
+        let options = GenerateOptions {
+            schema: Schema::Ohlcv1M,
+            tickers: vec!["HE.n.0".to_string(), "ZC.n.0".to_string()],
+            records: 10,
+            ..Default::default()
+        };
+        generate_mbinary(&options, &path).await?;
+
+        // Test
+        let mut csv = Vec::new();
+        let csv_count = export_mbinary(&path, &mut csv, &ExportOptions::new("csv", None)?).await?;
+
+        let mut jsonl = Vec::new();
+        let jsonl_options = ExportOptions::new("jsonl", Some("America/New_York"))?;
+        let jsonl_count = export_mbinary(&path, &mut jsonl, &jsonl_options).await?;
+
+        let mut raw = Vec::new();
+        let raw_count = export_mbinary(&path, &mut raw, &ExportOptions::default()).await?;
+
+        // Validate
+        assert_eq!(raw_count, 10);
+        assert_eq!(csv_count, 10);
+        assert_eq!(jsonl_count, 10);
+        assert_eq!(raw, std::fs::read(&path)?);
+
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(
+            lines[0],
+            "ts_event,instrument_id,ticker,open,high,low,close,volume"
+        );
+        assert!(lines[1].starts_with("2024-01-02T00:00:00.000000000Z,1,HE.n.0,"));
+        assert!(lines[2].starts_with("2024-01-02T00:00:00.000000000Z,2,ZC.n.0,"));
+
+        let jsonl = String::from_utf8(jsonl).unwrap();
+        let line = jsonl.lines().next().unwrap();
+        assert!(line.starts_with("{\"ts_event\":"));
+        let first: Value = serde_json::from_str(line)?;
+        assert_eq!(first["ticker"], "HE.n.0");
+        assert_eq!(first["ts_event"], "2024-01-01T19:00:00.000000000-05:00");
+        assert_eq!(first["open"], 100.0);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::compress::recode_mbinary;
+use crate::vendors::midas::load::{is_zstd, mbinary_writer};
+use mbinary::encode::MetadataEncoder;
+use mbinary::enums::{Dataset, Schema};
+use mbinary::metadata::Metadata;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::{BboMsg, BidAskPair, Mbp1Msg, OhlcvMsg, RecordHeader, TradeMsg};
+use mbinary::symbols::SymbolMap;
+use mbinary::PRICE_SCALE;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+
+/// Settings for a synthetic file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerateOptions {
+    pub schema: Schema,
+    pub dataset: Dataset,
+    /// Instruments get ids 1, 2, ... in this order.
+    pub tickers: Vec<String>,
+    /// Timestamp of the first record.
+    pub start: u64,
+    /// Records to write, not counting injected duplicates.
+    pub records: usize,
+    /// Mean nanoseconds between events for mbp-1, tbbo and trades, bar schemas use their interval.
+    pub interval: u64,
+    pub start_price: i64,
+    pub tick_size: i64,
+    pub seed: u64,
+    /// Records repeated immediately after themselves.
+    pub duplicates: usize,
+    /// Pauses of `gap` nanoseconds inserted before random records.
+    pub gaps: usize,
+    pub gap: u64,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            schema: Schema::Mbp1,
+            dataset: Dataset::Futures,
+            tickers: vec!["HE.n.0".to_string()],
+            // 2024-01-02 00:00:00 UTC
+            start: 1704153600000000000,
+            records: 1000,
+            interval: 1_000_000,
+            start_price: 100 * PRICE_SCALE,
+            tick_size: PRICE_SCALE / 4,
+            seed: 0,
+            duplicates: 0,
+            gaps: 0,
+            gap: 3_600_000_000_000,
+        }
+    }
+}
+
+/// Bar length of the schemas written once per interval, None for event schemas.
+fn bar_interval(schema: Schema) -> Option<u64> {
+    match schema {
+        Schema::Ohlcv1S | Schema::Bbo1S => Some(1_000_000_000),
+        Schema::Ohlcv1M | Schema::Bbo1M => Some(60_000_000_000),
+        Schema::Ohlcv1H => Some(3_600_000_000_000),
+        Schema::Ohlcv1D => Some(86_400_000_000_000),
+        Schema::Mbp1 | Schema::Tbbo | Schema::Trades => None,
+    }
+}
+
+struct InstrumentWalk {
+    instrument_id: u32,
+    /// Best bid, the ask is one or two ticks above.
+    bid: i64,
+    spread: i64,
+    sequence: u32,
+}
+
+impl InstrumentWalk {
+    fn step(&mut self, rng: &mut StdRng, tick_size: i64) {
+        self.bid = (self.bid + rng.gen_range(-1..=1) * tick_size).max(tick_size);
+        self.spread = rng.gen_range(1..=2) * tick_size;
+        self.sequence += 1;
+    }
+
+    fn ask(&self) -> i64 {
+        self.bid + self.spread
+    }
+
+    fn levels(&self, rng: &mut StdRng) -> [BidAskPair; 1] {
+        [BidAskPair {
+            bid_px: self.bid,
+            ask_px: self.ask(),
+            bid_sz: rng.gen_range(1..=100),
+            ask_sz: rng.gen_range(1..=100),
+            bid_ct: rng.gen_range(1..=10),
+            ask_ct: rng.gen_range(1..=10),
+        }]
+    }
+
+    fn event(&mut self, schema: Schema, ts: u64, rng: &mut StdRng, tick_size: i64) -> RecordEnum {
+        self.step(rng, tick_size);
+
+        let action = match (schema, rng.gen_range(0..10)) {
+            (Schema::Mbp1, 0..=3) => b'A',
+            (Schema::Mbp1, 4..=6) => b'C',
+            (Schema::Mbp1, 7..=8) => b'M',
+            _ => b'T',
+        };
+        let side = if rng.gen_bool(0.5) { b'B' } else { b'A' };
+        let price = match (action, side) {
+            (b'T', b'B') => self.ask(),
+            (b'T', _) => self.bid,
+            (_, b'B') => self.bid - rng.gen_range(0..3) * tick_size,
+            _ => self.ask() + rng.gen_range(0..3) * tick_size,
+        };
+        let ts_event = ts - rng.gen_range(0..=1_000.min(ts));
+        let size = rng.gen_range(1..=50);
+
+        match schema {
+            Schema::Trades => RecordEnum::Trade(TradeMsg {
+                hd: RecordHeader::new::<TradeMsg>(self.instrument_id, ts_event, 0),
+                price,
+                size,
+                action: action as c_char,
+                side: side as c_char,
+                depth: 0,
+                flags: 0,
+                ts_recv: ts,
+                ts_in_delta: rng.gen_range(0..20_000),
+                sequence: self.sequence,
+            }),
+            _ => {
+                let msg = Mbp1Msg {
+                    hd: RecordHeader::new::<Mbp1Msg>(self.instrument_id, ts_event, 0),
+                    price,
+                    size,
+                    action: action as c_char,
+                    side: side as c_char,
+                    depth: 0,
+                    flags: 0,
+                    ts_recv: ts,
+                    ts_in_delta: rng.gen_range(0..20_000),
+                    sequence: self.sequence,
+                    discriminator: 0,
+                    levels: self.levels(rng),
+                };
+                if schema == Schema::Tbbo {
+                    RecordEnum::Tbbo(msg)
+                } else {
+                    RecordEnum::Mbp1(msg)
+                }
+            }
+        }
+    }
+
+    fn bar(&mut self, schema: Schema, ts: u64, rng: &mut StdRng, tick_size: i64) -> RecordEnum {
+        match schema {
+            Schema::Bbo1S | Schema::Bbo1M => {
+                self.step(rng, tick_size);
+                RecordEnum::Bbo(BboMsg {
+                    hd: RecordHeader::new::<BboMsg>(self.instrument_id, ts, 0),
+                    levels: self.levels(rng),
+                })
+            }
+            _ => {
+                let open = self.bid;
+                let (mut high, mut low, mut volume) = (open, open, 0);
+                for _ in 0..rng.gen_range(1..=20) {
+                    self.step(rng, tick_size);
+                    high = high.max(self.bid);
+                    low = low.min(self.bid);
+                    volume += rng.gen_range(1..=50);
+                }
+                RecordEnum::Ohlcv(OhlcvMsg {
+                    hd: RecordHeader::new::<OhlcvMsg>(self.instrument_id, ts, 0),
+                    open,
+                    high,
+                    low,
+                    close: self.bid,
+                    volume,
+                })
+            }
+        }
+    }
+}
+
+/// Writes a synthetic file of random walk prices, one walk per ticker, ordered by timestamp.
+/// The metadata covers the records written and maps each ticker to its id, the same seed always
+/// produces the same records. Returns the number of records written including duplicates.
+pub async fn generate_mbinary(options: &GenerateOptions, output: &PathBuf) -> Result<usize> {
+    // The header is rewritten in place once the end is known, so compressed files are generated
+    // plain and compressed after
+    if is_zstd(output) {
+        let plain = output.with_extension("");
+        let recoded = match generate_mbinary_plain(options, &plain).await {
+            Ok(_) => recode_mbinary(&plain, output)
+                .await
+                .map(|count| count as usize),
+            Err(e) => Err(e),
+        };
+        let _ = std::fs::remove_file(&plain);
+        return recoded;
+    }
+    generate_mbinary_plain(options, output).await
+}
+
+async fn generate_mbinary_plain(options: &GenerateOptions, output: &PathBuf) -> Result<usize> {
+    if options.tickers.is_empty() {
+        return Err(error!(CustomError, "At least one ticker is required."));
+    }
+    if options.tick_size <= 0 {
+        return Err(error!(CustomError, "Tick size must be positive."));
+    }
+
+    // Gaps go between timestamps, bar schemas share one timestamp across instruments
+    let bar = bar_interval(options.schema);
+    let steps = match bar {
+        Some(_) => options.records.div_ceil(options.tickers.len()),
+        None => options.records,
+    };
+    if options.duplicates > options.records || options.gaps >= steps.max(1) {
+        return Err(error!(
+            CustomError,
+            "Cannot inject more duplicates than records or more gaps than timestamps."
+        ));
+    }
+
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let duplicates: HashSet<usize> = sample(&mut rng, options.records, options.duplicates)
+        .into_iter()
+        .collect();
+    let gaps: HashSet<usize> = sample(&mut rng, steps - 1, options.gaps)
+        .into_iter()
+        .map(|step| step + 1)
+        .collect();
+
+    let mut mappings = SymbolMap::new();
+    let mut walks: Vec<InstrumentWalk> = options
+        .tickers
+        .iter()
+        .enumerate()
+        .map(|(i, ticker)| {
+            let instrument_id = i as u32 + 1;
+            mappings.add_instrument(ticker, instrument_id);
+            InstrumentWalk {
+                instrument_id,
+                bid: options.start_price,
+                spread: options.tick_size,
+                sequence: 0,
+            }
+        })
+        .collect();
+
+    // End is rewritten once the last timestamp is known, the header keeps its length.
+    let mut metadata = Metadata::new(
+        options.schema,
+        options.dataset,
+        options.start,
+        options.start,
+        mappings,
+    );
+    let mut encoder = mbinary_writer(&metadata, output)?;
+
+    let mut ts = options.start;
+    let mut count = 0;
+
+    for i in 0..options.records {
+        let record = match bar {
+            Some(interval) => {
+                // Every instrument gets a bar per interval
+                let walk = i % walks.len();
+                if walk == 0 && i > 0 {
+                    ts += interval;
+                    if gaps.contains(&(i / walks.len())) {
+                        ts += options.gap - options.gap % interval;
+                    }
+                }
+                walks[walk].bar(options.schema, ts, &mut rng, options.tick_size)
+            }
+            None => {
+                if i > 0 {
+                    ts += rng.gen_range(1..=2 * options.interval.max(1));
+                    if gaps.contains(&i) {
+                        ts += options.gap;
+                    }
+                }
+                let walk = rng.gen_range(0..walks.len());
+                walks[walk].event(options.schema, ts, &mut rng, options.tick_size)
+            }
+        };
+
+        encoder.encode_record(&record.to_record_ref())?;
+        count += 1;
+        if duplicates.contains(&i) {
+            encoder.encode_record(&record.to_record_ref())?;
+            count += 1;
+        }
+    }
+    encoder.finish()?;
+
+    metadata.end = ts + 1;
+    let mut file = std::fs::OpenOptions::new().write(true).open(output)?;
+    MetadataEncoder::new(&mut file).encode_metadata(&metadata)?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::checks::scan_duplicates;
+    use crate::vendors::midas::verify::verify_mbinary;
+    use mbinary::decode::AsyncDecoder;
+    use mbinary::records::Record;
+    use serial_test::serial;
+
+    async fn read_records(path: &PathBuf) -> Result<(Metadata, Vec<RecordEnum>)> {
+        let mut decoder =
+            AsyncDecoder::<tokio::io::BufReader<tokio::fs::File>>::from_file(path).await?;
+        let metadata = decoder.metadata().unwrap();
+        let records = decoder.decode().await?;
+        Ok((metadata, records))
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_generate_seeded() -> Result<()> {
+        let path1 = PathBuf::from("tests/data/test_generate1.bin");
+        let path2 = PathBuf::from("tests/data/test_generate2.bin");
+        let options = GenerateOptions {
+            tickers: vec!["HE.n.0".to_string(), "ZC.n.0".to_string()],
+            records: 500,
+            seed: 7,
+            ..Default::default()
+        };
+
+        // Test
+        let count = generate_mbinary(&options, &path1).await?;
+        generate_mbinary(&options, &path2).await?;
+
+        // Validate
+        assert_eq!(count, 500);
+        let (metadata, records) = read_records(&path1).await?;
+        assert_eq!(
+            (metadata.clone(), records.clone()),
+            read_records(&path2).await?
+        );
+        let verification = verify_mbinary(&path1);
+        assert!(verification.is_ok(), "{}", verification);
+
+        assert_eq!(
+            metadata.mappings.get_instrument_ticker(2).unwrap(),
+            "ZC.n.0"
+        );
+        assert!(records
+            .windows(2)
+            .all(|w| w[0].timestamp() <= w[1].timestamp()));
+        assert!(records.iter().all(|r| r.price() % options.tick_size == 0));
+
+        // Cleanup
+        std::fs::remove_file(&path1)?;
+        std::fs::remove_file(&path2)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_generate_duplicates_and_gaps() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_generate_injected.bin");
+        let options = GenerateOptions {
+            schema: Schema::Ohlcv1M,
+            tickers: vec!["HE.n.0".to_string(), "ZC.n.0".to_string()],
+            records: 100,
+            duplicates: 3,
+            gaps: 1,
+            gap: 3_600_000_000_000,
+            ..Default::default()
+        };
+
+        // Test
+        let count = generate_mbinary(&options, &path).await?;
+
+        // Validate
+        assert_eq!(count, 103);
+        assert_eq!(scan_duplicates(&path, u64::MAX, None).await?.len(), 3);
+        let (metadata, records) = read_records(&path).await?;
+        assert_eq!(metadata.schema, Schema::Ohlcv1M);
+        let last = records.last().unwrap().timestamp();
+        assert_eq!(metadata.end, last + 1);
+        assert_eq!(
+            last - options.start,
+            (50 - 1) * 60_000_000_000 + options.gap
+        );
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::Result;
+use async_compression::tokio::bufread::ZstdDecoder;
 use mbinary::decode::AsyncDecoder;
 use mbinary::metadata::Metadata;
 use mbinary::{
@@ -7,9 +8,104 @@ use mbinary::{
     record_ref::RecordRef,
     records::Mbp1Msg,
 };
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
-use tokio::io::BufReader;
+use tokio::io::{AsyncRead, BufReader};
+
+/// Reader over a plain or zstd compressed file.
+pub type MbinaryReader = BufReader<Box<dyn AsyncRead + Unpin + Send>>;
+
+/// File written plain or zstd compressed.
+enum FileWriter {
+    Plain(BufWriter<std::fs::File>),
+    Zstd(zstd::Encoder<'static, BufWriter<std::fs::File>>),
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            FileWriter::Plain(writer) => writer.write(buf),
+            FileWriter::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            FileWriter::Plain(writer) => writer.flush(),
+            FileWriter::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Encoder streaming records into a plain or zstd compressed file. The file is only complete once
+/// `finish` returns, a compressed file dropped before then is missing the end of its frame.
+pub struct MbinaryWriter {
+    writer: FileWriter,
+}
+
+impl MbinaryWriter {
+    pub fn encode_record(&mut self, record: &RecordRef) -> Result<()> {
+        self.writer.write_all(record.as_ref())?;
+        Ok(())
+    }
+
+    /// Ends the zstd frame of compressed files and flushes the file.
+    pub fn finish(self) -> Result<()> {
+        let mut file = match self.writer {
+            FileWriter::Plain(file) => file,
+            FileWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Files ending in `.zst` are read and written zstd compressed.
+pub fn is_zstd(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "zst")
+}
+
+/// File name without the `.bin` and `.zst` extensions ex. data.bin.zst -> data
+pub fn mbinary_stem(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = name.strip_suffix(".zst").unwrap_or(&name);
+    name.strip_suffix(".bin").unwrap_or(name).to_string()
+}
+
+/// Ticker made safe to use in a file name, characters other than letters, digits, `.`, `-`
+/// and `_` become `_` ex. ES/M4 -> ES_M4.
+pub fn file_component(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Writes bytes to the file, compressed as a separate zstd frame for `.zst` files so appends stay readable.
+fn write_bytes(bytes: &[u8], file_name: &PathBuf, append: bool) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(file_name)?;
+
+    if is_zstd(file_name) {
+        file.write_all(&zstd::encode_all(bytes, 0)?)?;
+    } else {
+        file.write_all(bytes)?;
+    }
+    file.flush()?;
+
+    Ok(())
+}
 
 pub fn metadata_to_file(metadata: &Metadata, file_name: &PathBuf, append: bool) -> Result<()> {
     // Encode metadata
@@ -17,9 +113,7 @@ pub fn metadata_to_file(metadata: &Metadata, file_name: &PathBuf, append: bool)
     let mut encoder = MetadataEncoder::new(&mut buffer);
     encoder.encode_metadata(&metadata)?;
 
-    let _ = encoder.write_to_file(file_name, append);
-
-    Ok(())
+    write_bytes(&buffer, file_name, append)
 }
 
 pub async fn mbinary_to_file(
@@ -38,17 +132,136 @@ pub async fn mbinary_to_file(
     let mut encoder = RecordEncoder::new(&mut buffer);
     encoder.encode_records(&refs)?;
 
-    let _ = encoder.write_to_file(file_name, append)?;
-
-    Ok(())
+    write_bytes(&buffer, file_name, append)
 }
 
-pub async fn read_mbinary_file(filepath: &PathBuf) -> Result<AsyncDecoder<BufReader<File>>> {
-    let decoder = AsyncDecoder::<BufReader<File>>::from_file(filepath).await?;
+pub async fn read_mbinary_file(filepath: &Path) -> Result<AsyncDecoder<MbinaryReader>> {
+    let decoder = AsyncDecoder::new(BufReader::new(mbinary_reader(filepath).await?)).await?;
 
     Ok(decoder)
 }
 
+/// Opens the file's raw mbinary bytes, decompressing `.zst` files.
+pub async fn mbinary_reader(filepath: &Path) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+    let file = BufReader::new(File::open(filepath).await?);
+
+    if is_zstd(filepath) {
+        let mut decoder = ZstdDecoder::new(file);
+        decoder.multiple_members(true);
+        Ok(Box::new(decoder))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Creates the file, or opens it to append, compressing what is written to `.zst` files.
+fn file_writer(file_name: &Path, append: bool) -> Result<FileWriter> {
+    let file = BufWriter::new(
+        std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(file_name)?,
+    );
+
+    if is_zstd(file_name) {
+        Ok(FileWriter::Zstd(zstd::Encoder::new(file, 0)?))
+    } else {
+        Ok(FileWriter::Plain(file))
+    }
+}
+
+/// Creates the file and returns an encoder to stream records into it, without a metadata header.
+pub fn record_writer(file_name: &Path) -> Result<MbinaryWriter> {
+    Ok(MbinaryWriter {
+        writer: file_writer(file_name, false)?,
+    })
+}
+
+/// Creates the file with the metadata header written and returns an encoder to stream records into it.
+pub fn mbinary_writer(metadata: &Metadata, file_name: &Path) -> Result<MbinaryWriter> {
+    let mut writer = file_writer(file_name, false)?;
+    MetadataEncoder::new(&mut writer).encode_metadata(metadata)?;
+
+    Ok(MbinaryWriter { writer })
+}
+
+/// Output files kept open at once by a `WriterPool`.
+pub const MAX_OPEN_WRITERS: usize = 256;
+
+/// Writers for one output file per key, with at most `max_open` files open at once. Once more are
+/// needed the least recently written is finished, and reopened to append if written again.
+/// Compressed files then hold one zstd frame per reopening, which `read_mbinary_file` reads through.
+pub struct WriterPool {
+    max_open: usize,
+    /// Open writers with the write count when each was last used.
+    open: HashMap<PathBuf, (u64, MbinaryWriter)>,
+    writes: u64,
+}
+
+impl WriterPool {
+    pub fn new(max_open: usize) -> Self {
+        WriterPool {
+            max_open: max_open.max(1),
+            open: HashMap::new(),
+            writes: 0,
+        }
+    }
+
+    /// Creates the file with the metadata header written, replacing any existing file.
+    pub fn create(&mut self, metadata: &Metadata, file_name: &Path) -> Result<()> {
+        self.make_room()?;
+        let writer = mbinary_writer(metadata, file_name)?;
+        self.open
+            .insert(file_name.to_path_buf(), (self.writes, writer));
+        Ok(())
+    }
+
+    /// Writes the record to a file made by `create`.
+    pub fn encode_record(&mut self, file_name: &Path, record: &RecordRef) -> Result<()> {
+        self.writes += 1;
+
+        if !self.open.contains_key(file_name) {
+            self.make_room()?;
+            let writer = MbinaryWriter {
+                writer: file_writer(file_name, true)?,
+            };
+            self.open.insert(file_name.to_path_buf(), (0, writer));
+        }
+        if let Some((used, writer)) = self.open.get_mut(file_name) {
+            *used = self.writes;
+            writer.encode_record(record)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the least recently written file when `max_open` are open.
+    fn make_room(&mut self) -> Result<()> {
+        if self.open.len() < self.max_open {
+            return Ok(());
+        }
+        let oldest = self
+            .open
+            .iter()
+            .min_by_key(|(_, (used, _))| *used)
+            .map(|(file_name, _)| file_name.clone());
+
+        if let Some((_, writer)) = oldest.and_then(|file_name| self.open.remove(&file_name)) {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Finishes every open file.
+    pub fn finish(self) -> Result<()> {
+        for (_, (_, writer)) in self.open {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -282,4 +495,163 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_zstd_append() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_zstd_append.bin.zst");
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument("HE.n.0", 1);
+        let metadata = Metadata::new(Schema::Mbp1, Dataset::Futures, 0, 0, symbol_map);
+        let record = Mbp1Msg {
+            hd: RecordHeader::new::<Mbp1Msg>(1, 1724079906415347717, 0),
+            price: 76025000000,
+            size: 2,
+            action: 84,
+            side: 66,
+            depth: 0,
+            flags: 0,
+            ts_recv: 1724079906416004321,
+            ts_in_delta: 17171,
+            sequence: 900097,
+            discriminator: 0,
+            levels: [BidAskPair {
+                bid_px: 76000000000,
+                ask_px: 76025000000,
+                bid_sz: 7,
+                ask_sz: 3,
+                bid_ct: 6,
+                ask_ct: 3,
+            }],
+        };
+
+        // Test
+        metadata_to_file(&metadata, &path, false)?;
+        mbinary_to_file(&vec![record; 3], &path, true).await?;
+        mbinary_to_file(&vec![record; 2], &path, true).await?;
+
+        // Validate
+        let mut decoder = read_mbinary_file(&path).await?;
+        assert_eq!(decoder.metadata(), Some(metadata));
+        assert_eq!(decoder.decode().await?.len(), 5);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mbinary_writer_finish() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_mbinary_writer.bin.zst");
+        let metadata = Metadata::new(Schema::Mbp1, Dataset::Futures, 0, 0, SymbolMap::new());
+        let record = Mbp1Msg {
+            hd: RecordHeader::new::<Mbp1Msg>(1, 1724079906415347717, 0),
+            price: 76025000000,
+            size: 2,
+            action: 84,
+            side: 66,
+            depth: 0,
+            flags: 0,
+            ts_recv: 1724079906416004321,
+            ts_in_delta: 17171,
+            sequence: 900097,
+            discriminator: 0,
+            levels: [BidAskPair {
+                bid_px: 76000000000,
+                ask_px: 76025000000,
+                bid_sz: 7,
+                ask_sz: 3,
+                bid_ct: 6,
+                ask_ct: 3,
+            }],
+        };
+
+        // Test
+        let mut encoder = mbinary_writer(&metadata, &path)?;
+        for _ in 0..3 {
+            encoder.encode_record(&RecordRef::from(&record))?;
+        }
+        encoder.finish()?;
+
+        // Validate
+        zstd::decode_all(std::fs::File::open(&path)?)?;
+        let mut decoder = read_mbinary_file(&path).await?;
+        assert_eq!(decoder.decode().await?.len(), 3);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_writer_pool_reopens() -> Result<()> {
+        let paths = [
+            PathBuf::from("tests/data/test_writer_pool_1.bin"),
+            PathBuf::from("tests/data/test_writer_pool_2.bin.zst"),
+        ];
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument("HE.n.0", 1);
+        let metadata = Metadata::new(Schema::Mbp1, Dataset::Futures, 0, 0, symbol_map);
+        let record = Mbp1Msg {
+            hd: RecordHeader::new::<Mbp1Msg>(1, 1724079906415347717, 0),
+            price: 76025000000,
+            size: 2,
+            action: 84,
+            side: 66,
+            depth: 0,
+            flags: 0,
+            ts_recv: 1724079906416004321,
+            ts_in_delta: 17171,
+            sequence: 900097,
+            discriminator: 0,
+            levels: [BidAskPair {
+                bid_px: 76000000000,
+                ask_px: 76025000000,
+                bid_sz: 7,
+                ask_sz: 3,
+                bid_ct: 6,
+                ask_ct: 3,
+            }],
+        };
+
+        // Test
+        // One file open at a time, every write closes the other file
+        let mut pool = WriterPool::new(1);
+        for path in &paths {
+            pool.create(&metadata, path)?;
+        }
+        for _ in 0..3 {
+            for path in &paths {
+                pool.encode_record(path, &RecordRef::from(&record))?;
+            }
+        }
+        pool.finish()?;
+
+        // Validate
+        for path in &paths {
+            let mut decoder = read_mbinary_file(path).await?;
+            assert_eq!(
+                decoder
+                    .metadata()
+                    .unwrap()
+                    .mappings
+                    .get_instrument_ticker(1),
+                Some("HE.n.0".to_string())
+            );
+            let records = decoder.decode().await?;
+            assert_eq!(records.len(), 3);
+            assert!(records.iter().all(|r| *r == RecordEnum::Mbp1(record)));
+        }
+
+        // Cleanup
+        for path in &paths {
+            std::fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
 }
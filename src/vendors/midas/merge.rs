@@ -0,0 +1,205 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::{mbinary_writer, read_mbinary_file};
+use mbinary::metadata::Metadata;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::Record;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::{Path, PathBuf};
+
+/// Combines file metadata, files must share schema and dataset and agree on instrument tickers.
+pub fn merge_metadata(files: &[PathBuf], metadatas: &[Metadata]) -> Result<Metadata> {
+    let mut merged = metadatas
+        .first()
+        .cloned()
+        .ok_or_else(|| error!(CustomError, "No files to merge."))?;
+
+    for (file, metadata) in files.iter().zip(metadatas).skip(1) {
+        if metadata.schema != merged.schema || metadata.dataset != merged.dataset {
+            return Err(error!(
+                CustomError,
+                "File {} is {} {}, expected {} {}.",
+                file.display(),
+                metadata.dataset,
+                metadata.schema,
+                merged.dataset,
+                merged.schema
+            ));
+        }
+
+        for (id, ticker) in &metadata.mappings.map {
+            if let Some(existing) = merged.mappings.map.get(id) {
+                if existing != ticker {
+                    return Err(error!(
+                        CustomError,
+                        "Instrument id {} maps to {} and {} in file {}.",
+                        id,
+                        existing,
+                        ticker,
+                        file.display()
+                    ));
+                }
+            }
+        }
+
+        merged.mappings.merge(&metadata.mappings);
+        merged.start = merged.start.min(metadata.start);
+        merged.end = merged.end.max(metadata.end);
+    }
+
+    Ok(merged)
+}
+
+/// K-way merges files into one timestamp ordered file, returns the number of records written.
+pub async fn merge_mbinary(filepaths: &[PathBuf], output: &Path) -> Result<usize> {
+    let mut decoders = Vec::with_capacity(filepaths.len());
+    let mut metadatas = Vec::with_capacity(filepaths.len());
+
+    for filepath in filepaths {
+        let mut decoder = read_mbinary_file(filepath).await?;
+        let metadata = decoder
+            .metadata()
+            .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+        decoders.push(decoder);
+        metadatas.push(metadata);
+    }
+
+    let metadata = merge_metadata(filepaths, &metadatas)?;
+    let mut encoder = mbinary_writer(&metadata, output)?;
+
+    // Prime the heap with the first record of each file, ties resolve by file order
+    let mut heads: Vec<Option<RecordEnum>> = Vec::with_capacity(decoders.len());
+    let mut heap = BinaryHeap::new();
+
+    for (idx, decoder) in decoders.iter_mut().enumerate() {
+        let head = match decoder.decode_ref().await? {
+            Some(record_ref) => Some(RecordEnum::from_ref(record_ref)?),
+            None => None,
+        };
+        if let Some(record) = &head {
+            heap.push(Reverse((record.timestamp(), idx)));
+        }
+        heads.push(head);
+    }
+
+    let mut count = 0;
+
+    while let Some(Reverse((_, idx))) = heap.pop() {
+        if let Some(record) = heads[idx].take() {
+            encoder.encode_record(&record.to_record_ref())?;
+            count += 1;
+        }
+
+        if let Some(record_ref) = decoders[idx].decode_ref().await? {
+            let record = RecordEnum::from_ref(record_ref)?;
+            heap.push(Reverse((record.timestamp(), idx)));
+            heads[idx] = Some(record);
+        }
+    }
+    encoder.finish()?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mbinary::decode::AsyncDecoder;
+    use mbinary::encode::CombinedEncoder;
+    use mbinary::enums::{Dataset, Schema};
+    use mbinary::record_ref::RecordRef;
+    use mbinary::records::{OhlcvMsg, RecordHeader};
+    use mbinary::symbols::SymbolMap;
+    use serial_test::serial;
+    use std::path::Path;
+
+    fn create_test_file(path: &Path, schema: Schema, id: u32, ts: &[u64]) -> Result<()> {
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument(&format!("TICKER{}", id), id);
+        let metadata = Metadata::new(
+            schema,
+            Dataset::Futures,
+            ts[0],
+            ts[ts.len() - 1],
+            symbol_map,
+        );
+
+        let records: Vec<OhlcvMsg> = ts
+            .iter()
+            .map(|t| OhlcvMsg {
+                hd: RecordHeader::new::<OhlcvMsg>(id, *t, 0),
+                open: 100,
+                high: 200,
+                low: 50,
+                close: 150,
+                volume: 1000,
+            })
+            .collect();
+        let refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = CombinedEncoder::new(&mut buffer);
+        encoder.encode(&metadata, &refs)?;
+        encoder.write_to_file(path, false)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_merge_mbinary() -> Result<()> {
+        let path1 = PathBuf::from("tests/data/test_merge_input1.bin");
+        let path2 = PathBuf::from("tests/data/test_merge_input2.bin");
+        let output = PathBuf::from("tests/data/test_merge_output.bin");
+        create_test_file(&path1, Schema::Ohlcv1M, 1, &[1, 3, 5, 7])?;
+        create_test_file(&path2, Schema::Ohlcv1M, 2, &[2, 3, 6])?;
+
+        // Test
+        let count = merge_mbinary(&[path1.clone(), path2.clone()], &output).await?;
+
+        // Validate
+        let mut decoder =
+            AsyncDecoder::<tokio::io::BufReader<tokio::fs::File>>::from_file(&output).await?;
+        let metadata = decoder.metadata().unwrap();
+        let records = decoder.decode().await?;
+        let ts: Vec<u64> = records.iter().map(|r| r.timestamp()).collect();
+
+        assert_eq!(count, 7);
+        assert_eq!(ts, vec![1, 2, 3, 3, 5, 6, 7]);
+        assert_eq!(records[2].header().instrument_id, 1);
+        assert_eq!(metadata.start, 1);
+        assert_eq!(metadata.end, 7);
+        assert_eq!(metadata.mappings.map.len(), 2);
+
+        // Cleanup
+        std::fs::remove_file(&path1)?;
+        std::fs::remove_file(&path2)?;
+        std::fs::remove_file(&output)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_merge_mbinary_schema_mismatch() -> Result<()> {
+        let path1 = PathBuf::from("tests/data/test_merge_input1.bin");
+        let path2 = PathBuf::from("tests/data/test_merge_input2.bin");
+        let output = PathBuf::from("tests/data/test_merge_output.bin");
+        create_test_file(&path1, Schema::Ohlcv1M, 1, &[1, 3])?;
+        create_test_file(&path2, Schema::Ohlcv1H, 2, &[2, 4])?;
+
+        // Test
+        let result = merge_mbinary(&[path1.clone(), path2.clone()], &output).await;
+
+        // Validate
+        assert!(result.is_err());
+        assert!(!output.exists());
+
+        // Cleanup
+        std::fs::remove_file(&path1)?;
+        std::fs::remove_file(&path2)?;
+
+        Ok(())
+    }
+}
@@ -1,3 +1,17 @@
 pub mod checks;
 pub mod compare;
+pub mod compress;
+pub mod coverage;
+pub mod export;
+pub mod generate;
 pub mod load;
+pub mod merge;
+pub mod plot;
+pub mod remap;
+pub mod report;
+pub mod resample;
+pub mod sample;
+pub mod slice;
+pub mod split;
+pub mod stats;
+pub mod verify;
@@ -0,0 +1,424 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::utils::{in_window, unix_nanos_to_date};
+use crate::vendors::midas::load::read_mbinary_file;
+use crate::vendors::midas::slice::{resolve_instrument_ids, SliceFilter};
+use colored::Colorize;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::{BidAskPair, Record};
+use mbinary::PRICE_SCALE;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Width reserved left of the chart for price labels.
+const AXIS_WIDTH: usize = 12;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartKind {
+    Line,
+    Candles,
+}
+
+impl TryFrom<&str> for ChartKind {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "line" => Ok(ChartKind::Line),
+            "candles" => Ok(ChartKind::Candles),
+            _ => Err(error!(
+                CustomError,
+                "Invalid chart '{}', expected line or candles.", value
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlotOptions {
+    pub kind: ChartKind,
+    /// Columns of the chart, excluding the price labels.
+    pub width: usize,
+    /// Rows of the price chart, excluding the axis and volume rows.
+    pub height: usize,
+}
+
+impl PlotOptions {
+    /// Fits the chart to the terminal when no width is given.
+    pub fn new(kind: ChartKind, width: Option<usize>, height: usize) -> Result<Self> {
+        let width = width.unwrap_or_else(|| {
+            let (_, columns) = console::Term::stdout().size();
+            (columns as usize).saturating_sub(AXIS_WIDTH + 2).min(200)
+        });
+
+        if width < 2 || height < 2 {
+            return Err(error!(
+                CustomError,
+                "Chart must be at least 2 columns wide and 2 rows high."
+            ));
+        }
+        Ok(PlotOptions {
+            kind,
+            width,
+            height,
+        })
+    }
+}
+
+/// Prices in one column of the chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bar {
+    pub open: i64,
+    pub high: i64,
+    pub low: i64,
+    pub close: i64,
+    pub volume: u64,
+}
+
+impl Bar {
+    fn add(&mut self, other: &Bar) {
+        self.high = self.high.max(other.high);
+        self.low = self.low.min(other.low);
+        self.close = other.close;
+        self.volume += other.volume;
+    }
+}
+
+/// One instrument's records bucketed into chart columns over its own time range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Series {
+    pub instrument_id: u32,
+    pub ticker: String,
+    pub start: u64,
+    pub end: u64,
+    pub bars: Vec<Option<Bar>>,
+}
+
+fn mid(level: &BidAskPair) -> Option<i64> {
+    let defined = |px: i64| px > 0 && px < i64::MAX;
+    (defined(level.bid_px) && defined(level.ask_px)).then(|| (level.bid_px + level.ask_px) / 2)
+}
+
+/// Price of a record as a bar, trades and OHLCV carry volume, quotes plot their midpoint.
+fn record_bar(record: &RecordEnum) -> Option<Bar> {
+    let trade = |price: i64, size: u32| Bar {
+        open: price,
+        high: price,
+        low: price,
+        close: price,
+        volume: size as u64,
+    };
+
+    match record {
+        RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) if msg.action as u8 == b'T' => {
+            Some(trade(msg.price, msg.size))
+        }
+        RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) => mid(&msg.levels[0]).map(|p| trade(p, 0)),
+        RecordEnum::Trade(msg) => Some(trade(msg.price, msg.size)),
+        RecordEnum::Bbo(msg) => mid(&msg.levels[0]).map(|p| trade(p, 0)),
+        RecordEnum::Ohlcv(msg) => Some(Bar {
+            open: msg.open,
+            high: msg.high,
+            low: msg.low,
+            close: msg.close,
+            volume: msg.volume,
+        }),
+    }
+}
+
+/// Reads the records matching the filter into `width` columns per instrument, ordered by instrument id.
+pub async fn plot_series(
+    filepath: &Path,
+    filter: &SliceFilter,
+    width: usize,
+) -> Result<Vec<Series>> {
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+    let ids = resolve_instrument_ids(&metadata.mappings, &filter.instrument_ids, &filter.tickers)?;
+    let selected = |record: &RecordEnum| {
+        ids.as_ref()
+            .is_none_or(|ids: &HashSet<u32>| ids.contains(&record.header().instrument_id))
+            && in_window(record.timestamp(), filter.start, filter.end)
+    };
+
+    // First pass finds each instrument's time range
+    let mut ranges: BTreeMap<u32, (u64, u64)> = BTreeMap::new();
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+        if selected(&record) && record_bar(&record).is_some() {
+            let ts = record.timestamp();
+            ranges
+                .entry(record.header().instrument_id)
+                .and_modify(|(start, end)| {
+                    *start = (*start).min(ts);
+                    *end = (*end).max(ts);
+                })
+                .or_insert((ts, ts));
+        }
+    }
+    if ranges.is_empty() {
+        return Err(error!(CustomError, "No prices to plot."));
+    }
+
+    let mut series: BTreeMap<u32, Series> = ranges
+        .iter()
+        .map(|(id, (start, end))| {
+            let series = Series {
+                instrument_id: *id,
+                ticker: metadata
+                    .mappings
+                    .get_instrument_ticker(*id)
+                    .unwrap_or_default(),
+                start: *start,
+                end: *end,
+                bars: vec![None; width],
+            };
+            (*id, series)
+        })
+        .collect();
+
+    let mut decoder = read_mbinary_file(filepath).await?;
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+        if !selected(&record) {
+            continue;
+        }
+        let Some(bar) = record_bar(&record) else {
+            continue;
+        };
+        let instrument_id = record.header().instrument_id;
+        let series = series.get_mut(&instrument_id).ok_or_else(|| {
+            error!(
+                CustomError,
+                "File {} changed while plotting, instrument {} was not in the first pass.",
+                filepath.display(),
+                instrument_id
+            )
+        })?;
+        let span = (series.end - series.start + 1) as u128;
+        let column = ((record.timestamp() - series.start) as u128 * width as u128 / span) as usize;
+
+        match &mut series.bars[column] {
+            Some(existing) => existing.add(&bar),
+            slot => *slot = Some(bar),
+        }
+    }
+
+    Ok(series.into_values().collect())
+}
+
+fn price(value: i64) -> String {
+    format!("{:.4}", value as f64 / PRICE_SCALE as f64)
+}
+
+/// Renders a series as a price chart with a volume sparkline underneath when there is volume.
+pub fn render_chart(series: &Series, options: &PlotOptions) -> String {
+    let bars: Vec<&Bar> = series.bars.iter().flatten().collect();
+    // Lines only plot closes, so they are scaled to the closes
+    let (high, low) = match options.kind {
+        ChartKind::Line => (
+            bars.iter().map(|b| b.close).max().unwrap_or_default(),
+            bars.iter().map(|b| b.close).min().unwrap_or_default(),
+        ),
+        ChartKind::Candles => (
+            bars.iter().map(|b| b.high).max().unwrap_or_default(),
+            bars.iter().map(|b| b.low).min().unwrap_or_default(),
+        ),
+    };
+    let rows = options.height;
+
+    // Row 0 is the top of the chart
+    let row = |price: i64| -> usize {
+        if high == low {
+            rows / 2
+        } else {
+            ((high - price) as f64 / (high - low) as f64 * (rows - 1) as f64).round() as usize
+        }
+    };
+
+    let mut grid = vec![vec![" ".to_string(); series.bars.len()]; rows];
+    let mut previous: Option<usize> = None;
+    for (column, bar) in series.bars.iter().enumerate() {
+        let Some(bar) = bar else {
+            continue;
+        };
+        match options.kind {
+            ChartKind::Line => {
+                let current = row(bar.close);
+                let (top, bottom) = match previous {
+                    Some(previous) => (previous.min(current), previous.max(current)),
+                    None => (current, current),
+                };
+                for cells in grid.iter_mut().take(bottom + 1).skip(top) {
+                    cells[column] = "│".cyan().to_string();
+                }
+                grid[current][column] = "•".cyan().to_string();
+                previous = Some(current);
+            }
+            ChartKind::Candles => {
+                let body = (row(bar.open.max(bar.close)), row(bar.open.min(bar.close)));
+                for (i, cells) in grid
+                    .iter_mut()
+                    .enumerate()
+                    .take(row(bar.low) + 1)
+                    .skip(row(bar.high))
+                {
+                    let cell = if i >= body.0 && i <= body.1 {
+                        "█"
+                    } else {
+                        "│"
+                    };
+                    cells[column] = if bar.close >= bar.open {
+                        cell.green().to_string()
+                    } else {
+                        cell.red().to_string()
+                    };
+                }
+            }
+        }
+    }
+
+    let last = bars.last().map(|b| price(b.close)).unwrap_or_default();
+    let mut lines = vec![format!(
+        "{} ({})  {} -> {}  last {}",
+        series.ticker.green().bold(),
+        series.instrument_id,
+        unix_nanos_to_date(series.start as i64).unwrap_or_default(),
+        unix_nanos_to_date(series.end as i64).unwrap_or_default(),
+        last
+    )];
+
+    for (i, cells) in grid.iter().enumerate() {
+        let label = if i == 0 {
+            price(high)
+        } else if i == rows - 1 {
+            price(low)
+        } else if i == rows / 2 {
+            price(high - (high - low) / 2)
+        } else {
+            String::new()
+        };
+        lines.push(format!(
+            "{:>width$} ┤{}",
+            label,
+            cells.concat(),
+            width = AXIS_WIDTH - 2
+        ));
+    }
+    lines.push(format!(
+        "{:>width$} └{}",
+        "",
+        "─".repeat(series.bars.len()),
+        width = AXIS_WIDTH - 2
+    ));
+
+    let max_volume = bars.iter().map(|b| b.volume).max().unwrap_or_default();
+    if max_volume > 0 {
+        let sparkline: String = series
+            .bars
+            .iter()
+            .map(|bar| match bar {
+                Some(bar) if bar.volume > 0 => {
+                    SPARKS[((bar.volume * 7) as f64 / max_volume as f64).round() as usize]
+                }
+                _ => ' ',
+            })
+            .collect();
+        lines.push(format!(
+            "{:>width$}  {}",
+            "volume",
+            sparkline,
+            width = AXIS_WIDTH - 2
+        ));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::generate::{generate_mbinary, GenerateOptions};
+    use mbinary::enums::Schema;
+    use serial_test::serial;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_plot_series() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_plot.bin");
+        let options = GenerateOptions {
+            schema: Schema::Ohlcv1M,
+            tickers: vec!["HE.n.0".to_string(), "ZC.n.0".to_string()],
+            records: 200,
+            ..Default::default()
+        };
+        generate_mbinary(&options, &path).await?;
+        let filter = SliceFilter {
+            tickers: vec!["ZC.n.0".to_string()],
+            end: Some(options.start + 50 * 60_000_000_000),
+            ..Default::default()
+        };
+
+        // Test
+        let series = plot_series(&path, &filter, 25).await?;
+
+        // Validate
+        assert_eq!(series.len(), 1);
+        let zc = &series[0];
+        assert_eq!(zc.ticker, "ZC.n.0");
+        assert_eq!(zc.end, options.start + 49 * 60_000_000_000);
+        assert_eq!(zc.bars.len(), 25);
+        assert!(zc.bars.iter().all(|bar| bar.is_some()));
+        let volume: u64 = zc.bars.iter().flatten().map(|b| b.volume).sum();
+        assert!(volume > 0);
+
+        let plot_options = PlotOptions::new(ChartKind::Candles, Some(25), 10)?;
+        let chart = render_chart(zc, &plot_options);
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines.len(), 1 + 10 + 1 + 1);
+        assert!(lines[0].contains("ZC.n.0"));
+        assert!(chart.contains('█'));
+        assert!(lines[12].trim_start().starts_with("volume"));
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_line() -> Result<()> {
+        let bar = |close: i64| {
+            Some(Bar {
+                open: close,
+                high: close,
+                low: close,
+                close,
+                volume: 0,
+            })
+        };
+        let series = Series {
+            instrument_id: 1,
+            ticker: "HE.n.0".to_string(),
+            start: 0,
+            end: 3,
+            bars: vec![bar(100), None, bar(300), bar(200)],
+        };
+
+        // Test
+        let chart = render_chart(&series, &PlotOptions::new(ChartKind::Line, Some(4), 3)?);
+
+        // Validate
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines.len(), 1 + 3 + 1);
+        assert!(lines[1].trim_start().starts_with("0.0000"));
+        assert!(lines[3].contains('•'));
+        assert!(PlotOptions::new(ChartKind::Line, Some(1), 3).is_err());
+
+        Ok(())
+    }
+}
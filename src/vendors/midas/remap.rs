@@ -0,0 +1,279 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::{is_zstd, mbinary_stem, mbinary_writer, read_mbinary_file};
+use mbinary::metadata::Metadata;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::Record;
+use mbinary::symbols::{Instrument, SymbolMap};
+use midas_client::instrument::Instruments;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Parses an id mapping ex. 20=45,21=46
+pub fn parse_id_map(value: &str) -> Result<HashMap<u32, u32>> {
+    let mut map = HashMap::new();
+
+    for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (old, new) = pair
+            .split_once('=')
+            .ok_or_else(|| error!(CustomError, "Invalid mapping '{}', expected old=new.", pair))?;
+        let parse = |id: &str| {
+            id.trim()
+                .parse::<u32>()
+                .map_err(|_| error!(CustomError, "Failed to parse id: {}", id))
+        };
+
+        if map.insert(parse(old)?, parse(new)?).is_some() {
+            return Err(error!(CustomError, "Id {} is mapped more than once.", old));
+        }
+    }
+
+    if map.is_empty() {
+        return Err(error!(CustomError, "No ids to remap."));
+    }
+    Ok(map)
+}
+
+/// Maps the ids in the file symbol map to the ids the server currently has for their tickers.
+pub async fn server_id_map(
+    metadata: &Metadata,
+    instrument_client: &Instruments,
+) -> Result<HashMap<u32, u32>> {
+    let mut map = HashMap::new();
+
+    for (id, ticker) in metadata.mappings.map.iter() {
+        let api_response = instrument_client
+            .get_symbol(ticker, &metadata.dataset)
+            .await
+            .map_err(|_| error!(CustomError, "Error getting ticker : {} .", ticker))?;
+        let instrument: &Instrument = api_response
+            .data
+            .first()
+            .ok_or_else(|| error!(CustomError, "Ticker {} not in database.", ticker))?;
+        let new_id = instrument
+            .instrument_id
+            .ok_or_else(|| error!(CustomError, "Ticker {} has no instrument id.", ticker))?;
+
+        if new_id != *id {
+            map.insert(*id, new_id);
+        }
+    }
+    Ok(map)
+}
+
+/// Symbol map with its ids remapped, fails if two tickers would end up sharing an id.
+fn remap_symbols(mappings: &SymbolMap, map: &HashMap<u32, u32>) -> Result<SymbolMap> {
+    let mut remapped = SymbolMap::new();
+
+    for (id, ticker) in mappings.map.iter() {
+        let new_id = *map.get(id).unwrap_or(id);
+
+        if let Some(existing) = remapped.map.get(&new_id) {
+            if existing != ticker {
+                return Err(error!(
+                    CustomError,
+                    "Id {} would be shared by {} and {}.", new_id, existing, ticker
+                ));
+            }
+        }
+        remapped.add_instrument(ticker, new_id);
+    }
+    Ok(remapped)
+}
+
+fn set_instrument_id(record: &mut RecordEnum, instrument_id: u32) {
+    match record {
+        RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) => msg.hd.instrument_id = instrument_id,
+        RecordEnum::Trade(msg) => msg.hd.instrument_id = instrument_id,
+        RecordEnum::Bbo(msg) => msg.hd.instrument_id = instrument_id,
+        RecordEnum::Ohlcv(msg) => msg.hd.instrument_id = instrument_id,
+    }
+}
+
+/// Rewrites the instrument ids in record headers and the metadata symbol map, streaming record by record.
+/// Ids not in `map` are kept. Returns the number of records remapped.
+pub async fn remap_mbinary(filepath: &Path, output: &Path, map: &HashMap<u32, u32>) -> Result<u64> {
+    if filepath == output {
+        return Err(error!(
+            CustomError,
+            "Output must differ from the input file."
+        ));
+    }
+
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let mut metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+    metadata.mappings = remap_symbols(&metadata.mappings, map)?;
+
+    let mut encoder = mbinary_writer(&metadata, output)?;
+    let mut count = 0;
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let mut record = RecordEnum::from_ref(record_ref)?;
+        let instrument_id = record.header().instrument_id;
+
+        if let Some(new_id) = map.get(&instrument_id) {
+            set_instrument_id(&mut record, *new_id);
+            count += 1;
+        }
+        encoder.encode_record(&record.to_record_ref())?;
+    }
+    encoder.finish()?;
+
+    Ok(count)
+}
+
+/// Remaps a file in place through a temporary file next to it, named with the same extension so
+/// it is compressed like the file ex. data.bin.zst -> data.remap.bin.zst
+pub async fn remap_mbinary_in_place(filepath: &Path, map: &HashMap<u32, u32>) -> Result<u64> {
+    let extension = if is_zstd(filepath) { "bin.zst" } else { "bin" };
+    let tmp = filepath.with_file_name(format!("{}.remap.{}", mbinary_stem(filepath), extension));
+
+    match remap_mbinary(filepath, &tmp, map).await {
+        Ok(count) => {
+            std::fs::rename(&tmp, filepath)?;
+            Ok(count)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::compress::compress_mbinary;
+    use crate::vendors::midas::verify::verify_mbinary;
+    use mbinary::decode::AsyncDecoder;
+    use mbinary::encode::CombinedEncoder;
+    use mbinary::enums::{Dataset, Schema};
+    use mbinary::record_ref::RecordRef;
+    use mbinary::records::{OhlcvMsg, RecordHeader};
+    use serial_test::serial;
+    use std::path::PathBuf;
+
+    fn create_test_file(path: &Path) -> Result<()> {
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument("HE.n.0", 1);
+        symbol_map.add_instrument("ZC.n.0", 2);
+        let metadata = Metadata::new(Schema::Ohlcv1H, Dataset::Futures, 0, 10, symbol_map);
+
+        let records: Vec<OhlcvMsg> = [(1, 1), (2, 1), (1, 2)]
+            .iter()
+            .map(|(id, ts)| OhlcvMsg {
+                hd: RecordHeader::new::<OhlcvMsg>(*id, *ts, 0),
+                open: 100,
+                high: 200,
+                low: 50,
+                close: 150,
+                volume: 1000,
+            })
+            .collect();
+        let refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = CombinedEncoder::new(&mut buffer);
+        encoder.encode(&metadata, &refs)?;
+        encoder.write_to_file(path, false)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_id_map() -> Result<()> {
+        // Test
+        let map = parse_id_map("20=45, 21=46")?;
+
+        // Validate
+        assert_eq!(map, HashMap::from([(20, 45), (21, 46)]));
+        assert!(parse_id_map("20=45,20=46").is_err());
+        assert!(parse_id_map("20:45").is_err());
+        assert!(parse_id_map("").is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_remap_in_place() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_remap.bin");
+        create_test_file(&path)?;
+
+        // Test
+        let count = remap_mbinary_in_place(&path, &HashMap::from([(1, 7)])).await?;
+
+        // Validate
+        assert_eq!(count, 2);
+        let mut decoder =
+            AsyncDecoder::<tokio::io::BufReader<tokio::fs::File>>::from_file(&path).await?;
+        let metadata = decoder.metadata().unwrap();
+        let ids: Vec<u32> = decoder
+            .decode()
+            .await?
+            .iter()
+            .map(|r| r.header().instrument_id)
+            .collect();
+        assert_eq!(ids, vec![7, 2, 7]);
+        assert_eq!(
+            metadata.mappings.get_instrument_ticker(7).unwrap(),
+            "HE.n.0"
+        );
+        assert_eq!(metadata.mappings.get_instrument_ticker(1), None);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_remap_in_place_zstd() -> Result<()> {
+        let plain = PathBuf::from("tests/data/test_remap_zstd.bin");
+        create_test_file(&plain)?;
+        let (path, _) = compress_mbinary(&plain, None, true).await?;
+
+        // Test
+        remap_mbinary_in_place(&path, &HashMap::from([(1, 7)])).await?;
+
+        // Validate
+        assert!(verify_mbinary(&path).errors.is_empty());
+        let mut decoder = read_mbinary_file(&path).await?;
+        let ids: Vec<u32> = decoder
+            .decode()
+            .await?
+            .iter()
+            .map(|r| r.header().instrument_id)
+            .collect();
+        assert_eq!(ids, vec![7, 2, 7]);
+        assert!(!PathBuf::from("tests/data/test_remap_zstd.remap.bin.zst").exists());
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_remap_collision() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_remap_collision.bin");
+        let output = PathBuf::from("tests/data/test_remap_collision_out.bin");
+        create_test_file(&path)?;
+
+        // Test
+        let result = remap_mbinary(&path, &output, &HashMap::from([(1, 2)])).await;
+
+        // Validate
+        assert!(result.is_err());
+        assert!(!output.exists());
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
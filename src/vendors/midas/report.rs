@@ -0,0 +1,733 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::compare::match_bucket;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::{BidAskPair, Record};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Write};
+use std::path::PathBuf;
+
+/// Field names accepted as tolerance keys.
+pub const FIELD_NAMES: [&str; 24] = [
+    "instrument_id",
+    "ts_event",
+    "rollover_flag",
+    "price",
+    "size",
+    "action",
+    "side",
+    "depth",
+    "flags",
+    "ts_recv",
+    "ts_in_delta",
+    "sequence",
+    "discriminator",
+    "bid_px",
+    "ask_px",
+    "bid_sz",
+    "ask_sz",
+    "bid_ct",
+    "ask_ct",
+    "open",
+    "high",
+    "low",
+    "close",
+    "volume",
+];
+
+/// Named field value of a record.
+pub type Field = (&'static str, i128);
+
+/// Gives a flat, named view of a record so records of different sources can be compared field by field.
+pub trait CompareFields {
+    /// Record family, records of different kinds never match.
+    fn kind(&self) -> &'static str;
+    fn instrument_id(&self) -> u32;
+    /// Fields taking part in the comparison in `FIELD_NAMES` order, only fields present on both
+    /// sides are compared.
+    fn fields(&self) -> Vec<(&'static str, i128)>;
+}
+
+fn level_fields(fields: &mut Vec<(&'static str, i128)>, level: &BidAskPair) {
+    fields.extend([
+        ("bid_px", level.bid_px as i128),
+        ("ask_px", level.ask_px as i128),
+        ("bid_sz", level.bid_sz as i128),
+        ("ask_sz", level.ask_sz as i128),
+        ("bid_ct", level.bid_ct as i128),
+        ("ask_ct", level.ask_ct as i128),
+    ]);
+}
+
+impl CompareFields for RecordEnum {
+    fn kind(&self) -> &'static str {
+        match self {
+            RecordEnum::Mbp1(_) | RecordEnum::Tbbo(_) => "mbp1",
+            RecordEnum::Trade(_) => "trade",
+            RecordEnum::Bbo(_) => "bbo",
+            RecordEnum::Ohlcv(_) => "ohlcv",
+        }
+    }
+
+    fn instrument_id(&self) -> u32 {
+        self.header().instrument_id
+    }
+
+    fn fields(&self) -> Vec<(&'static str, i128)> {
+        let hd = self.header();
+        let mut fields = vec![
+            ("instrument_id", hd.instrument_id as i128),
+            ("ts_event", hd.ts_event as i128),
+            ("rollover_flag", hd.rollover_flag as i128),
+        ];
+
+        match self {
+            RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) => {
+                fields.extend([
+                    ("price", msg.price as i128),
+                    ("size", msg.size as i128),
+                    ("action", msg.action as i128),
+                    ("side", msg.side as i128),
+                    ("depth", msg.depth as i128),
+                    ("flags", msg.flags as i128),
+                    ("ts_recv", msg.ts_recv as i128),
+                    ("ts_in_delta", msg.ts_in_delta as i128),
+                    ("sequence", msg.sequence as i128),
+                    ("discriminator", msg.discriminator as i128),
+                ]);
+                level_fields(&mut fields, &msg.levels[0]);
+            }
+            RecordEnum::Trade(msg) => {
+                fields.extend([
+                    ("price", msg.price as i128),
+                    ("size", msg.size as i128),
+                    ("action", msg.action as i128),
+                    ("side", msg.side as i128),
+                    ("depth", msg.depth as i128),
+                    ("flags", msg.flags as i128),
+                    ("ts_recv", msg.ts_recv as i128),
+                    ("ts_in_delta", msg.ts_in_delta as i128),
+                    ("sequence", msg.sequence as i128),
+                ]);
+            }
+            RecordEnum::Bbo(msg) => level_fields(&mut fields, &msg.levels[0]),
+            RecordEnum::Ohlcv(msg) => {
+                fields.extend([
+                    ("open", msg.open as i128),
+                    ("high", msg.high as i128),
+                    ("low", msg.low as i128),
+                    ("close", msg.close as i128),
+                    ("volume", msg.volume as i128),
+                ]);
+            }
+        }
+        fields
+    }
+}
+
+/// Absolute tolerance per field, fields without a tolerance must match exactly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tolerances(HashMap<String, u64>);
+
+impl Tolerances {
+    pub fn get(&self, field: &str) -> u64 {
+        self.0.get(field).copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TryFrom<&str> for Tolerances {
+    type Error = Error;
+
+    /// Parses `field=value` pairs separated by commas ex. ts_in_delta=100,price=0
+    fn try_from(value: &str) -> Result<Self> {
+        let mut tolerances = HashMap::new();
+
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (field, tolerance) = pair
+                .split_once('=')
+                .ok_or_else(|| error!(CustomError, "Invalid tolerance : {}", pair))?;
+            let field = field.trim();
+
+            if !FIELD_NAMES.contains(&field) {
+                return Err(error!(CustomError, "Invalid tolerance field : {}", field));
+            }
+            let tolerance = tolerance
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| error!(CustomError, "Invalid tolerance : {}", pair))?;
+
+            tolerances.insert(field.to_string(), tolerance);
+        }
+
+        Ok(Tolerances(tolerances))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl TryFrom<&str> for ReportFormat {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_uppercase().as_str() {
+            "TEXT" => Ok(Self::Text),
+            "JSON" => Ok(Self::Json),
+            _ => Err(error!(CustomError, "Invalid report format : {}", value)),
+        }
+    }
+}
+
+/// Where and how a comparison report is written and the tolerances applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompareOptions {
+    pub output: PathBuf,
+    pub format: ReportFormat,
+    pub tolerances: Tolerances,
+    /// Near matches and unmatched records kept per side, the rest are only counted.
+    pub max_examples: usize,
+}
+
+impl CompareOptions {
+    pub fn new(
+        output: &str,
+        format: &str,
+        tolerances: Option<&str>,
+        max_examples: usize,
+    ) -> Result<Self> {
+        Ok(CompareOptions {
+            output: PathBuf::from(output),
+            format: ReportFormat::try_from(format)?,
+            tolerances: Tolerances::try_from(tolerances.unwrap_or_default())?,
+            max_examples,
+        })
+    }
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        CompareOptions {
+            output: PathBuf::from("compare_results.txt"),
+            format: ReportFormat::Text,
+            tolerances: Tolerances::default(),
+            max_examples: DEFAULT_MAX_EXAMPLES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub left: i128,
+    pub right: i128,
+}
+
+/// Pairs up the fields present on both sides, both slices are in `FIELD_NAMES` order so one
+/// forward pass over each is enough.
+fn common_fields<'a>(
+    left: &'a [Field],
+    right: &'a [Field],
+) -> impl Iterator<Item = (&'static str, i128, i128)> + 'a {
+    let mut rest = right;
+    left.iter().filter_map(move |(field, left_value)| {
+        let pos = rest.iter().position(|(name, _)| name == field)?;
+        let right_value = rest[pos].1;
+        rest = &rest[pos + 1..];
+        Some((*field, *left_value, right_value))
+    })
+}
+
+/// Whether the fields of two records of the same kind are all within their tolerance.
+pub fn fields_match(left: &[Field], right: &[Field], tolerances: &Tolerances) -> bool {
+    if tolerances.is_empty() {
+        return left == right || common_fields(left, right).all(|(_, l, r)| l == r);
+    }
+    common_fields(left, right).all(|(field, l, r)| l.abs_diff(r) <= tolerances.get(field) as u128)
+}
+
+/// Fields of two records of the same kind that differ by more than their tolerance.
+pub fn diff_fields(left: &[Field], right: &[Field], tolerances: &Tolerances) -> Vec<FieldDiff> {
+    common_fields(left, right)
+        .filter(|(field, l, r)| l.abs_diff(*r) > tolerances.get(field) as u128)
+        .map(|(field, left, right)| FieldDiff {
+            field: field.to_string(),
+            left,
+            right,
+        })
+        .collect()
+}
+
+/// Record of a bucket with its resolved instrument name and fields.
+struct Keyed<T> {
+    record: T,
+    instrument: String,
+    fields: Vec<Field>,
+}
+
+impl<T: CompareFields> Keyed<T> {
+    fn new(record: T, name: impl Fn(u32) -> String) -> Self {
+        Keyed {
+            instrument: name(record.instrument_id()),
+            fields: record.fields(),
+            record,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InstrumentSummary {
+    pub matched: usize,
+    pub unmatched_left: usize,
+    pub unmatched_right: usize,
+}
+
+/// Unmatched pair from the same timestamp bucket with the fields that differ.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NearMatch {
+    pub ts: u64,
+    pub left_instrument: String,
+    pub right_instrument: String,
+    pub left: String,
+    pub right: String,
+    pub diffs: Vec<FieldDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnmatchedRecord {
+    pub ts: u64,
+    pub instrument: String,
+    pub record: String,
+}
+
+/// Near matches and unmatched records kept per side as examples, the rest are only counted.
+pub const DEFAULT_MAX_EXAMPLES: usize = 100;
+
+/// Result of comparing two files, left and right name the compared sources.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompareReport {
+    pub left: String,
+    pub right: String,
+    pub instruments: BTreeMap<String, InstrumentSummary>,
+    /// First `max_examples` near matches.
+    pub near_matches: Vec<NearMatch>,
+    /// First `max_examples` unmatched records of the left file, counts are in `instruments`.
+    pub unmatched_left: Vec<UnmatchedRecord>,
+    pub unmatched_right: Vec<UnmatchedRecord>,
+    #[serde(skip)]
+    pub max_examples: usize,
+}
+
+impl CompareReport {
+    pub fn new(left: &str, right: &str, max_examples: usize) -> Self {
+        CompareReport {
+            left: left.to_string(),
+            right: right.to_string(),
+            instruments: BTreeMap::new(),
+            near_matches: Vec::new(),
+            unmatched_left: Vec::new(),
+            unmatched_right: Vec::new(),
+            max_examples,
+        }
+    }
+
+    pub fn unmatched_left_count(&self) -> usize {
+        self.instruments.values().map(|s| s.unmatched_left).sum()
+    }
+
+    pub fn unmatched_right_count(&self) -> usize {
+        self.instruments.values().map(|s| s.unmatched_right).sum()
+    }
+
+    pub fn matched(&self) -> usize {
+        self.instruments.values().map(|s| s.matched).sum()
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.instruments
+            .values()
+            .all(|s| s.unmatched_left == 0 && s.unmatched_right == 0)
+    }
+
+    /// Matches the records of one timestamp bucket and records the outcome, either side may be empty.
+    /// Records only match when their instruments resolve to the same name.
+    pub fn add_bucket<L, R>(
+        &mut self,
+        ts: u64,
+        left: Vec<L>,
+        right: Vec<R>,
+        tolerances: &Tolerances,
+        left_name: impl Fn(u32) -> String,
+        right_name: impl Fn(u32) -> String,
+    ) where
+        L: CompareFields + Debug,
+        R: CompareFields + Debug,
+    {
+        // Instrument and fields are taken once per record, not once per compared pair
+        let mut left: Vec<Keyed<L>> = left
+            .into_iter()
+            .map(|l| Keyed::new(l, &left_name))
+            .collect();
+        let mut right: Vec<Keyed<R>> = right
+            .into_iter()
+            .map(|r| Keyed::new(r, &right_name))
+            .collect();
+
+        // Full matches within tolerance
+        let matched = match_bucket(&mut left, &mut right, |l, r| {
+            l.record.kind() == r.record.kind()
+                && l.instrument == r.instrument
+                && fields_match(&l.fields, &r.fields, tolerances)
+        });
+        for l in matched {
+            self.summary(l.instrument).matched += 1;
+        }
+
+        // Pair leftovers with the closest record of the same kind and instrument, only while
+        // near matches are still kept. Every lookup that finds candidates yields a near match, so
+        // pairing costs at most `max_examples` scans of a bucket.
+        let mut left: Vec<Option<Keyed<L>>> = left.into_iter().map(Some).collect();
+        let mut candidates: HashMap<(&'static str, String), Vec<usize>> = HashMap::new();
+        if self.near_matches.len() < self.max_examples {
+            for (idx, l) in left.iter().flatten().enumerate() {
+                candidates
+                    .entry((l.record.kind(), l.instrument.clone()))
+                    .or_default()
+                    .push(idx);
+            }
+        }
+
+        for r in right {
+            self.summary(r.instrument.clone()).unmatched_right += 1;
+
+            let closest = match candidates.get_mut(&(r.record.kind(), r.instrument.clone())) {
+                Some(idxs) if self.near_matches.len() < self.max_examples => idxs
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(pos, idx)| {
+                        let l = left[*idx].as_ref()?;
+                        Some((pos, diff_fields(&l.fields, &r.fields, tolerances)))
+                    })
+                    .min_by_key(|(_, diffs)| diffs.len())
+                    .and_then(|(pos, diffs)| Some((left[idxs.remove(pos)].take()?, diffs))),
+                _ => None,
+            };
+
+            match closest {
+                Some((l, diffs)) => {
+                    self.summary(l.instrument.clone()).unmatched_left += 1;
+                    self.near_matches.push(NearMatch {
+                        ts,
+                        left_instrument: l.instrument,
+                        right_instrument: r.instrument,
+                        left: format!("{:?}", l.record),
+                        right: format!("{:?}", r.record),
+                        diffs,
+                    });
+                }
+                None if self.unmatched_right.len() < self.max_examples => {
+                    self.unmatched_right.push(UnmatchedRecord {
+                        ts,
+                        instrument: r.instrument,
+                        record: format!("{:?}", r.record),
+                    })
+                }
+                None => {}
+            }
+        }
+
+        for l in left.into_iter().flatten() {
+            self.summary(l.instrument.clone()).unmatched_left += 1;
+            if self.unmatched_left.len() < self.max_examples {
+                self.unmatched_left.push(UnmatchedRecord {
+                    ts,
+                    instrument: l.instrument,
+                    record: format!("{:?}", l.record),
+                });
+            }
+        }
+    }
+
+    fn summary(&mut self, instrument: String) -> &mut InstrumentSummary {
+        self.instruments.entry(instrument).or_default()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "Compare {} against {}", self.left, self.right);
+        let _ = writeln!(
+            text,
+            "{:<20} {:>12} {:>16} {:>16}",
+            "Instrument", "Matched", "Unmatched Left", "Unmatched Right"
+        );
+        for (instrument, summary) in &self.instruments {
+            let _ = writeln!(
+                text,
+                "{:<20} {:>12} {:>16} {:>16}",
+                instrument, summary.matched, summary.unmatched_left, summary.unmatched_right
+            );
+        }
+
+        if !self.near_matches.is_empty() {
+            let _ = writeln!(text, "\nNear Matches:");
+            for near in &self.near_matches {
+                let diffs: Vec<String> = near
+                    .diffs
+                    .iter()
+                    .map(|d| format!("{} {} != {}", d.field, d.left, d.right))
+                    .collect();
+                let _ = writeln!(
+                    text,
+                    "ts {} {}/{}: {}",
+                    near.ts,
+                    near.left_instrument,
+                    near.right_instrument,
+                    diffs.join(", ")
+                );
+                let _ = writeln!(text, "  Left: {}", near.left);
+                let _ = writeln!(text, "  Right: {}", near.right);
+            }
+        }
+
+        for (name, records, count) in [
+            (
+                &self.left,
+                &self.unmatched_left,
+                self.unmatched_left_count(),
+            ),
+            (
+                &self.right,
+                &self.unmatched_right,
+                self.unmatched_right_count(),
+            ),
+        ] {
+            if !records.is_empty() {
+                let _ = writeln!(
+                    text,
+                    "\nUnmatched {} Records (first {} of {}):",
+                    name,
+                    records.len(),
+                    count
+                );
+                for record in records {
+                    let _ = writeln!(text, "{}", record.record);
+                }
+            }
+        }
+
+        text
+    }
+
+    pub fn write(&self, output: &PathBuf, format: ReportFormat) -> Result<()> {
+        let content = match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Json => serde_json::to_string_pretty(self)?,
+        };
+        std::fs::write(output, content)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mbinary::records::{OhlcvMsg, RecordHeader};
+
+    fn ohlcv(id: u32, ts: u64, close: i64) -> RecordEnum {
+        RecordEnum::Ohlcv(OhlcvMsg {
+            hd: RecordHeader::new::<OhlcvMsg>(id, ts, 0),
+            open: 100,
+            high: 200,
+            low: 50,
+            close,
+            volume: 1000,
+        })
+    }
+
+    #[test]
+    fn test_tolerances_parse() -> Result<()> {
+        // Test
+        let tolerances = Tolerances::try_from("ts_in_delta=100, price=5")?;
+
+        // Validate
+        assert_eq!(tolerances.get("ts_in_delta"), 100);
+        assert_eq!(tolerances.get("price"), 5);
+        assert_eq!(tolerances.get("size"), 0);
+        assert!(Tolerances::try_from("unknown=1").is_err());
+        assert!(Tolerances::try_from("price").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fields_match() -> Result<()> {
+        let left = ohlcv(1, 10, 150).fields();
+        let close = ohlcv(1, 10, 152).fields();
+        // Right side without the header fields the other source doesn't carry
+        let subset: Vec<Field> = left
+            .iter()
+            .filter(|(field, _)| *field != "instrument_id" && *field != "rollover_flag")
+            .copied()
+            .collect();
+
+        // Validate
+        assert!(fields_match(&left, &left, &Tolerances::default()));
+        assert!(fields_match(&left, &subset, &Tolerances::default()));
+        assert!(!fields_match(&left, &close, &Tolerances::default()));
+        assert!(fields_match(
+            &left,
+            &close,
+            &Tolerances::try_from("close=2")?
+        ));
+        assert_eq!(
+            diff_fields(&close, &subset, &Tolerances::default()),
+            vec![FieldDiff {
+                field: "close".to_string(),
+                left: 152,
+                right: 150
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_bucket() -> Result<()> {
+        let left = vec![ohlcv(1, 10, 150), ohlcv(1, 10, 160), ohlcv(2, 10, 150)];
+        let right = vec![ohlcv(1, 10, 152), ohlcv(1, 10, 170)];
+        let tolerances = Tolerances::try_from("close=5")?;
+
+        // Test
+        let mut report = CompareReport::new("file1", "file2", DEFAULT_MAX_EXAMPLES);
+        report.add_bucket(
+            10,
+            left,
+            right,
+            &tolerances,
+            |id| id.to_string(),
+            |id| id.to_string(),
+        );
+
+        // Validate
+        assert!(!report.is_match());
+        assert_eq!(report.matched(), 1);
+        assert_eq!(report.instruments["1"].unmatched_left, 1);
+        assert_eq!(report.instruments["1"].unmatched_right, 1);
+        assert_eq!(report.instruments["2"].unmatched_left, 1);
+        assert_eq!(report.near_matches.len(), 1);
+        assert_eq!(
+            report.near_matches[0].diffs,
+            vec![FieldDiff {
+                field: "close".to_string(),
+                left: 160,
+                right: 170
+            }]
+        );
+        assert_eq!(report.unmatched_left.len(), 1);
+        assert!(report.unmatched_right.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_bucket_instruments() {
+        // Test
+        let mut report = CompareReport::new("file1", "file2", DEFAULT_MAX_EXAMPLES);
+        report.add_bucket(
+            10,
+            vec![ohlcv(1, 10, 150)],
+            vec![ohlcv(1, 10, 150)],
+            &Tolerances::default(),
+            |_| "ZM.n.0".to_string(),
+            |_| "GC.n.0".to_string(),
+        );
+
+        // Validate
+        assert_eq!(report.matched(), 0);
+        assert_eq!(report.instruments["ZM.n.0"].unmatched_left, 1);
+        assert_eq!(report.instruments["GC.n.0"].unmatched_right, 1);
+        assert!(report.near_matches.is_empty());
+    }
+
+    #[test]
+    fn test_unmatched_examples_capped() {
+        let left: Vec<RecordEnum> = (0..5).map(|i| ohlcv(1, 10, 150 + i)).collect();
+
+        // Test
+        let mut report = CompareReport::new("file1", "file2", DEFAULT_MAX_EXAMPLES);
+        report.max_examples = 2;
+        report.add_bucket(
+            10,
+            left,
+            Vec::<RecordEnum>::new(),
+            &Tolerances::default(),
+            |id| id.to_string(),
+            |id| id.to_string(),
+        );
+
+        // Validate
+        assert_eq!(report.unmatched_left.len(), 2);
+        assert_eq!(report.unmatched_left_count(), 5);
+        assert_eq!(report.instruments["1"].unmatched_left, 5);
+        assert!(report.to_text().contains("(first 2 of 5)"));
+    }
+
+    #[test]
+    fn test_near_matches_capped() {
+        let left: Vec<RecordEnum> = (0..4).map(|i| ohlcv(1, 10, 150 + i)).collect();
+        let right: Vec<RecordEnum> = (0..4).map(|i| ohlcv(1, 10, 250 + i)).collect();
+
+        // Test
+        let mut report = CompareReport::new("file1", "file2", 2);
+        report.add_bucket(
+            10,
+            left,
+            right,
+            &Tolerances::default(),
+            |id| id.to_string(),
+            |id| id.to_string(),
+        );
+
+        // Validate
+        assert_eq!(report.near_matches.len(), 2);
+        assert_eq!(report.unmatched_left.len(), 2);
+        assert_eq!(report.unmatched_right.len(), 2);
+        assert_eq!(report.unmatched_left_count(), 4);
+        assert_eq!(report.unmatched_right_count(), 4);
+    }
+
+    #[test]
+    fn test_write_json() -> Result<()> {
+        let output = PathBuf::from("tests/data/test_report.json");
+        let mut report = CompareReport::new("file1", "file2", DEFAULT_MAX_EXAMPLES);
+        report.add_bucket(
+            10,
+            vec![ohlcv(1, 10, 150)],
+            Vec::<RecordEnum>::new(),
+            &Tolerances::default(),
+            |id| id.to_string(),
+            |id| id.to_string(),
+        );
+
+        // Test
+        report.write(&output, ReportFormat::Json)?;
+
+        // Validate
+        let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&output)?)?;
+        assert_eq!(value["instruments"]["1"]["unmatched_left"], 1);
+        assert_eq!(value["unmatched_left"][0]["ts"], 10);
+
+        // Cleanup
+        std::fs::remove_file(&output)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,583 @@
+use crate::dates::parse_tz;
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::{
+    file_component, is_zstd, mbinary_stem, read_mbinary_file, WriterPool, MAX_OPEN_WRITERS,
+};
+use chrono::{Duration, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+use mbinary::enums::Schema;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::{OhlcvMsg, Record, RecordHeader};
+use mbinary::symbols::SymbolMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const NANOS_PER_HOUR: u64 = 3_600_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Interval {
+    pub fn nanos(&self) -> u64 {
+        match self {
+            Interval::Second => 1_000_000_000,
+            Interval::Minute => 60_000_000_000,
+            Interval::Hour => NANOS_PER_HOUR,
+            Interval::Day => 24 * NANOS_PER_HOUR,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        match self {
+            Interval::Second => Schema::Ohlcv1S,
+            Interval::Minute => Schema::Ohlcv1M,
+            Interval::Hour => Schema::Ohlcv1H,
+            Interval::Day => Schema::Ohlcv1D,
+        }
+    }
+}
+
+impl TryFrom<&str> for Interval {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_uppercase().as_str() {
+            "1S" => Ok(Self::Second),
+            "1M" => Ok(Self::Minute),
+            "1H" => Ok(Self::Hour),
+            "1D" => Ok(Self::Day),
+            _ => Err(error!(CustomError, "Invalid interval : {}", value)),
+        }
+    }
+}
+
+/// What to emit for intervals without trades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyIntervals {
+    /// No bar, matching vendor OHLCV data.
+    Skip,
+    /// Flat bar at the previous close with zero volume.
+    Fill,
+}
+
+impl TryFrom<&str> for EmptyIntervals {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_uppercase().as_str() {
+            "SKIP" => Ok(Self::Skip),
+            "FILL" => Ok(Self::Fill),
+            _ => Err(error!(
+                CustomError,
+                "Invalid empty interval handling : {}", value
+            )),
+        }
+    }
+}
+
+/// Daily session open that bars are aligned to, daily bars span one session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+    pub start: NaiveTime,
+    pub tz: Tz,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            start: NaiveTime::MIN,
+            tz: chrono_tz::UTC,
+        }
+    }
+}
+
+impl Session {
+    /// Session opening at `start` (HH:MM) local time in the `tz` timezone ex. 17:00 America/Chicago
+    pub fn new(start: &str, tz: &str) -> Result<Self> {
+        let start = NaiveTime::parse_from_str(start, "%H:%M").map_err(|_| {
+            error!(
+                InvalidDateFormat,
+                "Invalid session start '{}'. Expected format: HH:MM", start
+            )
+        })?;
+        Ok(Session {
+            start,
+            tz: parse_tz(tz)?,
+        })
+    }
+
+    /// Latest session open at or before `ts`, opens falling in a daylight saving gap move past the gap.
+    pub fn open(&self, ts: u64) -> u64 {
+        let local = self.tz.timestamp_nanos(ts as i64).naive_local();
+
+        for days in 0..3 {
+            let naive = (local.date() - Duration::days(days)).and_time(self.start);
+            let open = self
+                .tz
+                .from_local_datetime(&naive)
+                .earliest()
+                .or_else(|| {
+                    self.tz
+                        .from_local_datetime(&(naive + Duration::hours(1)))
+                        .earliest()
+                })
+                .and_then(|open| open.timestamp_nanos_opt());
+
+            if let Some(open) = open {
+                if open as u64 <= ts {
+                    return open as u64;
+                }
+            }
+        }
+
+        ts - ts % Interval::Day.nanos()
+    }
+
+    /// Start of the interval containing `ts`.
+    pub fn bucket(&self, ts: u64, interval: Interval) -> u64 {
+        Self::bucket_from(self.open(ts), ts, interval)
+    }
+
+    /// Start of the interval containing `ts` in the session opening at `open`.
+    fn bucket_from(open: u64, ts: u64, interval: Interval) -> u64 {
+        match interval {
+            Interval::Day => open,
+            _ => open + (ts - open) / interval.nanos() * interval.nanos(),
+        }
+    }
+
+    /// Start of the interval following the one starting at `bucket`.
+    pub fn next_bucket(&self, bucket: u64, interval: Interval) -> u64 {
+        match interval {
+            // Sessions last 23 to 25 hours around daylight saving changes
+            Interval::Day => self.open(bucket + 26 * NANOS_PER_HOUR),
+            _ => self.bucket(bucket + interval.nanos(), interval),
+        }
+    }
+}
+
+/// Bounds of the session holding the latest trade, the timezone is only converted when a trade
+/// falls outside of it.
+#[derive(Debug, Default)]
+struct SessionBounds {
+    open: u64,
+    next_open: u64,
+}
+
+impl SessionBounds {
+    fn bucket(&mut self, session: &Session, ts: u64, interval: Interval) -> u64 {
+        if ts < self.open || ts >= self.next_open {
+            self.open = session.open(ts);
+            self.next_open = session.next_bucket(self.open, Interval::Day);
+        }
+        Session::bucket_from(self.open, ts, interval)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResampleOptions {
+    pub interval: Interval,
+    pub session: Session,
+    pub empty: EmptyIntervals,
+}
+
+/// Trade price, size and event time, None for book updates that are not trades.
+fn trade(record: &RecordEnum) -> Option<(i64, u64, u64)> {
+    match record {
+        RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) if msg.action as u8 == b'T' => {
+            Some((msg.price, msg.size as u64, msg.hd.ts_event))
+        }
+        RecordEnum::Trade(msg) => Some((msg.price, msg.size as u64, msg.hd.ts_event)),
+        _ => None,
+    }
+}
+
+struct InstrumentBars {
+    path: PathBuf,
+    bar: Option<OhlcvMsg>,
+}
+
+impl InstrumentBars {
+    fn write(&self, writers: &mut WriterPool, bar: &OhlcvMsg) -> Result<()> {
+        writers.encode_record(&self.path, &bar.into())
+    }
+}
+
+/// Builds OHLCV bars from the trades in an MBP-1, TBBO or trades file, writing one file per instrument.
+/// Bars are stamped with their interval start. Trades timestamped before their instrument's open bar
+/// arrive after that bar's interval was written, so they are dropped and counted. At most
+/// `MAX_OPEN_WRITERS` files are open at once. Returns the files written.
+pub async fn resample_mbinary(
+    filepath: &Path,
+    output_dir: &Path,
+    options: &ResampleOptions,
+) -> Result<Vec<PathBuf>> {
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+
+    if !matches!(
+        metadata.schema,
+        Schema::Mbp1 | Schema::Tbbo | Schema::Trades
+    ) {
+        return Err(error!(
+            CustomError,
+            "Cannot resample {} data, expected mbp-1, tbbo or trades.", metadata.schema
+        ));
+    }
+
+    let stem = mbinary_stem(filepath);
+    // Parts are compressed like their source
+    let extension = if is_zstd(filepath) { "bin.zst" } else { "bin" };
+    let schema = options.interval.schema();
+
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut writers = WriterPool::new(MAX_OPEN_WRITERS);
+    let mut instruments: HashMap<u32, InstrumentBars> = HashMap::new();
+    let mut bounds = SessionBounds::default();
+    let mut dropped = 0;
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+        let (price, size, ts_event) = match trade(&record) {
+            Some(trade) => trade,
+            None => continue,
+        };
+        let hd = record.header();
+        let bucket = bounds.bucket(&options.session, ts_event, options.interval);
+
+        let bars = match instruments.get_mut(&hd.instrument_id) {
+            Some(bars) => bars,
+            None => {
+                let ticker = metadata.mappings.get_instrument_ticker(hd.instrument_id);
+                let mut part = metadata.clone();
+                part.schema = schema;
+                part.mappings = SymbolMap::new();
+                if let Some(ticker) = &ticker {
+                    part.mappings.add_instrument(ticker, hd.instrument_id);
+                }
+
+                let name = ticker.unwrap_or_else(|| hd.instrument_id.to_string());
+                let mut path = output_dir.join(format!(
+                    "{}_{}_{}.{}",
+                    stem,
+                    file_component(&name),
+                    schema,
+                    extension
+                ));
+                // Tickers that only differ in replaced characters keep their own file
+                if instruments.values().any(|bars| bars.path == path) {
+                    path = output_dir.join(format!(
+                        "{}_{}_{}_{}.{}",
+                        stem,
+                        file_component(&name),
+                        hd.instrument_id,
+                        schema,
+                        extension
+                    ));
+                }
+                writers.create(&part, &path)?;
+                instruments
+                    .entry(hd.instrument_id)
+                    .or_insert(InstrumentBars { path, bar: None })
+            }
+        };
+
+        match bars.bar.as_mut() {
+            Some(bar) if bucket < bar.hd.ts_event => dropped += 1,
+            Some(bar) if bucket == bar.hd.ts_event => {
+                bar.high = bar.high.max(price);
+                bar.low = bar.low.min(price);
+                bar.close = price;
+                bar.volume += size;
+            }
+            _ => {
+                if let Some(bar) = bars.bar.take() {
+                    bars.write(&mut writers, &bar)?;
+
+                    if options.empty == EmptyIntervals::Fill {
+                        let mut next = options
+                            .session
+                            .next_bucket(bar.hd.ts_event, options.interval);
+                        while next < bucket {
+                            bars.write(
+                                &mut writers,
+                                &OhlcvMsg {
+                                    hd: RecordHeader::new::<OhlcvMsg>(hd.instrument_id, next, 0),
+                                    open: bar.close,
+                                    high: bar.close,
+                                    low: bar.close,
+                                    close: bar.close,
+                                    volume: 0,
+                                },
+                            )?;
+                            next = options.session.next_bucket(next, options.interval);
+                        }
+                    }
+                }
+
+                bars.bar = Some(OhlcvMsg {
+                    hd: RecordHeader::new::<OhlcvMsg>(hd.instrument_id, bucket, hd.rollover_flag),
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                    volume: size,
+                });
+            }
+        }
+    }
+
+    let mut files = Vec::with_capacity(instruments.len());
+    for (_, mut bars) in instruments {
+        if let Some(bar) = bars.bar.take() {
+            bars.write(&mut writers, &bar)?;
+        }
+        files.push(bars.path);
+    }
+    writers.finish()?;
+    files.sort();
+
+    if dropped > 0 {
+        println!(
+            "Dropped {} trades timestamped before their instrument's open bar.",
+            dropped
+        );
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mbinary::decode::AsyncDecoder;
+    use mbinary::encode::CombinedEncoder;
+    use mbinary::enums::Dataset;
+    use mbinary::metadata::Metadata;
+    use mbinary::record_ref::RecordRef;
+    use mbinary::records::TradeMsg;
+    use serial_test::serial;
+    use std::path::Path;
+
+    // 2024-08-20 00:00:00 UTC
+    const DAY: u64 = 1724112000000000000;
+    const MINUTE: u64 = 60_000_000_000;
+
+    fn trade_msg(id: u32, ts: u64, price: i64, size: u32) -> TradeMsg {
+        TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(id, ts, 0),
+            price,
+            size,
+            action: 84,
+            side: 66,
+            depth: 0,
+            flags: 0,
+            ts_recv: ts,
+            ts_in_delta: 0,
+            sequence: 0,
+        }
+    }
+
+    fn create_test_file(path: &Path, tickers: [&str; 2]) -> Result<()> {
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument(tickers[0], 1);
+        symbol_map.add_instrument(tickers[1], 2);
+        let metadata = Metadata::new(
+            Schema::Trades,
+            Dataset::Futures,
+            DAY,
+            DAY + Interval::Day.nanos(),
+            symbol_map,
+        );
+
+        let records = [
+            trade_msg(1, DAY + 1_000, 100, 1),
+            trade_msg(2, DAY + 2_000, 500, 4),
+            trade_msg(1, DAY + 30_000_000_000, 120, 2),
+            trade_msg(1, DAY + 45_000_000_000, 90, 3),
+            trade_msg(1, DAY + 3 * MINUTE + 5_000, 110, 1),
+            // Late trade of the first bar, dropped
+            trade_msg(1, DAY + 50_000_000_000, 70, 5),
+        ];
+        let refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = CombinedEncoder::new(&mut buffer);
+        encoder.encode(&metadata, &refs)?;
+        encoder.write_to_file(path, false)?;
+
+        Ok(())
+    }
+
+    async fn read_bars(path: &Path) -> Result<(Metadata, Vec<OhlcvMsg>)> {
+        let mut decoder =
+            AsyncDecoder::<tokio::io::BufReader<tokio::fs::File>>::from_file(path).await?;
+        let metadata = decoder.metadata().unwrap();
+        let bars = decoder
+            .decode()
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                RecordEnum::Ohlcv(msg) => Some(msg),
+                _ => None,
+            })
+            .collect();
+        Ok((metadata, bars))
+    }
+
+    #[test]
+    fn test_session_bucket() -> Result<()> {
+        // 2024-08-20 17:00 America/Chicago is 22:00 UTC
+        let session = Session::new("17:00", "America/Chicago")?;
+        let open = DAY + 22 * NANOS_PER_HOUR;
+
+        // Test
+        let before = session.bucket(open - 1, Interval::Day);
+        let after = session.bucket(open + 90 * MINUTE, Interval::Hour);
+
+        // Validate
+        assert_eq!(before, open - Interval::Day.nanos());
+        assert_eq!(after, open + NANOS_PER_HOUR);
+        assert_eq!(session.next_bucket(before, Interval::Day), open);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_bounds() -> Result<()> {
+        // Spans the 2024-11-03 daylight saving change in Chicago
+        let session = Session::new("17:00", "America/Chicago")?;
+        let start = 1730419200000000000; // 2024-11-01 00:00:00 UTC
+        let mut bounds = SessionBounds::default();
+
+        // Test
+        for ts in (0..4 * 24 * 4).map(|i| start + i * 15 * MINUTE) {
+            // Validate
+            assert_eq!(
+                bounds.bucket(&session, ts, Interval::Hour),
+                session.bucket(ts, Interval::Hour)
+            );
+            assert_eq!(
+                bounds.bucket(&session, ts, Interval::Day),
+                session.bucket(ts, Interval::Day)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_resample_skip() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_resample.bin");
+        let dir = PathBuf::from("tests/data/test_resample_skip");
+        create_test_file(&path, ["HE.n.0", "ZC.n.0"])?;
+        let options = ResampleOptions {
+            interval: Interval::Minute,
+            session: Session::default(),
+            empty: EmptyIntervals::Skip,
+        };
+
+        // Test
+        let files = resample_mbinary(&path, &dir, &options).await?;
+
+        // Validate
+        assert_eq!(
+            files,
+            vec![
+                dir.join("test_resample_HE.n.0_ohlcv-1m.bin"),
+                dir.join("test_resample_ZC.n.0_ohlcv-1m.bin")
+            ]
+        );
+        let (metadata, bars) = read_bars(&files[0]).await?;
+        assert_eq!(metadata.schema, Schema::Ohlcv1M);
+        assert_eq!(metadata.mappings.map.len(), 1);
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].hd.ts_event, DAY);
+        assert_eq!(
+            (bars[0].open, bars[0].high, bars[0].low, bars[0].close),
+            (100, 120, 90, 90)
+        );
+        assert_eq!(bars[0].volume, 6);
+        assert_eq!(bars[1].hd.ts_event, DAY + 3 * MINUTE);
+        assert_eq!((bars[1].low, bars[1].close, bars[1].volume), (110, 110, 1));
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_resample_sanitizes_tickers() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_resample.bin");
+        let dir = PathBuf::from("tests/data/test_resample_sanitize");
+        create_test_file(&path, ["ES/M4", "ES:M4"])?;
+        let options = ResampleOptions {
+            interval: Interval::Minute,
+            session: Session::default(),
+            empty: EmptyIntervals::Skip,
+        };
+
+        // Test
+        let files = resample_mbinary(&path, &dir, &options).await?;
+
+        // Validate
+        assert_eq!(
+            files,
+            vec![
+                dir.join("test_resample_ES_M4_2_ohlcv-1m.bin"),
+                dir.join("test_resample_ES_M4_ohlcv-1m.bin")
+            ]
+        );
+        assert_eq!(read_bars(&files[0]).await?.1.len(), 1);
+        assert_eq!(read_bars(&files[1]).await?.1.len(), 2);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_resample_fill() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_resample.bin");
+        let dir = PathBuf::from("tests/data/test_resample_fill");
+        create_test_file(&path, ["HE.n.0", "ZC.n.0"])?;
+        let options = ResampleOptions {
+            interval: Interval::Minute,
+            session: Session::default(),
+            empty: EmptyIntervals::Fill,
+        };
+
+        // Test
+        let files = resample_mbinary(&path, &dir, &options).await?;
+
+        // Validate
+        let (_, bars) = read_bars(&files[0]).await?;
+        assert_eq!(bars.len(), 4);
+        assert_eq!(bars[1].hd.ts_event, DAY + MINUTE);
+        assert_eq!(bars[2].hd.ts_event, DAY + 2 * MINUTE);
+        assert_eq!((bars[1].open, bars[1].close, bars[1].volume), (90, 90, 0));
+        assert_eq!(bars[3].close, 110);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::utils::in_window;
+use crate::vendors::midas::load::{mbinary_writer, read_mbinary_file};
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::Record;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Which records a sample keeps, shared by the mbinary and dbn samplers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleMode {
+    /// Records within this many minutes of each instrument's first record.
+    FirstMinutes(u64),
+    /// Every k-th record of each instrument, starting with the first.
+    EveryNth(usize),
+    /// Records with start <= ts < end, missing bounds match everything.
+    Window {
+        start: Option<u64>,
+        end: Option<u64>,
+    },
+}
+
+impl SampleMode {
+    /// Builds the mode from command options, exactly one of minutes, every or a window bound must be set.
+    pub fn new(
+        minutes: Option<u64>,
+        every: Option<usize>,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Result<Self> {
+        let window = start.is_some() || end.is_some();
+
+        match (minutes, every, window) {
+            (Some(0), None, false) => Err(error!(CustomError, "Minutes must be at least 1.")),
+            (Some(minutes), None, false) => Ok(SampleMode::FirstMinutes(minutes)),
+            (None, Some(0), false) => Err(error!(CustomError, "Every must be at least 1.")),
+            (None, Some(every), false) => Ok(SampleMode::EveryNth(every)),
+            (None, None, true) => Ok(SampleMode::Window { start, end }),
+            _ => Err(error!(
+                CustomError,
+                "Sample by one of minutes, every or a start/end window."
+            )),
+        }
+    }
+}
+
+/// Decides record by record whether a record belongs in the sample.
+#[derive(Debug)]
+pub struct Sampler {
+    mode: SampleMode,
+    /// First timestamp or records seen per instrument, depending on the mode.
+    seen: HashMap<u32, u64>,
+}
+
+impl Sampler {
+    pub fn new(mode: SampleMode) -> Self {
+        Sampler {
+            mode,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn keep(&mut self, instrument_id: u32, ts: u64) -> bool {
+        match self.mode {
+            SampleMode::FirstMinutes(minutes) => {
+                let first = *self.seen.entry(instrument_id).or_insert(ts);
+                ts < first + minutes * 60_000_000_000
+            }
+            SampleMode::EveryNth(every) => {
+                let count = self.seen.entry(instrument_id).or_insert(0);
+                *count += 1;
+                (*count - 1).is_multiple_of(every as u64)
+            }
+            SampleMode::Window { start, end } => in_window(ts, start, end),
+        }
+    }
+
+    /// Metadata range of the sample, only a window narrows the source range.
+    pub fn range(&self, start: u64, end: u64) -> (u64, u64) {
+        match self.mode {
+            SampleMode::Window {
+                start: from,
+                end: to,
+            } => (from.unwrap_or(start), to.unwrap_or(end)),
+            _ => (start, end),
+        }
+    }
+}
+
+/// Writes a small subset of a file, metadata and symbol map are kept so the sample works
+/// with the same commands as the source. Returns the number of records written.
+pub async fn sample_mbinary(filepath: &PathBuf, output: &PathBuf, mode: SampleMode) -> Result<u64> {
+    if filepath == output {
+        return Err(error!(
+            CustomError,
+            "Output must differ from the input file."
+        ));
+    }
+
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let mut metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+
+    let mut sampler = Sampler::new(mode);
+    (metadata.start, metadata.end) = sampler.range(metadata.start, metadata.end);
+
+    let mut encoder = mbinary_writer(&metadata, output)?;
+    let mut count = 0;
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+
+        if sampler.keep(record.header().instrument_id, record.timestamp()) {
+            encoder.encode_record(&record.to_record_ref())?;
+            count += 1;
+        }
+    }
+    encoder.finish()?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::generate::{generate_mbinary, GenerateOptions};
+    use mbinary::decode::AsyncDecoder;
+    use mbinary::enums::Schema;
+    use serial_test::serial;
+
+    #[test]
+    fn test_sample_mode() -> Result<()> {
+        assert_eq!(
+            SampleMode::new(Some(5), None, None, None)?,
+            SampleMode::FirstMinutes(5)
+        );
+        assert_eq!(
+            SampleMode::new(None, None, None, Some(10))?,
+            SampleMode::Window {
+                start: None,
+                end: Some(10)
+            }
+        );
+        assert!(SampleMode::new(Some(5), Some(2), None, None).is_err());
+        assert!(SampleMode::new(None, None, None, None).is_err());
+        assert!(SampleMode::new(None, Some(0), None, None).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_sample_mbinary() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_sample_input.bin");
+        let output = PathBuf::from("tests/data/test_sample_output.bin");
+        let options = GenerateOptions {
+            schema: Schema::Ohlcv1M,
+            tickers: vec!["HE.n.0".to_string(), "ZC.n.0".to_string()],
+            records: 120,
+            ..Default::default()
+        };
+        generate_mbinary(&options, &path).await?;
+
+        // Test
+        let first = sample_mbinary(&path, &output, SampleMode::FirstMinutes(10)).await?;
+        let every = sample_mbinary(&path, &output, SampleMode::EveryNth(4)).await?;
+
+        let window = SampleMode::Window {
+            start: Some(options.start + 60_000_000_000),
+            end: Some(options.start + 180_000_000_000),
+        };
+        let windowed = sample_mbinary(&path, &output, window).await?;
+
+        // Validate
+        assert_eq!(first, 20);
+        assert_eq!(every, 30);
+        assert_eq!(windowed, 4);
+
+        let mut decoder =
+            AsyncDecoder::<tokio::io::BufReader<tokio::fs::File>>::from_file(&output).await?;
+        let metadata = decoder.metadata().unwrap();
+        assert_eq!(metadata.mappings.map.len(), 2);
+        assert_eq!(metadata.start, options.start + 60_000_000_000);
+        assert_eq!(decoder.decode().await?.len(), 4);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(&output)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::utils::in_window;
+use crate::vendors::midas::load::{mbinary_writer, read_mbinary_file};
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::Record;
+use mbinary::symbols::SymbolMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Record filter for slicing, empty instrument lists and missing bounds match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SliceFilter {
+    pub instrument_ids: Vec<u32>,
+    pub tickers: Vec<String>,
+    /// Inclusive start in unix nanos.
+    pub start: Option<u64>,
+    /// Exclusive end in unix nanos.
+    pub end: Option<u64>,
+}
+
+impl SliceFilter {
+    fn in_window(&self, ts: u64) -> bool {
+        in_window(ts, self.start, self.end)
+    }
+}
+
+/// Resolves instrument ids and tickers against the file symbol map, None if no instruments were given.
+pub fn resolve_instrument_ids(
+    mappings: &SymbolMap,
+    instrument_ids: &[u32],
+    tickers: &[String],
+) -> Result<Option<HashSet<u32>>> {
+    if instrument_ids.is_empty() && tickers.is_empty() {
+        return Ok(None);
+    }
+
+    let mut ids: HashSet<u32> = instrument_ids.iter().copied().collect();
+
+    for ticker in tickers {
+        let id = mappings
+            .map
+            .iter()
+            .find(|(_, t)| *t == ticker)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| error!(CustomError, "Ticker {} not in file symbol map.", ticker))?;
+        ids.insert(id);
+    }
+
+    Ok(Some(ids))
+}
+
+/// Streams the records matching the filter into a new file, returns the number of records written.
+pub async fn slice_mbinary(filepath: &Path, output: &Path, filter: &SliceFilter) -> Result<usize> {
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let mut metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+
+    let ids = resolve_instrument_ids(&metadata.mappings, &filter.instrument_ids, &filter.tickers)?;
+
+    // Narrow metadata to the slice
+    if let Some(ids) = &ids {
+        metadata.mappings.map.retain(|id, _| ids.contains(id));
+    }
+    metadata.start = filter.start.unwrap_or(metadata.start);
+    metadata.end = filter.end.unwrap_or(metadata.end);
+
+    let mut encoder = mbinary_writer(&metadata, output)?;
+    let mut count = 0;
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+
+        if let Some(ids) = &ids {
+            if !ids.contains(&record.header().instrument_id) {
+                continue;
+            }
+        }
+
+        if filter.in_window(record.timestamp()) {
+            encoder.encode_record(&record.to_record_ref())?;
+            count += 1;
+        }
+    }
+    encoder.finish()?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mbinary::decode::AsyncDecoder;
+    use mbinary::encode::CombinedEncoder;
+    use mbinary::enums::{Dataset, Schema};
+    use mbinary::metadata::Metadata;
+    use mbinary::record_ref::RecordRef;
+    use mbinary::records::{OhlcvMsg, RecordHeader};
+    use serial_test::serial;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    fn create_test_file(path: &Path) -> Result<()> {
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument("HE.n.0", 1);
+        symbol_map.add_instrument("ZC.n.0", 2);
+        let metadata = Metadata::new(Schema::Ohlcv1M, Dataset::Futures, 0, 400, symbol_map);
+
+        let mut records = Vec::new();
+        for ts in (0..400).step_by(100) {
+            for id in [1, 2] {
+                records.push(OhlcvMsg {
+                    hd: RecordHeader::new::<OhlcvMsg>(id, ts, 0),
+                    open: 100,
+                    high: 200,
+                    low: 50,
+                    close: 150,
+                    volume: 1000,
+                });
+            }
+        }
+        let refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = CombinedEncoder::new(&mut buffer);
+        encoder.encode(&metadata, &refs)?;
+        encoder.write_to_file(path, false)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_slice_mbinary() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_slice_input.bin");
+        let output = PathBuf::from("tests/data/test_slice_output.bin");
+        create_test_file(&path)?;
+
+        let filter = SliceFilter {
+            instrument_ids: vec![],
+            tickers: vec!["ZC.n.0".to_string()],
+            start: Some(100),
+            end: Some(300),
+        };
+
+        // Test
+        let count = slice_mbinary(&path, &output, &filter).await?;
+
+        // Validate
+        let mut decoder =
+            AsyncDecoder::<tokio::io::BufReader<tokio::fs::File>>::from_file(&output).await?;
+        let metadata = decoder.metadata().unwrap();
+        let records = decoder.decode().await?;
+
+        assert_eq!(count, 2);
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.header().instrument_id == 2));
+        assert_eq!(metadata.start, 100);
+        assert_eq!(metadata.end, 300);
+        assert_eq!(metadata.mappings.map.len(), 1);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(&output)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_instrument_ids_error() {
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument("HE.n.0", 1);
+
+        // Test
+        let result = resolve_instrument_ids(&symbol_map, &[], &["ZC.n.0".to_string()]);
+
+        // Validate
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use crate::dates::NANOS_PER_DAY;
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::{
+    file_component, is_zstd, mbinary_stem, read_mbinary_file, WriterPool, MAX_OPEN_WRITERS,
+};
+use chrono::{TimeZone, Utc};
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::Record;
+use mbinary::symbols::SymbolMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    Instrument,
+    Day,
+}
+
+impl TryFrom<&str> for SplitBy {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_uppercase().as_str() {
+            "INSTRUMENT" => Ok(Self::Instrument),
+            "DAY" => Ok(Self::Day),
+            _ => Err(error!(CustomError, "Invalid split key : {}", value)),
+        }
+    }
+}
+
+/// Splits a file into one file per instrument or per UTC day, returns the files written. At most
+/// `MAX_OPEN_WRITERS` parts are open at once.
+pub async fn split_mbinary(
+    filepath: &Path,
+    output_dir: &Path,
+    by: SplitBy,
+) -> Result<Vec<PathBuf>> {
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+    let stem = mbinary_stem(filepath);
+    // Parts are compressed like their source
+    let extension = if is_zstd(filepath) { "bin.zst" } else { "bin" };
+
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut writers = WriterPool::new(MAX_OPEN_WRITERS);
+    let mut paths: HashMap<String, PathBuf> = HashMap::new();
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+        let instrument_id = record.header().instrument_id;
+        let ts = record.timestamp();
+
+        let key = match by {
+            SplitBy::Instrument => metadata
+                .mappings
+                .get_instrument_ticker(instrument_id)
+                .unwrap_or_else(|| instrument_id.to_string()),
+            SplitBy::Day => Utc
+                .timestamp_nanos(ts as i64)
+                .date_naive()
+                .format("%Y-%m-%d")
+                .to_string(),
+        };
+
+        if !paths.contains_key(&key) {
+            let mut part = metadata.clone();
+
+            match by {
+                SplitBy::Instrument => {
+                    let mut mappings = SymbolMap::new();
+                    if let Some(ticker) = metadata.mappings.get_instrument_ticker(instrument_id) {
+                        mappings.add_instrument(&ticker, instrument_id);
+                    }
+                    part.mappings = mappings;
+                }
+                SplitBy::Day => {
+                    part.start = ts - ts % NANOS_PER_DAY;
+                    part.end = part.start + NANOS_PER_DAY;
+                }
+            }
+
+            let mut path =
+                output_dir.join(format!("{}_{}.{}", stem, file_component(&key), extension));
+            // Tickers that only differ in replaced characters keep their own file
+            if paths.values().any(|used| *used == path) {
+                path = output_dir.join(format!(
+                    "{}_{}_{}.{}",
+                    stem,
+                    file_component(&key),
+                    instrument_id,
+                    extension
+                ));
+            }
+            writers.create(&part, &path)?;
+            paths.insert(key.clone(), path);
+        }
+
+        if let Some(path) = paths.get(&key) {
+            writers.encode_record(path, &record.to_record_ref())?;
+        }
+    }
+    writers.finish()?;
+
+    let mut files: Vec<PathBuf> = paths.into_values().collect();
+    files.sort();
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mbinary::decode::AsyncDecoder;
+    use mbinary::encode::CombinedEncoder;
+    use mbinary::enums::{Dataset, Schema};
+    use mbinary::metadata::Metadata;
+    use mbinary::record_ref::RecordRef;
+    use mbinary::records::{OhlcvMsg, RecordHeader};
+    use serial_test::serial;
+    use std::path::Path;
+
+    // 2024-08-20 00:00:00 UTC
+    const DAY: u64 = 1724112000000000000;
+
+    fn create_test_file(path: &Path, tickers: [&str; 2]) -> Result<()> {
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument(tickers[0], 1);
+        symbol_map.add_instrument(tickers[1], 2);
+        let metadata = Metadata::new(
+            Schema::Ohlcv1H,
+            Dataset::Futures,
+            DAY,
+            DAY + 2 * NANOS_PER_DAY,
+            symbol_map,
+        );
+
+        let mut records = Vec::new();
+        for ts in [DAY, DAY + 3_600_000_000_000, DAY + NANOS_PER_DAY] {
+            for id in [1, 2] {
+                records.push(OhlcvMsg {
+                    hd: RecordHeader::new::<OhlcvMsg>(id, ts, 0),
+                    open: 100,
+                    high: 200,
+                    low: 50,
+                    close: 150,
+                    volume: 1000,
+                });
+            }
+        }
+        let refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = CombinedEncoder::new(&mut buffer);
+        encoder.encode(&metadata, &refs)?;
+        encoder.write_to_file(path, false)?;
+
+        Ok(())
+    }
+
+    async fn read_records(path: &Path) -> Result<(Metadata, Vec<RecordEnum>)> {
+        let mut decoder =
+            AsyncDecoder::<tokio::io::BufReader<tokio::fs::File>>::from_file(path).await?;
+        let metadata = decoder.metadata().unwrap();
+        let records = decoder.decode().await?;
+        Ok((metadata, records))
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_split_by_instrument() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_split.bin");
+        let dir = PathBuf::from("tests/data/test_split_instrument");
+        create_test_file(&path, ["HE.n.0", "ZC.n.0"])?;
+
+        // Test
+        let files = split_mbinary(&path, &dir, SplitBy::Instrument).await?;
+
+        // Validate
+        assert_eq!(
+            files,
+            vec![
+                dir.join("test_split_HE.n.0.bin"),
+                dir.join("test_split_ZC.n.0.bin")
+            ]
+        );
+        let (metadata, records) = read_records(&files[1]).await?;
+        assert_eq!(records.len(), 3);
+        assert!(records.iter().all(|r| r.header().instrument_id == 2));
+        assert_eq!(
+            metadata.mappings.get_instrument_ticker(2).unwrap(),
+            "ZC.n.0"
+        );
+        assert_eq!(metadata.mappings.map.len(), 1);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_split_by_instrument_sanitizes_tickers() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_split.bin");
+        let dir = PathBuf::from("tests/data/test_split_sanitize");
+        create_test_file(&path, ["ES/M4", "ES:M4"])?;
+
+        // Test
+        let files = split_mbinary(&path, &dir, SplitBy::Instrument).await?;
+
+        // Validate
+        assert_eq!(
+            files,
+            vec![
+                dir.join("test_split_ES_M4.bin"),
+                dir.join("test_split_ES_M4_2.bin")
+            ]
+        );
+        for file in &files {
+            let (_, records) = read_records(file).await?;
+            assert_eq!(records.len(), 3);
+        }
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_split_by_day() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_split.bin");
+        let dir = PathBuf::from("tests/data/test_split_day");
+        create_test_file(&path, ["HE.n.0", "ZC.n.0"])?;
+
+        // Test
+        let files = split_mbinary(&path, &dir, SplitBy::Day).await?;
+
+        // Validate
+        assert_eq!(
+            files,
+            vec![
+                dir.join("test_split_2024-08-20.bin"),
+                dir.join("test_split_2024-08-21.bin")
+            ]
+        );
+        let (metadata, records) = read_records(&files[0]).await?;
+        assert_eq!(records.len(), 4);
+        assert_eq!(metadata.start, DAY);
+        assert_eq!(metadata.end, DAY + NANOS_PER_DAY);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,365 @@
+use crate::dates::parse_tz;
+use crate::error;
+use crate::error::{Error, Result};
+use crate::utils::unix_nanos_to_date;
+use crate::vendors::midas::load::read_mbinary_file;
+use crate::vendors::midas::report::ReportFormat;
+use chrono::{NaiveDate, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+use mbinary::record_enum::RecordEnum;
+use mbinary::records::Record;
+use mbinary::PRICE_SCALE;
+use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Summary of one instrument's records, prices are fixed point with `PRICE_SCALE` like the records.
+/// Prices and size come from trades, or from the bars in OHLCV files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InstrumentStats {
+    pub instrument_id: u32,
+    pub ticker: String,
+    pub records: u64,
+    pub first_ts: u64,
+    pub last_ts: u64,
+    /// Calendar days with records in `timezone`.
+    pub trading_days: usize,
+    pub timezone: String,
+    pub min_price: Option<i64>,
+    pub max_price: Option<i64>,
+    pub vwap: Option<f64>,
+    pub volume: u64,
+    /// Longest stretch in nanoseconds between consecutive records and where it started.
+    pub largest_gap: u64,
+    pub largest_gap_start: u64,
+}
+
+/// Unix nanosecond bounds of a calendar day in `tz`, None when midnight falls in a daylight saving gap.
+fn day_bounds(tz: Tz, date: NaiveDate) -> Option<(u64, u64)> {
+    let midnight = |date: NaiveDate| {
+        tz.from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()?
+            .timestamp_nanos_opt()
+    };
+    Some((midnight(date)? as u64, midnight(date.succ_opt()?)? as u64))
+}
+
+struct Accumulator {
+    stats: InstrumentStats,
+    tz: Tz,
+    days: HashSet<NaiveDate>,
+    /// Bounds of the last day added, the timezone is only converted for records outside of it.
+    day: (u64, u64),
+    notional: f64,
+}
+
+impl Accumulator {
+    fn new(instrument_id: u32, ticker: String, tz: Tz) -> Self {
+        Accumulator {
+            stats: InstrumentStats {
+                instrument_id,
+                ticker,
+                timezone: tz.to_string(),
+                ..Default::default()
+            },
+            tz,
+            days: HashSet::new(),
+            day: (0, 0),
+            notional: 0.0,
+        }
+    }
+
+    fn add(&mut self, record: &RecordEnum) {
+        let ts = record.timestamp();
+        let stats = &mut self.stats;
+
+        if stats.records == 0 {
+            stats.first_ts = ts;
+        } else if ts > stats.last_ts && ts - stats.last_ts > stats.largest_gap {
+            stats.largest_gap = ts - stats.last_ts;
+            stats.largest_gap_start = stats.last_ts;
+        }
+        stats.records += 1;
+        stats.first_ts = stats.first_ts.min(ts);
+        stats.last_ts = stats.last_ts.max(ts);
+        if ts < self.day.0 || ts >= self.day.1 {
+            let date = self.tz.timestamp_nanos(ts as i64).date_naive();
+            self.days.insert(date);
+            self.day = day_bounds(self.tz, date).unwrap_or((0, 0));
+        }
+
+        // (low, high, typical price, size)
+        let traded = match record {
+            RecordEnum::Mbp1(msg) | RecordEnum::Tbbo(msg) if msg.action as u8 == b'T' => {
+                Some((msg.price, msg.price, msg.price as f64, msg.size as u64))
+            }
+            RecordEnum::Trade(msg) => {
+                Some((msg.price, msg.price, msg.price as f64, msg.size as u64))
+            }
+            RecordEnum::Ohlcv(msg) => Some((
+                msg.low,
+                msg.high,
+                (msg.high as f64 + msg.low as f64 + msg.close as f64) / 3.0,
+                msg.volume,
+            )),
+            _ => None,
+        };
+
+        if let Some((low, high, price, size)) = traded {
+            stats.min_price = Some(stats.min_price.map_or(low, |min| min.min(low)));
+            stats.max_price = Some(stats.max_price.map_or(high, |max| max.max(high)));
+            stats.volume += size;
+            self.notional += price * size as f64;
+        }
+    }
+
+    fn finish(mut self) -> InstrumentStats {
+        self.stats.trading_days = self.days.len();
+        if self.stats.volume > 0 {
+            self.stats.vwap = Some(self.notional / self.stats.volume as f64);
+        }
+        self.stats
+    }
+}
+
+/// Streams a file once and returns per instrument statistics ordered by instrument id, trading days
+/// are counted as calendar days in the `tz` timezone ex. America/Chicago.
+pub async fn mbinary_stats(filepath: &Path, tz: &str) -> Result<Vec<InstrumentStats>> {
+    let tz = parse_tz(tz)?;
+    let mut decoder = read_mbinary_file(filepath).await?;
+    let metadata = decoder
+        .metadata()
+        .ok_or_else(|| error!(CustomError, "File {} has no metadata.", filepath.display()))?;
+    let mut instruments: BTreeMap<u32, Accumulator> = BTreeMap::new();
+
+    while let Some(record_ref) = decoder.decode_ref().await? {
+        let record = RecordEnum::from_ref(record_ref)?;
+        let instrument_id = record.header().instrument_id;
+
+        instruments
+            .entry(instrument_id)
+            .or_insert_with(|| {
+                let ticker = metadata
+                    .mappings
+                    .get_instrument_ticker(instrument_id)
+                    .unwrap_or_default();
+                Accumulator::new(instrument_id, ticker, tz)
+            })
+            .add(&record);
+    }
+
+    Ok(instruments.into_values().map(Accumulator::finish).collect())
+}
+
+fn price(value: Option<f64>) -> String {
+    value
+        .map(|p| format!("{:.4}", p / PRICE_SCALE as f64))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Formats nanoseconds as the largest whole units ex. 1d 02h 03m 04s
+fn duration(nanos: u64) -> String {
+    let seconds = nanos / 1_000_000_000;
+    let (days, hours, minutes, seconds) = (
+        seconds / 86_400,
+        seconds % 86_400 / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60,
+    );
+
+    if days > 0 {
+        format!("{}d {:02}h {:02}m {:02}s", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{:.3}s", nanos as f64 / 1e9)
+    }
+}
+
+pub fn stats_table(stats: &[InstrumentStats]) -> Result<Table> {
+    let days = match stats.first() {
+        Some(s) => format!("Days ({})", s.timezone),
+        None => "Days".to_string(),
+    };
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.add_row(Row::new(
+        [
+            "Id",
+            "Ticker",
+            "Records",
+            "First",
+            "Last",
+            days.as_str(),
+            "Min",
+            "Max",
+            "VWAP",
+            "Volume",
+            "Largest Gap",
+        ]
+        .iter()
+        .map(|title| Cell::new(title).style_spec("bFg"))
+        .collect(),
+    ));
+
+    for s in stats {
+        table.add_row(Row::new(vec![
+            Cell::new(&s.instrument_id.to_string()).style_spec("Fw"),
+            Cell::new(&s.ticker).style_spec("Fg"),
+            Cell::new(&s.records.to_string()),
+            Cell::new(&unix_nanos_to_date(s.first_ts as i64)?),
+            Cell::new(&unix_nanos_to_date(s.last_ts as i64)?),
+            Cell::new(&s.trading_days.to_string()),
+            Cell::new(&price(s.min_price.map(|p| p as f64))),
+            Cell::new(&price(s.max_price.map(|p| p as f64))),
+            Cell::new(&price(s.vwap)),
+            Cell::new(&s.volume.to_string()),
+            Cell::new(&format!(
+                "{} at {}",
+                duration(s.largest_gap),
+                unix_nanos_to_date(s.largest_gap_start as i64)?
+            )),
+        ]));
+    }
+    Ok(table)
+}
+
+pub fn render_stats(stats: &[InstrumentStats], format: ReportFormat) -> Result<String> {
+    match format {
+        ReportFormat::Text => Ok(stats_table(stats)?.to_string()),
+        ReportFormat::Json => Ok(serde_json::to_string_pretty(stats)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::NANOS_PER_DAY;
+    use mbinary::encode::CombinedEncoder;
+    use mbinary::enums::{Dataset, Schema};
+    use mbinary::metadata::Metadata;
+    use mbinary::record_ref::RecordRef;
+    use mbinary::records::{RecordHeader, TradeMsg};
+    use mbinary::symbols::SymbolMap;
+    use serial_test::serial;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    // 2024-08-20 00:00:00 UTC
+    const DAY: u64 = 1724112000000000000;
+
+    fn create_test_file(path: &Path) -> Result<()> {
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument("HE.n.0", 1);
+        let metadata = Metadata::new(Schema::Trades, Dataset::Futures, 0, 0, symbol_map);
+
+        let records: Vec<TradeMsg> = [
+            (1, DAY, 100, 1),
+            (1, DAY + 5_000_000_000, 200, 3),
+            (1, DAY + NANOS_PER_DAY, 150, 4),
+            (2, DAY, 10, 1),
+        ]
+        .iter()
+        .map(|(id, ts, price, size)| TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(*id, *ts, 0),
+            price: *price,
+            size: *size,
+            action: 84,
+            side: 66,
+            depth: 0,
+            flags: 0,
+            ts_recv: *ts,
+            ts_in_delta: 0,
+            sequence: 0,
+        })
+        .collect();
+        let refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = CombinedEncoder::new(&mut buffer);
+        encoder.encode(&metadata, &refs)?;
+        encoder.write_to_file(path, false)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mbinary_stats() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_stats.bin");
+        create_test_file(&path)?;
+
+        // Test
+        let stats = mbinary_stats(&path, "UTC").await?;
+
+        // Validate
+        assert_eq!(stats.len(), 2);
+        let he = &stats[0];
+        assert_eq!(he.ticker, "HE.n.0");
+        assert_eq!(he.records, 3);
+        assert_eq!((he.first_ts, he.last_ts), (DAY, DAY + NANOS_PER_DAY));
+        assert_eq!(he.trading_days, 2);
+        assert_eq!((he.min_price, he.max_price), (Some(100), Some(200)));
+        assert_eq!(he.volume, 8);
+        assert_eq!(he.vwap, Some(1300.0 / 8.0));
+        assert_eq!(he.largest_gap, NANOS_PER_DAY - 5_000_000_000);
+        assert_eq!(he.largest_gap_start, DAY + 5_000_000_000);
+        assert_eq!(stats[1].ticker, "");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_stats(&stats, ReportFormat::Json)?)?;
+        assert_eq!(json[0]["volume"], 8);
+        assert_eq!(json[0]["timezone"], "UTC");
+        assert!(render_stats(&stats, ReportFormat::Text)?.contains("Days (UTC)"));
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trading_days_timezone() -> Result<()> {
+        // 01:00 and 06:00 UTC fall on both sides of midnight in Chicago (UTC-5)
+        let trades: Vec<RecordEnum> = [DAY + 3_600_000_000_000, DAY + 6 * 3_600_000_000_000]
+            .iter()
+            .map(|ts| {
+                RecordEnum::Trade(TradeMsg {
+                    hd: RecordHeader::new::<TradeMsg>(1, *ts, 0),
+                    price: 100,
+                    size: 1,
+                    action: 84,
+                    side: 66,
+                    depth: 0,
+                    flags: 0,
+                    ts_recv: *ts,
+                    ts_in_delta: 0,
+                    sequence: 0,
+                })
+            })
+            .collect();
+
+        // Test
+        let days = |tz: &str| -> Result<usize> {
+            let mut accumulator = Accumulator::new(1, "HE.n.0".to_string(), parse_tz(tz)?);
+            trades.iter().for_each(|trade| accumulator.add(trade));
+            Ok(accumulator.finish().trading_days)
+        };
+
+        // Validate
+        assert_eq!(days("UTC")?, 1);
+        assert_eq!(days("America/Chicago")?, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(duration(1_500_000_000), "1.500s");
+        assert_eq!(duration(3_723_000_000_000), "1h 02m 03s");
+        assert_eq!(duration(NANOS_PER_DAY + 60_000_000_000), "1d 00h 01m 00s");
+    }
+}
@@ -0,0 +1,395 @@
+use crate::error;
+use crate::error::{Error, Result};
+use crate::vendors::midas::load::is_zstd;
+use mbinary::enums::{RType, Schema};
+use mbinary::metadata::Metadata;
+use mbinary::record_enum::RecordEnum;
+use mbinary::record_ref::RecordRef;
+use mbinary::records::{BboMsg, Mbp1Msg, OhlcvMsg, Record, RecordHeader, TbboMsg, TradeMsg};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{BufReader, ErrorKind, Read};
+use std::mem;
+use std::path::{Path, PathBuf};
+use tokio::task::JoinSet;
+use walkdir::WalkDir;
+
+/// Outcome of verifying one file, a file passes when no errors were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileVerification {
+    pub path: PathBuf,
+    pub records: u64,
+    pub errors: Vec<String>,
+    /// Checks not run because the metadata does not carry what they need.
+    pub skipped: Vec<&'static str>,
+}
+
+impl FileVerification {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for FileVerification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_ok() {
+            write!(
+                f,
+                "OK     {} ({} records)",
+                self.path.display(),
+                self.records
+            )?;
+        } else {
+            write!(
+                f,
+                "FAILED {} ({} records)",
+                self.path.display(),
+                self.records
+            )?;
+        }
+        for error in &self.errors {
+            write!(f, "\n  - {}", error)?;
+        }
+        for check in &self.skipped {
+            write!(f, "\n  skipped {}", check)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encoded size of a record type, None for unknown types.
+fn record_size(rtype: u8) -> Option<usize> {
+    match RType::try_from(rtype).ok()? {
+        RType::Mbp1 => Some(mem::size_of::<Mbp1Msg>()),
+        RType::Ohlcv => Some(mem::size_of::<OhlcvMsg>()),
+        RType::Trades => Some(mem::size_of::<TradeMsg>()),
+        RType::Tbbo => Some(mem::size_of::<TbboMsg>()),
+        RType::Bbo => Some(mem::size_of::<BboMsg>()),
+    }
+}
+
+/// Fills `buf` as far as the reader allows and returns the number of bytes read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// Walks the raw bytes of a file rather than using the decoder, which stops quietly at a truncated record.
+pub fn verify_mbinary(path: &Path) -> FileVerification {
+    let mut result = FileVerification {
+        path: path.to_path_buf(),
+        ..Default::default()
+    };
+    if let Err(e) = verify_records(path, &mut result) {
+        result.errors.push(e.to_string());
+    }
+    result
+}
+
+fn verify_records(path: &Path, result: &mut FileVerification) -> Result<()> {
+    let file = std::fs::File::open(path)?;
+    // Offsets in errors are into the decompressed bytes for .zst files
+    let mut reader: Box<dyn Read> = if is_zstd(path) {
+        Box::new(zstd::Decoder::new(file)?)
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    // Header
+    let mut length = [0u8; 2];
+    if read_full(&mut reader, &mut length)? < 2 {
+        return Err(error!(CustomError, "Header truncated."));
+    }
+    let mut header = vec![0u8; u16::from_le_bytes(length) as usize];
+    if read_full(&mut reader, &mut header)? < header.len() {
+        return Err(error!(CustomError, "Header truncated."));
+    }
+    let metadata = Metadata::deserialize(&header)
+        .map_err(|e| error!(CustomError, "Header does not decode: {}", e))?;
+
+    let check_range = metadata.start != 0 || metadata.end != 0;
+    let check_ids = !metadata.mappings.map.is_empty();
+    if !check_range {
+        result
+            .skipped
+            .push("timestamp range, metadata has no start/end");
+    }
+    if !check_ids {
+        result
+            .skipped
+            .push("instrument ids, metadata symbol map is empty");
+    }
+
+    let mut offset = 2 + header.len() as u64;
+    let mut buffer = vec![0u8; mem::size_of::<Mbp1Msg>()];
+    let mut wrong_schema = 0u64;
+    let mut out_of_range: Option<(u64, u64)> = None;
+    let mut unknown_ids = BTreeSet::new();
+
+    loop {
+        let read = read_full(&mut reader, &mut buffer[..1])?;
+        if read == 0 {
+            break;
+        }
+        let length = buffer[0] as usize * RecordHeader::LENGTH_MULTIPLIER;
+        if read_full(&mut reader, &mut buffer[1..2])? < 1 {
+            return Err(error!(
+                CustomError,
+                "Truncated record at byte {}, 1 of {} bytes present.", offset, length
+            ));
+        }
+        let rtype = buffer[1];
+        let size = record_size(rtype)
+            .ok_or_else(|| error!(CustomError, "Unknown rtype {} at byte {}.", rtype, offset))?;
+        if length != size {
+            return Err(error!(
+                CustomError,
+                "Record at byte {} has length {}, rtype {} records are {} bytes.",
+                offset,
+                length,
+                rtype,
+                size
+            ));
+        }
+        if buffer.len() < size {
+            buffer.resize(size, 0);
+        }
+        let present = 2 + read_full(&mut reader, &mut buffer[2..size])?;
+        if present < size {
+            return Err(error!(
+                CustomError,
+                "Truncated record at byte {}, {} of {} bytes present.", offset, present, size
+            ));
+        }
+
+        // Safety: `buffer` holds a complete record of the size its rtype requires.
+        let record = RecordEnum::from_ref(unsafe { RecordRef::new(&buffer[..size]) })?;
+        let ts = record.timestamp();
+        let instrument_id = record.header().instrument_id;
+
+        if !schema_rtype(metadata.schema, rtype) {
+            wrong_schema += 1;
+        }
+        if check_range && (ts < metadata.start || ts > metadata.end) {
+            out_of_range.get_or_insert((0, ts)).0 += 1;
+        }
+        if check_ids && !metadata.mappings.map.contains_key(&instrument_id) {
+            unknown_ids.insert(instrument_id);
+        }
+
+        result.records += 1;
+        offset += size as u64;
+    }
+
+    if wrong_schema > 0 {
+        result.errors.push(format!(
+            "{} records do not match the {} schema.",
+            wrong_schema, metadata.schema
+        ));
+    }
+    if let Some((count, first)) = out_of_range {
+        result.errors.push(format!(
+            "{} records outside {}..={}, first at {}.",
+            count, metadata.start, metadata.end, first
+        ));
+    }
+    if !unknown_ids.is_empty() {
+        result.errors.push(format!(
+            "Instrument ids not in the symbol map: {}.",
+            unknown_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Files to verify, a directory is searched recursively for `.bin` and `.bin.zst` files.
+fn mbinary_files(path: &PathBuf) -> Result<Vec<PathBuf>> {
+    if !path.exists() {
+        return Err(error!(FileNotFoundError, "{}", path.display()));
+    }
+    if path.is_file() {
+        return Ok(vec![path.clone()]);
+    }
+
+    let mut files: Vec<PathBuf> = WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            let name = p.to_string_lossy();
+            name.ends_with(".bin") || name.ends_with(".bin.zst")
+        })
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+/// True when a record of `rtype` belongs in a `schema` file. Tbbo records are mbp-1 messages and
+/// carry the mbp-1 rtype.
+fn schema_rtype(schema: Schema, rtype: u8) -> bool {
+    rtype == RType::from(schema) as u8 || (schema == Schema::Tbbo && rtype == RType::Mbp1 as u8)
+}
+
+/// Verifies a file, or every file in a directory in parallel, results are sorted by path.
+pub async fn verify_path(path: &PathBuf) -> Result<Vec<FileVerification>> {
+    let mut tasks = JoinSet::new();
+    for file in mbinary_files(path)? {
+        tasks.spawn_blocking(move || verify_mbinary(&file));
+    }
+
+    let mut results = Vec::with_capacity(tasks.len());
+    while let Some(result) = tasks.join_next().await {
+        results.push(result.map_err(|e| error!(CustomError, "Verification task failed: {}", e))?);
+    }
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vendors::midas::generate::{generate_mbinary, GenerateOptions};
+    use mbinary::encode::CombinedEncoder;
+    use mbinary::enums::Dataset;
+    use mbinary::symbols::SymbolMap;
+    use serial_test::serial;
+    use std::io::Write;
+
+    fn create_test_file(path: &Path, schema: Schema, end: u64) -> Result<()> {
+        let mut symbol_map = SymbolMap::new();
+        symbol_map.add_instrument("HE.n.0", 1);
+        let metadata = Metadata::new(schema, Dataset::Futures, 100, end, symbol_map);
+
+        let records: Vec<OhlcvMsg> = [(1, 100), (1, 200), (2, 300)]
+            .iter()
+            .map(|(id, ts)| OhlcvMsg {
+                hd: RecordHeader::new::<OhlcvMsg>(*id, *ts, 0),
+                open: 100,
+                high: 200,
+                low: 50,
+                close: 150,
+                volume: 1000,
+            })
+            .collect();
+        let refs: Vec<RecordRef> = records.iter().map(RecordRef::from).collect();
+
+        let mut buffer = Vec::new();
+        let mut encoder = CombinedEncoder::new(&mut buffer);
+        encoder.encode(&metadata, &refs)?;
+        encoder.write_to_file(path, false)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_verify_checks() -> Result<()> {
+        let dir = PathBuf::from("tests/data/test_verify");
+        std::fs::create_dir_all(&dir)?;
+        create_test_file(&dir.join("schema.bin"), Schema::Mbp1, 300)?;
+        create_test_file(&dir.join("range.bin"), Schema::Ohlcv1S, 250)?;
+
+        // Test
+        let results = verify_path(&dir).await?;
+
+        // Validate
+        assert_eq!(results.len(), 2);
+        let range = &results[0];
+        assert_eq!(range.records, 3);
+        assert_eq!(range.errors.len(), 2);
+        assert!(range.errors[0].contains("1 records outside 100..=250"));
+        assert!(range.errors[1].ends_with("symbol map: 2."));
+        let schema = &results[1];
+        assert!(schema.errors[0].contains("3 records do not match the mbp-1 schema"));
+
+        // Cleanup
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_verify_tbbo() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_verify_tbbo.bin");
+        let options = GenerateOptions {
+            schema: Schema::Tbbo,
+            tickers: vec!["HE.n.0".to_string()],
+            records: 100,
+            ..Default::default()
+        };
+        generate_mbinary(&options, &path).await?;
+
+        // Test
+        let result = verify_mbinary(&path);
+
+        // Validate
+        assert_eq!(result.records, 100);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_verify_truncated() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_verify_truncated.bin");
+        create_test_file(&path, Schema::Ohlcv1S, 300)?;
+        let size = std::fs::metadata(&path)?.len();
+        let file = std::fs::OpenOptions::new().write(true).open(&path)?;
+        file.set_len(size - 5)?;
+
+        // Test
+        let result = verify_mbinary(&path);
+
+        // Validate
+        assert!(!result.is_ok());
+        assert_eq!(result.records, 2);
+        assert!(result.errors[0].starts_with("Custom error: Truncated record"));
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_verify_bad_length() -> Result<()> {
+        let path = PathBuf::from("tests/data/test_verify_length.bin");
+        create_test_file(&path, Schema::Ohlcv1S, 300)?;
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+        file.write_all(&[2, RType::Mbp1 as u8, 0, 0, 0, 0, 0, 0])?;
+
+        // Test
+        let result = verify_mbinary(&path);
+
+        // Validate
+        assert_eq!(result.records, 3);
+        assert!(result.errors[0].contains("has length 8, rtype 1 records are"));
+
+        // Cleanup
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
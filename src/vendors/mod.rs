@@ -34,6 +34,7 @@ pub trait Vendor {
         hist_client: &Historical,
         instrument_client: &Instruments,
         download_approval: bool,
+        compress: bool,
     ) -> Result<()>;
 
     /// Transforms data from vendor format to mbinary format, saves to staging file.
@@ -46,7 +47,8 @@ pub trait Vendor {
         env_dirs: bool,
     ) -> Result<PathBuf>;
 
-    /// Transforms data from vendor format to mbinary format, saves to staging file.
+    /// Transforms data from vendor format to mbinary format, saves to staging file. Staging files
+    /// are zstd compressed with `compress`.
     async fn stage(
         &self,
         dataset: Dataset,
@@ -54,8 +56,10 @@ pub trait Vendor {
         dbn_path: &PathBuf,
         mbinary_filename: &PathBuf,
         instrument_client: &Instruments,
+        compress: bool,
     ) -> Result<Vec<PathBuf>>;
 
-    /// Loads to database, deletes staging file after.
+    /// Loads to database, deletes staging file after. Compressed staging files are decompressed
+    /// first as the server reads plain mbinary.
     async fn upload(&self, client: &Historical, files: Vec<PathBuf>) -> Result<()>;
 }
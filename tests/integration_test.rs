@@ -14,6 +14,7 @@ use midas_clilib::instrument::InstrumentCommands;
 use midas_clilib::instrument::{CreateArgs, DeleteArgs, GetArgs, UpdateArgs};
 use midas_clilib::midas::MidasCommands;
 use midas_clilib::strategies::StrategyCommands;
+use midas_clilib::vendors::midas::report::DEFAULT_MAX_EXAMPLES;
 use midas_clilib::TaskManager;
 use midas_clilib::{self};
 use serial_test::serial;
@@ -46,6 +47,8 @@ async fn create_test_ticker(ticker: &str) -> Result<()> {
         expiration_date: "2025-01-27".to_string(),
         is_continuous: false,
         active: true,
+        tz: None,
+        expiry_tz: "America/New_York".to_string(),
     };
 
     // Command
@@ -92,6 +95,8 @@ async fn test_create_instrument() -> Result<()> {
         expiration_date: "2025-01-27".to_string(),
         is_continuous: false,
         active: true,
+        tz: None,
+        expiry_tz: "America/New_York".to_string(),
     };
     let command = InstrumentCommands::Create(create_args);
     let context = Context::init()?;
@@ -115,6 +120,18 @@ async fn test_get_all_instruments() -> Result<()> {
     let get_args = GetArgs {
         dataset: dataset.as_str().to_string(),
         vendor: None,
+        sort: None,
+        desc: false,
+        columns: None,
+        json: false,
+        ticker: None,
+        regex: false,
+        active: None,
+        continuous: None,
+        expiring_before: None,
+        expiring_after: None,
+        stale: None,
+        tz: None,
     };
 
     let command = InstrumentCommands::Get(get_args);
@@ -141,6 +158,18 @@ async fn test_get_instrument_by_vendor() -> Result<()> {
     let get_args = GetArgs {
         dataset: dataset.as_str().to_string(),
         vendor: Some(vendor.as_str().to_string()),
+        sort: None,
+        desc: false,
+        columns: None,
+        json: false,
+        ticker: None,
+        regex: false,
+        active: None,
+        continuous: None,
+        expiring_before: None,
+        expiring_after: None,
+        stale: None,
+        tz: None,
     };
 
     let command = InstrumentCommands::Get(get_args);
@@ -181,6 +210,8 @@ async fn test_update_instrument() -> Result<()> {
         expiration_date: "2025-01-27".to_string(),
         is_continuous: false,
         active: false,
+        tz: None,
+        expiry_tz: "America/New_York".to_string(),
     };
 
     let command = InstrumentCommands::Update(args);
@@ -575,6 +606,7 @@ async fn test_databento_upload(dataset: &Dataset) -> Result<()> {
         dbn_filepath:"GLBX.MDP3_mbp-1_HEG4_HEJ4_LEG4_LEJ4_LEM4_HEM4_HEK4_2024-02-09T00:00:00Z_2024-02-17T00:00:00Z.dbn".to_string(),
         dbn_downloadtype: "stream".to_string(),
         midas_filepath: "system_tests_data.bin".to_string(),
+        compress: false,
     };
 
     upload_cmd.process_command(task_manager).await?;
@@ -632,6 +664,13 @@ async fn test_get_records_continuous(dataset: &Dataset) -> Result<()> {
             dataset: dataset.as_str().to_string(),
             stype: stype.as_str().to_string(),
             file_path,
+            format: "mbinary".to_string(),
+            tz: None,
+            plot: None,
+            no_cache: true,
+            refresh: false,
+            chunk: "1d".to_string(),
+            concurrency: 4,
         };
 
         historical_command.process_command(task_manager).await?;
@@ -683,6 +722,13 @@ async fn test_get_records_raw(dataset: &Dataset) -> Result<()> {
             dataset: dataset.as_str().to_string(),
             stype: stype.as_str().to_string(),
             file_path,
+            format: "mbinary".to_string(),
+            tz: None,
+            plot: None,
+            no_cache: true,
+            refresh: false,
+            chunk: "1d".to_string(),
+            concurrency: 4,
         };
 
         historical_command.process_command(task_manager).await?;
@@ -724,6 +770,10 @@ async fn test_compare_files_continuous() -> Result<()> {
         let compare_command = DatabentoCommands::Compare {
             dbn_filepath,
             midas_filepath: mbinary_filepath,
+            output: "compare_results.txt".to_string(),
+            format: "text".to_string(),
+            tolerances: None,
+            max_examples: DEFAULT_MAX_EXAMPLES,
         };
 
         compare_command.process_command(task_manager).await?;
@@ -767,6 +817,10 @@ async fn test_compare_files_raw() -> Result<()> {
         let compare_command = DatabentoCommands::Compare {
             dbn_filepath,
             midas_filepath: mbinary_filepath,
+            output: "compare_results.txt".to_string(),
+            format: "text".to_string(),
+            tolerances: None,
+            max_examples: DEFAULT_MAX_EXAMPLES,
         };
 
         compare_command.process_command(task_manager).await?;
@@ -796,6 +850,7 @@ async fn test_databento_transform() -> anyhow::Result<()> {
         dataset: dataset.as_str().to_string(),
         dbn_filepath: dbn_filepath.to_string(),
         midas_filepath: mbinary_filepath.to_string(),
+        compress: false,
     };
 
     let context = Context::init()?;
@@ -805,6 +860,8 @@ async fn test_databento_transform() -> anyhow::Result<()> {
     // Check duplicates
     let duplicatecheck_cmd = MidasCommands::Duplicates {
         filepath: mbinary_filepath.to_string(),
+        window: 1_000_000_000,
+        write_deduped: None,
     };
 
     let context = Context::init()?;
@@ -815,6 +872,10 @@ async fn test_databento_transform() -> anyhow::Result<()> {
     let compare_cmd = DatabentoCommands::Compare {
         dbn_filepath: dbn_filepath.to_string(),
         midas_filepath: mbinary_filepath.to_string(),
+        output: "compare_results.txt".to_string(),
+        format: "text".to_string(),
+        tolerances: None,
+        max_examples: DEFAULT_MAX_EXAMPLES,
     };
 
     let context = Context::init()?;
@@ -845,6 +906,7 @@ async fn test_update_databento() -> Result<()> {
     let update_cmd = DatabentoCommands::Update {
         dataset: dataset.as_str().to_string(),
         approval: true,
+        compress: false,
     };
 
     let context = Context::init()?;
@@ -876,6 +938,7 @@ async fn test_databento_download() -> Result<()> {
         stype: "continuous".to_string(),
         approval: true,
         dir_path: None,
+        tz: None,
     };
 
     let context = Context::init()?;
@@ -892,6 +955,7 @@ async fn test_databento_download() -> Result<()> {
         stype: "continuous".to_string(),
         approval: true,
         dir_path: None,
+        tz: None,
     };
 
     let context = Context::init()?;
@@ -908,6 +972,7 @@ async fn test_databento_download() -> Result<()> {
         stype: "continuous".to_string(),
         approval: true,
         dir_path: None,
+        tz: None,
     };
 
     let context = Context::init()?;
@@ -924,6 +989,7 @@ async fn test_databento_download() -> Result<()> {
         stype: "continuous".to_string(),
         approval: true,
         dir_path: None,
+        tz: None,
     };
 
     let context = Context::init()?;
@@ -940,6 +1006,7 @@ async fn test_databento_download() -> Result<()> {
         stype: "continuous".to_string(),
         approval: true,
         dir_path: None,
+        tz: None,
     };
 
     let context = Context::init()?;